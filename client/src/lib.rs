@@ -6,7 +6,15 @@ extern crate log;
 use log::{debug, error, info, trace, warn};
 
 pub use rx;
-use rx::ecs::{Render, Transformation, Velocity, ViewProjection, WinitEvents};
+use rx::ecs::{Camera, Children, CullingStats, Light, Material, Name, Parent, Render, RenderLayers, Transform, Transformation, Velocity, ViewProjection, WinitEvents, WorldBounds};
+use rx::glm;
+use rx::debug_draw::DebugDraw;
+use rx::graphics::post::PostEffect;
+use rx::prefab::{PrefabHooks, Prefabs, spawn_prefab};
+use rx::scene::{SceneAssets, SceneEntity};
+use rx::skybox::SkyboxSource;
+use rx::text::{Text, TextQueue};
+use rx::ui::Ui;
 use rx::ecs::layer::EcsInitTuple;
 use rx::specs::Builder;
 use rx::specs::WorldExt;
@@ -58,13 +66,60 @@ pub fn start() {
         let mesh = map::generate2d();
         storage.load_mesh(api, mesh).expect("")
     };
+    let screen_mesh_ptr = {
+        let (api, _, storage) = eng.loader();
+        storage.load_mesh(api, map::screen(40., 30.)).expect("")
+    };
+    let font = {
+        let (_, loader, _) = eng.loader();
+        loader.as_ref().map(|loader| loader.load_font("font"))
+    };
+    match font {
+        Some(Ok(ttf)) => eng.renderer_mut().load_font(ttf, 32.).unwrap_or_else(|e| warn!("{}", e)),
+        Some(Err(e)) => warn!("No font, text is disabled: {}", e),
+        None => (),
+    }
+    eng.renderer_mut().set_clear_color(glm::vec4(0.45, 0.6, 0.8, 1.));
+    let sky = {
+        let (_, loader, _) = eng.loader();
+        loader.as_ref().and_then(|loader| loader.load_img("skybox").ok())
+    };
+    if let Some(sky) = sky {
+        eng.renderer_mut().set_skybox(Some(SkyboxSource::Equirectangular(sky)))
+            .unwrap_or_else(|e| warn!("{}", e));
+    }
+    eng.renderer_mut().set_post_effects(vec![
+        PostEffect::bloom(1., 0.5, 6.),
+        PostEffect::tone_map(1.),
+        PostEffect::fxaa(),
+        PostEffect::vignette(0.4, 0.6),
+    ]);
+    let samples = eng.renderer_mut().set_msaa(4).expect("");
+    info!("MSAA: {}x", samples);
+    let screen_target = eng.renderer_mut().create_render_target(320, 240).expect("");
+    let mut scene_assets = SceneAssets::default();
+    scene_assets.add_mesh("map", map_mesh_ptr);
+    scene_assets.add_mesh("screen", screen_mesh_ptr.clone());
+    scene_assets.add_target("screen", screen_target);
+    let (prefabs, level) = {
+        let (_, loader, _) = eng.loader();
+        (
+            loader.as_ref().map(|loader| loader.load_prefabs("demo")),
+            loader.as_ref().map(|loader| loader.load_scene("level")),
+        )
+    };
     let (draw, redner) = eng.renderer().queue();
+    let render_stats = eng.renderer().stats_handle();
 
+    let debug_sys = systems::generic::DebugModeSystem::new(redner.clone());
     let render_sys = systems::generic::RenderSubmitSystem::new(draw, redner);
     let input_sys = systems::test::InputTestSystem::default();
     let move_sys = systems::test::MoveSystem;
     let mouse_sys = systems::test::MoveClickSystem::default();
     let transform_sys = systems::generic::TransformationSystem;
+    let viewport_sys = systems::generic::CameraViewportSystem;
+    let stats_sys = systems::generic::StatsOverlaySystem;
+    let save_sys = systems::generic::SceneSaveSystem::new("saved.ron".into());
 
     let ecs_layer = rx::ecs::layer::EcsLayer::new(move |(mut world, mut r_dispatcher, mut c_dispatcher): EcsInitTuple<'static>| {
         use rx::ecs::{
@@ -72,7 +127,10 @@ pub fn start() {
             Position,
             Rotation,
             ActiveCamera,
+            CameraObstacle,
             CameraTarget,
+            OrthoCamera,
+            FlyCamera,
         };
 
 
@@ -81,66 +139,99 @@ pub fn start() {
         world.register::<Position>();
         world.register::<Velocity>();
         world.register::<Transformation>();
+        world.register::<Transform>();
+        world.register::<Parent>();
+        world.register::<Children>();
         world.register::<TargetCamera>();
+        world.register::<OrthoCamera>();
+        world.register::<FlyCamera>();
+        world.register::<CameraObstacle>();
         world.register::<Follower>();
+        world.register::<Light>();
+        world.register::<Material>();
+        world.register::<WorldBounds>();
+        world.register::<Text>();
+        world.register::<Camera>();
+        world.register::<RenderLayers>();
+        world.register::<Name>();
+        world.insert(scene_assets);
+        //built-in prefabs, files may add to or replace them
+        let mut all_prefabs = Prefabs::default();
+        all_prefabs.add("follower", SceneEntity {
+            position: Some([0.; 3]),
+            rotation: Some([0.; 3]),
+            velocity: Some([0.; 3]),
+            ..Default::default()
+        });
+        match prefabs {
+            Some(Ok(prefabs)) => all_prefabs.extend(prefabs),
+            Some(Err(e)) => warn!("No prefabs: {}", e),
+            None => (),
+        }
+        world.insert(all_prefabs);
 
         let player = world.create_entity()
+            .with(Name("player".to_owned()))
             .with(Rotation::default())
             .with(Position::default())
             .with(Velocity::default())
             .with(Transformation::default())
+            .with(Text::world(&glm::vec3(0., 3., 0.), "player", 16., &glm::vec4(1., 1., 0., 1.)))
 //            .with(Render {
 //                mesh: ico_mesh.clone()
 //            })
             .build();
+        //followers of any prefab based on "follower" chase the player
+        let mut hooks = PrefabHooks::default();
+        hooks.add("follower", move |follower, world| {
+            world.write_storage::<Follower>().insert(follower, Follower { lead: player })
+                .expect("not able to insert follower");
+        });
+        world.insert(hooks);
+        //plate floating above the player, follows it through `Parent`
         world.create_entity()
-            .with(Rotation::default())
-            .with(Position {
-                x: -100.,
-                y: -5.,
-                z: -100.
-            })
-
+            .with(Transform::from_translation(glm::vec3(0., 12., 0.)).with_scale(glm::vec3(0.2, 0.2, 0.2)))
+            .with(Parent(player))
             .with(Transformation::default())
             .with(Render {
-                mesh: map_mesh_ptr.clone()
+                mesh: screen_mesh_ptr.clone()
             })
+            .with(Material::color(1., 0.8, 0.2))
             .build();
-
         for e in 1..20 {
-            let _ = world.create_entity()
-                .with(Rotation::default())
-                .with(Position {
-                    x: e as f32 * 10. *  {if e % 2 == 0 { -1. } else { 1. } },
-                    y: 0.0,
-                    z: 0.0,
-                })
-                .with(Transformation::default())
-//            .with(Render {
-//                mesh: {
-//                    if e % 2 == 0 {
-//                        ico_mesh.clone()
-//                    } else {
-//                        tetrahedron_mesh.clone()
-//                    }
-//                }
-//            })
-                .with(Follower {
-                    lead: player
-                })
-                .with(Velocity::default())
-            .build();
+            let overrides = SceneEntity {
+                position: Some([e as f32 * 10. * if e % 2 == 0 { -1. } else { 1. }, 0., 0.]),
+                ..Default::default()
+            };
+            if let Err(e) = spawn_prefab(&mut world, "follower", overrides) {
+                warn!("{}", e);
+            }
         }
 
+        let mut orbit = TargetCamera::default();
+        orbit.collision_margin = Some(2.);
         let cam_entity = world.create_entity()
-            .with(TargetCamera::default())
+            .with(Name("camera".to_owned()))
+            .with(orbit)
+            .with(Camera::default())
             .build();
-
+        match level {
+            Some(Ok(level)) => if let Err(e) = level.spawn(&mut world) {
+                warn!("{}", e);
+            },
+            Some(Err(e)) => warn!("No level scene: {}", e),
+            None => (),
+        }
 
         world.insert(ActiveCamera(Some(cam_entity)));
         world.insert(CameraTarget(Some(player)));
         world.insert(WinitEvents::default());
         world.insert(ViewProjection::default());
+        world.insert(CullingStats::default());
+        world.insert(DebugDraw::default());
+        world.insert(Ui::default());
+        world.insert(TextQueue::default());
+        world.insert(render_stats);
 
         r_dispatcher = r_dispatcher
             .with(systems::test::FollowingSystem, "follow_sys", &[])
@@ -148,7 +239,11 @@ pub fn start() {
             .with(input_sys, "in_tst_sys", &[])
             .with(move_sys, "move_sys", &[])
             .with(mouse_sys, "mouse_sys", &[])
-            .with(transform_sys, "tsm_sys", &[]);
+            .with(debug_sys, "debug_sys", &[])
+            .with(stats_sys, "stats_sys", &[])
+            .with(viewport_sys, "viewport_sys", &[])
+            .with(transform_sys, "tsm_sys", &["viewport_sys"])
+            .with_thread_local(save_sys);
         c_dispatcher = c_dispatcher
             .with_thread_local(render_sys);
         return (world, r_dispatcher, c_dispatcher);
@@ -100,4 +100,30 @@ pub fn generate2d() -> Mesh {
         normals,
         indices,
     }
-}
\ No newline at end of file
+}
+///Upright quad facing +z with full texture on it, centered at origin
+pub fn screen(width: f32, height: f32) -> Mesh {
+    let (w, h) = (width / 2., height / 2.);
+    Mesh {
+        //lu, ld, ru, rd
+        positions: vec![
+            -w, h, 0.,
+            -w, -h, 0.,
+            w, h, 0.,
+            w, -h, 0.,
+        ],
+        uvs: vec![
+            0., 0.,
+            0., 1.,
+            1., 0.,
+            1., 1.,
+        ],
+        normals: vec![
+            0., 0., 1.,
+            0., 0., 1.,
+            0., 0., 1.,
+            0., 0., 1.,
+        ],
+        indices: vec![1, 0, 2, 1, 2, 3],
+    }
+}
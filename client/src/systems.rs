@@ -5,6 +5,7 @@ pub mod test {
     use log::{debug, error, info, trace, warn};
 
     use rx::ecs::{ActiveCamera, CameraTarget, Position, Render, Rotation, TargetCamera, Transformation, Velocity, ViewProjection, WinitEvents};
+    use rx::debug_draw::DebugDraw;
     use rx::events::MyEvent;
     use rx::glm;
     use rx::glm::{Vec2, Vec3};
@@ -27,10 +28,11 @@ pub mod test {
         type SystemData = (
             Read<'a, ViewProjection>,
             Read<'a, WinitEvents>,
+            Write<'a, DebugDraw>,
         );
 
         fn run(&mut self, data: Self::SystemData) {
-            let (vp, events) = data;
+            let (vp, events, mut debug_draw) = data;
 
             for e in &events.0 {
                 match e {
@@ -64,6 +66,13 @@ pub mod test {
             let mut ray_wor = glm::vec4_to_vec3(&ray_wor);
             let ray_wor = glm::normalize(&ray_wor);
 
+            debug_draw.axes(&glm::vec3(0., 0., 0.), 20.);
+            //no size before first resize event
+            if self.w > 0 && self.h > 0 {
+                let origin = glm::vec4_to_vec3(&(&i_view * glm::vec4(0., 0., 0., 1.)));
+                debug_draw.line(&origin, &(origin + ray_wor * 500.), &glm::vec4(1., 1., 0., 1.));
+            }
+
 //            float x = (2.0f * mouse_x) / width - 1.0f;
 //            float y = 1.0f - (2.0f * mouse_y) / height;
 //            float z = 1.0f;
@@ -152,7 +161,6 @@ pub mod test {
             ) = data;
 
             let events = &events.0;
-            let cam = camera.get_mut(active.0.unwrap()).unwrap();
             let pos = position.get_mut(target.0.unwrap()).unwrap();
 
             let mut accum_delta = (0.0, 0.0);
@@ -255,18 +263,18 @@ pub mod test {
                             ElementState::Released => false,
                         }
                     },
-                    MyEvent::Resized(w, h) => cam.update_aspect(*w as f32 / *h as f32),
                     _ => ()
                 };
             }
 
 
-            cam.distance += 0.4 * accum_dist;
-            cam.yaw += 0.4 * accum_delta.0 as f32;
-            cam.pitch -= 0.4 * accum_delta.1 as f32;
+            //other camera kinds are driven elsewhere, movement then goes along world axes
+            let mut degree = 0.;
+            if let Some(cam) = camera.get_mut(active.0.unwrap()) {
+                cam.orbit(0.4 * accum_delta.0 as f32, -0.4 * accum_delta.1 as f32, 0.4 * accum_dist);
 
-
-            let mut degree = cam.yaw - 180.;
+                degree = cam.yaw - 180.;
+            }
 
             let mut d_vec: Vec2 = self.pad.as_vec2();
             if self.pad.is_active() {
@@ -335,12 +343,27 @@ pub mod test {
 }
 
 pub mod generic {
+    use std::path::PathBuf;
     use std::sync::mpsc::Sender;
 
-    use rx::ecs::{ActiveCamera, CameraTarget, Position, Render, Rotation, TargetCamera, Transformation, ViewProjection};
+    #[allow(unused_imports)]
+    use log::{debug, error, info, trace, warn};
+    use rx::assets::MeshPtr;
+    use rx::culling::{Aabb, Frustum};
+    use rx::debug_draw::DebugDraw;
+    use rx::ecs::{ActiveCamera, Camera, camera_of, CameraObstacle, CameraTarget, CameraView, Children, CullingStats, FlyCamera, Light, LightKind, Material, OrthoCamera, Parent, Position, Render, RenderLayers, Rotation, TargetCamera, Transform, Transformation, ViewProjection, WinitEvents, WorldBounds};
+    use rx::ecs::layer::DURATION_PER_UPD;
+    use rx::events::MyEvent;
     use rx::glm;
-    use rx::render::{DrawCmd, RenderCommand};
-    use rx::specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+    use rx::glm::Mat4;
+    use rx::graphics::draw::{DebugMode, LAYER_DEFAULT, View};
+    use rx::graphics::shadow::CASTER_DISTANCE;
+    use rx::render::{DrawCmd, RenderCommand, SharedRenderStats};
+    use rx::scene::Scene;
+    use rx::text::{Text, TextAnchor, TextQueue};
+    use rx::ui::Ui;
+    use rx::winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+    use rx::specs::{Entities, Entity, Join, Read, ReadStorage, RunNow, System, World, Write, WriteStorage};
 
     pub struct RenderSubmitSystem {
         send_draw: Sender<DrawCmd>,
@@ -358,24 +381,258 @@ pub mod generic {
 
     impl<'a> System<'a> for RenderSubmitSystem {
         type SystemData = (
+            Entities<'a>,
             Read<'a, ActiveCamera>,
             ReadStorage<'a, TargetCamera>,
+            ReadStorage<'a, OrthoCamera>,
+            ReadStorage<'a, FlyCamera>,
+            ReadStorage<'a, Camera>,
             ReadStorage<'a, Transformation>,
-            WriteStorage<'a, Render>
+            ReadStorage<'a, Light>,
+            ReadStorage<'a, Position>,
+            ReadStorage<'a, Material>,
+            ReadStorage<'a, WorldBounds>,
+            ReadStorage<'a, RenderLayers>,
+            WriteStorage<'a, Render>,
+            Write<'a, CullingStats>,
+            Read<'a, DebugDraw>,
+            Read<'a, Ui>,
+            ReadStorage<'a, Text>,
+            Read<'a, TextQueue>,
         );
 
 
-        fn run(&mut self, (active, camera, transformation, mut render): Self::SystemData) {
-            let cam = camera.get(active.0.unwrap()).unwrap();
-            self.send_render.send(RenderCommand::PushView(cam.view.clone()));
+        fn run(&mut self, (entities, active, target_camera, ortho_camera, fly_camera, camera, transformation, light, position, material, bounds, layers, mut render, mut stats, debug_draw, ui, text, text_queue): Self::SystemData) {
+            let mut views: Vec<(i32, View)> = (&entities, &camera).join()
+                .filter_map(|(e, c)| {
+                    camera_of(e, &target_camera, &ortho_camera, &fly_camera).map(|cam| (c.order, c.view_of(cam)))
+                })
+                .collect();
+            //without `Camera` components active one fills the screen
+            if views.is_empty() {
+                let cam = camera_of(active.0.unwrap(), &target_camera, &ortho_camera, &fly_camera).unwrap();
+                views.push((0, View::full(cam.view(), cam.projection(), cam.eye())));
+            }
+            views.sort_by_key(|(order, _)| *order);
+            for (_, view) in views.iter() {
+                self.send_render.send(RenderCommand::PushCamera(view.clone()))
+                    .expect("not able to submit");
+            }
+            for (light, pos) in (&light, position.maybe()).join() {
+                let position = pos.map_or(glm::vec3(0., 0., 0.), |p| glm::vec3(p.x, p.y, p.z));
+                self.send_render.send(RenderCommand::PushLight {
+                    position,
+                    light: light.clone(),
+                }).expect("not able to submit");
+            }
+            self.send_render.send(RenderCommand::PushState)
+                .expect("not able to submit");
+            self.send_render.send(RenderCommand::DebugLines(debug_draw.vertices().to_vec()))
+                .expect("not able to submit");
+            self.send_render.send(RenderCommand::Ui(ui.frame().clone()))
+                .expect("not able to submit");
+            let mut texts = text_queue.texts().to_vec();
+            for (text, transformation) in (&text, transformation.maybe()).join() {
+                let mut text = text.clone();
+                if let (TextAnchor::World(offset), Some(t)) = (text.anchor, transformation) {
+                    text.anchor = TextAnchor::World(glm::vec4_to_vec3(&(t.model * glm::vec4(offset.x, offset.y, offset.z, 1.))));
+                }
+                texts.push(text);
+            }
+            self.send_render.send(RenderCommand::Text(texts))
+                .expect("not able to submit");
+
+            let frustums: Vec<Frustum> = views.iter()
+                .map(|(_, view)| Frustum::from_view_proj(&(view.proj * view.view)))
+                .collect();
+            //renderer fits cascades of the first directional light to the first screen view
+            let main_view = views.iter().position(|(_, view)| view.target.is_none());
+            let light_direction = (&light).join()
+                .filter_map(|l| match l.kind {
+                    LightKind::Directional { direction } => Some(glm::normalize(&direction)),
+                    _ => None,
+                })
+                .next();
+            let shadowed = match (main_view, light_direction) {
+                (Some(v), Some(direction)) => Some((&frustums[v], direction * CASTER_DISTANCE)),
+                _ => None,
+            };
+            *stats = CullingStats::default();
+            for (transformation, render, material, bounds, layers) in (&transformation, &mut render, material.maybe(), bounds.maybe(), layers.maybe()).join() {
+                let material = material.cloned().unwrap_or_default();
+                //entities without bounds yet are always drawn
+                let visible = bounds.map_or(true, |b| frustums.iter().any(|f| f.intersects(&b.0)));
+                //hidden opaque ones may still shadow what's in view, renderer culls them per cascade
+                let caster = || match (bounds, shadowed) {
+                    (Some(b), Some((frustum, reach))) => !material.transparent && frustum.intersects(&b.0.swept(&reach)),
+                    _ => false,
+                };
+                if !visible && !caster() {
+                    stats.culled += 1;
+                    continue;
+                }
+                stats.drawn += 1;
+                let layers = layers.map_or(LAYER_DEFAULT, |l| l.0);
+                self.send_draw.send((render.mesh.clone(), transformation.model, material, layers))
+                    .expect("not able to submit");
+            }
+        }
+    }
+
+    ///Cycles renderer `DebugMode` on F1 or with on-screen button
+    pub struct DebugModeSystem {
+        send_render: Sender<RenderCommand>,
+        mode: DebugMode,
+    }
+
+    impl DebugModeSystem {
+        pub fn new(send_render: Sender<RenderCommand>) -> Self {
+            Self {
+                send_render,
+                mode: DebugMode::default(),
+            }
+        }
+    }
+
+    impl<'a> System<'a> for DebugModeSystem {
+        type SystemData = (
+            Read<'a, WinitEvents>,
+            Write<'a, Ui>,
+        );
 
-            for (transformation, render) in (&transformation, &mut render).join() {
-                self.send_draw.send((render.mesh.clone(), transformation.mvp, transformation.model))
+        fn run(&mut self, (events, mut ui): Self::SystemData) {
+            let mut cycle = false;
+            for event in &events.0 {
+                if let MyEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F1),
+                        ..
+                    },
+                    ..
+                } = event {
+                    cycle = true;
+                }
+            }
+            ui.panel(10., 10., 120., 40., &glm::vec4(0.1, 0.1, 0.1, 0.6));
+            cycle |= ui.button(20., 20., 100., 20., &glm::vec4(0.3, 0.5, 0.8, 1.));
+            if cycle {
+                self.mode = self.mode.next();
+                self.send_render.send(RenderCommand::SetDebugMode(self.mode))
                     .expect("not able to submit");
             }
         }
     }
 
+    ///FPS and counters of the last frame below debug mode button
+    pub struct StatsOverlaySystem;
+
+    impl<'a> System<'a> for StatsOverlaySystem {
+        type SystemData = (
+            Read<'a, SharedRenderStats>,
+            Read<'a, CullingStats>,
+            Write<'a, TextQueue>,
+        );
+
+        fn run(&mut self, (render_stats, culling, mut texts): Self::SystemData) {
+            let stats = match render_stats.lock() {
+                Ok(stats) => stats.clone(),
+                Err(_) => return,
+            };
+            let frame = stats.frame_time.as_secs_f32();
+            let fps = if frame > 0. { 1. / frame } else { 0. };
+            let gpu = stats.gpu_time.map_or("-".to_owned(), |t| format!("{:.2}ms", t.as_secs_f32() * 1000.));
+            texts.screen(
+                10.,
+                60.,
+                &format!(
+                    "FPS: {:.0}\ndraws: {}\ntris: {}\nculled: {}/{}\ngpu: {}",
+                    fps, stats.draw_calls, stats.triangles, culling.culled, culling.drawn + culling.culled, gpu
+                ),
+                18.,
+                &glm::vec4(1., 1., 1., 1.),
+            );
+        }
+    }
+
+    ///Keeps camera projections in aspect of their viewports
+    pub struct CameraViewportSystem;
+
+    impl CameraViewportSystem {
+        fn fit(cam: &mut dyn CameraView, c: Option<&Camera>, resized: Option<(u32, u32)>) {
+            match (c, resized) {
+                //offscreen ones don't follow window, but may have just got their target
+                (Some(Camera { target: Some(target), viewport, .. }), _) => {
+                    cam.update_aspect(viewport.aspect(target.extent()))
+                }
+                (Some(c), Some(size)) => cam.update_aspect(c.aspect(size)),
+                (None, Some((w, h))) => cam.update_aspect(w as f32 / h as f32),
+                _ => (),
+            }
+        }
+    }
+
+    impl<'a> System<'a> for CameraViewportSystem {
+        type SystemData = (
+            Read<'a, WinitEvents>,
+            WriteStorage<'a, TargetCamera>,
+            WriteStorage<'a, OrthoCamera>,
+            WriteStorage<'a, FlyCamera>,
+            ReadStorage<'a, Camera>,
+        );
+
+        fn run(&mut self, (events, mut target_camera, mut ortho_camera, mut fly_camera, camera): Self::SystemData) {
+            let resized = events.0.iter()
+                .filter_map(|event| match event {
+                    MyEvent::Resized(w, h) => Some((*w, *h)),
+                    _ => None,
+                })
+                .last();
+            for (cam, c) in (&mut target_camera, camera.maybe()).join() {
+                Self::fit(cam, c, resized);
+            }
+            for (cam, c) in (&mut ortho_camera, camera.maybe()).join() {
+                Self::fit(cam, c, resized);
+            }
+            for (cam, c) in (&mut fly_camera, camera.maybe()).join() {
+                Self::fit(cam, c, resized);
+            }
+        }
+    }
+
+    ///Saves the world to `path` on F5
+    pub struct SceneSaveSystem {
+        path: PathBuf,
+    }
+
+    impl SceneSaveSystem {
+        pub fn new(path: PathBuf) -> Self {
+            Self { path }
+        }
+    }
+
+    //capture reads every scene storage, so it runs on the world directly
+    impl<'a> RunNow<'a> for SceneSaveSystem {
+        fn run_now(&mut self, world: &'a World) {
+            let save = world.read_resource::<WinitEvents>().0.iter().any(|event| match event {
+                MyEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F5),
+                        ..
+                    },
+                    ..
+                } => true,
+                _ => false,
+            });
+            if save {
+                Scene::capture(world).save(&self.path).unwrap_or_else(|e| warn!("{}", e));
+            }
+        }
+
+        fn setup(&mut self, _world: &mut World) {}
+    }
+
     pub struct TransformationSystem;
 
     impl<'a> System<'a> for TransformationSystem {
@@ -383,10 +640,19 @@ pub mod generic {
             Read<'a, ActiveCamera>,
             Read<'a, CameraTarget>,
             WriteStorage<'a, TargetCamera>,
+            WriteStorage<'a, OrthoCamera>,
+            WriteStorage<'a, FlyCamera>,
             ReadStorage<'a, Rotation>,
             ReadStorage<'a, Position>,
+            ReadStorage<'a, Transform>,
+            ReadStorage<'a, Parent>,
+            WriteStorage<'a, Children>,
             WriteStorage<'a, Transformation>,
             Write<'a, ViewProjection>,
+            Entities<'a>,
+            ReadStorage<'a, Render>,
+            WriteStorage<'a, WorldBounds>,
+            ReadStorage<'a, CameraObstacle>,
         );
 
         fn run(&mut self, data: Self::SystemData) {
@@ -394,41 +660,104 @@ pub mod generic {
                 active_camera,
                 camera_target,
                 mut camera,
+                mut ortho_camera,
+                mut fly_camera,
                 rot,
                 pos,
+                transform,
+                parent,
+                mut children,
                 mut tsm,
-                mut vp_e
+                mut vp_e,
+                entities,
+                render,
+                mut bounds,
+                obstacle,
             ) = data;
 
-            let target_pos = pos.get(camera_target.0.unwrap()).unwrap();
-            let target_rot = rot.get(camera_target.0.unwrap()).unwrap();
-            let cam = camera.get_mut(active_camera.0.unwrap()).unwrap();
+            //`Position` and `Rotation` matrix, relative to `Parent` if there's one
+            let euler = |e: Entity| -> Option<Mat4> {
+                let (pos, rot) = (pos.get(e)?, rot.get(e)?);
+                let mut mtx = glm::translation(&glm::vec3(pos.x, pos.y, pos.z));
+                mtx = glm::rotate_y(&mtx, rot.y.to_radians());
+                mtx = glm::rotate_x(&mtx, rot.x.to_radians());
+                Some(glm::rotate_z(&mtx, rot.z.to_radians()))
+            };
+            for (e, tsm, _) in (&entities, &mut tsm, !&transform).join() {
+                if let Some(model) = euler(e) {
+                    tsm.model = model;
+                }
+            }
 
-            let vp = cam.target_at(
-                &glm::vec3(
-                    target_pos.x,
-                    target_pos.y,
-                    target_pos.z,
-                ),
-                &glm::vec3(
-                    target_rot.x,
-                    target_rot.y,
-                    target_rot.z,
-                ),
-            );
+            //children lists follow `Parent`, rebuilt every update
+            for list in (&mut children).join() {
+                list.0.clear();
+            }
+            for (e, p) in (&entities, &parent).join() {
+                match children.get_mut(p.0) {
+                    Some(list) => list.0.push(e),
+                    None if entities.is_alive(p.0) => {
+                        children.insert(p.0, Children(vec![e])).expect("not able to insert children");
+                    }
+                    //parent is deleted, its children stay where they were
+                    None => (),
+                }
+            }
+            for (e, t, _) in (&entities, &transform, !&parent).join() {
+                tsm.insert(e, Transformation { model: t.matrix() }).expect("not able to insert transformation");
+            }
+            //down from every root, entities in parent cycles have no root and aren't updated
+            let mut stack: Vec<(Entity, Mat4)> = (&entities, &tsm, &children, !&parent).join()
+                .map(|(e, t, _, _)| (e, t.model))
+                .collect();
+            while let Some((e, model)) = stack.pop() {
+                for &child in children.get(e).map_or(&[][..], |list| &list.0[..]) {
+                    let local = transform.get(child).map(|t| t.matrix()).or_else(|| euler(child));
+                    let global = model * local.unwrap_or_else(glm::identity);
+                    tsm.insert(child, Transformation { model: global }).expect("not able to insert transformation");
+                    stack.push((child, global));
+                }
+            }
 
-            vp_e.view = cam.view.clone();
-            vp_e.proj = cam.projection.clone();
+            //orbits where the target is in world, after its parents moved it
+            let target = camera_target.0
+                .and_then(|e| tsm.get(e))
+                .map(|t| glm::vec3(t.model[(0, 3)], t.model[(1, 3)], t.model[(2, 3)]));
+            if let Some(target) = target {
+                let obstacles: Vec<(Aabb, MeshPtr, Mat4)> = (&obstacle, &render, &tsm).join()
+                    .map(|(_, r, t)| (r.mesh.bounds().transformed(&t.model), r.mesh.clone(), t.model))
+                    .collect();
+                let dt = DURATION_PER_UPD.as_secs_f32();
+                for cam in (&mut camera).join() {
+                    cam.follow(dt);
+                    cam.obstruct(&target, obstacles.iter());
+                    cam.target_at(&target, &glm::vec3(0., 0., 0.));
+                }
+            }
 
-            for (pos, rot, tsm) in (&pos, &rot, &mut tsm).join() {
-                tsm.model = {
-                    let mut mtx = glm::identity();
-                    glm::rotate(&mut mtx, rot.x, &glm::vec3(1., 0., 0.)) *
-                        glm::rotate(&mut mtx, rot.y, &glm::vec3(0., 1., 0.)) *
-                        glm::rotate(&mut mtx, rot.z, &glm::vec3(0., 0., 1.)) *
-                        glm::translate(&mut mtx, &glm::vec3(pos.x, pos.y, pos.z))
-                };
-                tsm.mvp = &vp * tsm.model
+            //fields may be set directly, views follow them
+            for (cam, p) in (&mut ortho_camera, parent.maybe()).join() {
+                cam.update_view();
+                //attached ones look from parent's space
+                if let Some(model) = p.and_then(|p| tsm.get(p.0)) {
+                    cam.view = cam.view * glm::inverse(&model.model);
+                }
+            }
+            for (cam, p) in (&mut fly_camera, parent.maybe()).join() {
+                cam.update_view();
+                //attached ones look from parent's space
+                if let Some(model) = p.and_then(|p| tsm.get(p.0)) {
+                    cam.view = cam.view * glm::inverse(&model.model);
+                }
+            }
+            let cam = camera_of(active_camera.0.unwrap(), &camera, &ortho_camera, &fly_camera).unwrap();
+
+            vp_e.view = cam.view();
+            vp_e.proj = cam.projection();
+
+            for (e, tsm, render) in (&entities, &tsm, &render).join() {
+                let world = WorldBounds(render.mesh.bounds().transformed(&tsm.model));
+                bounds.insert(e, world).expect("not able to insert bounds");
             }
         }
     }
@@ -5,23 +5,32 @@ use std::io::Write;
 
 use client::rx::shaderc::ShaderKind;
 
-pub const VERTEX_SOURCE: &'static str = include_str!("../../shaders/one.vert");
-
-pub const FRAGMENT_SOURCE: &'static str = include_str!("../../shaders/one.frag");
+pub const SHADERS: &[(&str, &str, ShaderKind)] = &[
+    ("one.vert", include_str!("../../shaders/one.vert"), ShaderKind::Vertex),
+    ("one.frag", include_str!("../../shaders/one.frag"), ShaderKind::Fragment),
+    ("shadow.vert", include_str!("../../shaders/shadow.vert"), ShaderKind::Vertex),
+    ("debug.vert", include_str!("../../shaders/debug.vert"), ShaderKind::Vertex),
+    ("debug.frag", include_str!("../../shaders/debug.frag"), ShaderKind::Fragment),
+    ("ui.vert", include_str!("../../shaders/ui.vert"), ShaderKind::Vertex),
+    ("ui.frag", include_str!("../../shaders/ui.frag"), ShaderKind::Fragment),
+    ("text.frag", include_str!("../../shaders/text.frag"), ShaderKind::Fragment),
+    ("skybox.vert", include_str!("../../shaders/skybox.vert"), ShaderKind::Vertex),
+    ("skybox.frag", include_str!("../../shaders/skybox.frag"), ShaderKind::Fragment),
+    ("post.vert", include_str!("../../shaders/post.vert"), ShaderKind::Vertex),
+    ("tonemap.frag", include_str!("../../shaders/tonemap.frag"), ShaderKind::Fragment),
+    ("gamma.frag", include_str!("../../shaders/gamma.frag"), ShaderKind::Fragment),
+    ("fxaa.frag", include_str!("../../shaders/fxaa.frag"), ShaderKind::Fragment),
+    ("vignette.frag", include_str!("../../shaders/vignette.frag"), ShaderKind::Fragment),
+    ("bloom.frag", include_str!("../../shaders/bloom.frag"), ShaderKind::Fragment),
+    ("blit.frag", include_str!("../../shaders/blit.frag"), ShaderKind::Fragment),
+];
 
 fn main() {
-    let v = client::rx::graphics::pipelines::shader::compile(include_str!("../../shaders/one.vert"), client::rx::shaderc::ShaderKind::Vertex, "one.vert", "main").expect("");
-    let f = client::rx::graphics::pipelines::shader::compile(include_str!("../../shaders/one.frag"), client::rx::shaderc::ShaderKind::Fragment, "one.frag", "main").expect("");
-
-    {
-        let mut v_file = File::create("assets/one.vert.spv").expect("");
-        v_file.write_all(v.as_binary_u8()).expect("");
-    };
-
-    {
-        let mut f_file = File::create("assets/one.frag.spv").expect("");
-        f_file.write_all(f.as_binary_u8()).expect("");
-    };
+    for (name, source, kind) in SHADERS {
+        let artifact = client::rx::graphics::pipelines::shader::compile(source, *kind, name, "main").expect("");
+        let mut file = File::create(format!("assets/{}.spv", name)).expect("");
+        file.write_all(artifact.as_binary_u8()).expect("");
+    }
 
     client::start();
 }
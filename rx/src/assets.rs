@@ -1,17 +1,22 @@
 use core::ptr;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 use std::mem::size_of;
 use std::ops::{Deref, Range};
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use glm::{Mat4, Vec3};
 use hal::Backend;
 use image::RgbaImage;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
+use crate::culling::{Aabb, ray_triangle};
 use crate::graphics::wrapper::ApiWrapper;
+use crate::prefab::Prefabs;
+use crate::scene::Scene;
 
 #[derive(Debug, Clone)]
 pub struct AssetsStorage {
@@ -23,6 +28,35 @@ pub struct AssetsStorage {
 pub struct MeshPtr {
     pub(crate)indices: Range<u32>,
     pub(crate)base_vertex: i32,
+    pub(crate)bounds: Aabb,
+    ///model space triangles kept for raycasts
+    pub(crate)triangles: Arc<Vec<[Vec3; 3]>>,
+}
+
+impl MeshPtr {
+    ///Bounds in model space
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    ///Distance along normalized `dir` to the closest triangle of mesh placed with `model`
+    pub fn ray_distance(&self, model: &Mat4, origin: &Vec3, dir: &Vec3) -> Option<f32> {
+        let world = |p: &Vec3| glm::vec4_to_vec3(&(model * glm::vec4(p.x, p.y, p.z, 1.)));
+        self.triangles.iter()
+            .filter_map(|[a, b, c]| ray_triangle(origin, dir, &[world(a), world(b), world(c)]))
+            .fold(None, |closest: Option<f32>, hit| Some(closest.map_or(hit, |c| c.min(hit))))
+    }
+}
+
+///Index of uploaded texture, see `AssetsStorage::load_texture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TexturePtr(pub(crate) usize);
+
+impl Default for TexturePtr {
+    ///1x1 white texture, always present
+    fn default() -> Self {
+        TexturePtr(0)
+    }
 }
 
 impl PartialEq<Self> for MeshPtr {
@@ -94,6 +128,14 @@ impl AssetsStorage {
             let mesh_ptr = MeshPtr {
                 indices: self.idx_offset..(self.idx_offset + indices.len() as u32),
                 base_vertex: self.mesh_offset,
+                bounds: Aabb::from_positions(&positions),
+                triangles: Arc::new(indices.chunks_exact(3).map(|t| {
+                    let vertex = |i: u32| {
+                        let i = i as usize * 3;
+                        glm::vec3(positions[i], positions[i + 1], positions[i + 2])
+                    };
+                    [vertex(t[0]), vertex(t[1]), vertex(t[2])]
+                }).collect()),
             };
             self.mesh_offset += (positions.len() / 3) as i32;
             self.idx_offset += indices.len() as u32;
@@ -102,6 +144,21 @@ impl AssetsStorage {
             Ok(mesh_ptr)
         }
     }
+
+    pub fn load_texture<B: Backend>(&mut self,
+                                    wrapper: &mut ApiWrapper<B>,
+                                    image: RgbaImage,
+    ) -> Result<TexturePtr, &'static str> {
+        wrapper.upload_texture(image).map(TexturePtr)
+    }
+
+    ///Cube texture, only for pipelines sampling `samplerCube`
+    pub fn load_cube_texture<B: Backend>(&mut self,
+                                         wrapper: &mut ApiWrapper<B>,
+                                         faces: &[RgbaImage; 6],
+    ) -> Result<TexturePtr, &'static str> {
+        wrapper.upload_cube_texture(faces).map(TexturePtr)
+    }
 }
 
 //fn align_to(value: u32, alignment: u32) -> u32 {
@@ -119,6 +176,9 @@ pub struct AssetsLoader {
 impl AssetsLoader {
     const IMAGE_DIR: &'static str = "images";
     const MODEL_DIR: &'static str = "models";
+    const FONT_DIR: &'static str = "fonts";
+    const SCENE_DIR: &'static str = "scenes";
+    const PREFAB_DIR: &'static str = "prefabs";
 
     pub fn new(dir: &'static str) -> Result<Self, &str> {
         let dir = PathBuf::from(dir).canonicalize().map_err(|e| {
@@ -157,6 +217,44 @@ impl AssetsLoader {
         Ok(image)
     }
 
+    ///Raw TTF bytes, see `Renderer::load_font`
+    pub fn load_font(&self, name: &'static str) -> Result<Vec<u8>, &str> {
+        let (mut buffer, file_name) = self.open_file(name, Self::FONT_DIR, "ttf")?;
+        let mut bytes = Vec::new();
+        buffer.read_to_end(&mut bytes).map_err(|e| {
+            error!("{:?}", e);
+            "Error with loading font"
+        })?;
+        info!("Loaded font: {:?}", file_name);
+        Ok(bytes)
+    }
+
+    ///RON scene, spawned with `Scene::spawn`
+    pub fn load_scene(&self, name: &'static str) -> Result<Scene, &'static str> {
+        let (mut buffer, file_name) = self.open_file(name, Self::SCENE_DIR, "ron")?;
+        let mut text = String::new();
+        buffer.read_to_string(&mut text).map_err(|e| {
+            error!("{:?}", e);
+            "Error with loading scene"
+        })?;
+        let scene = Scene::from_ron(&text)?;
+        info!("Loaded scene: {:?}", file_name);
+        Ok(scene)
+    }
+
+    ///RON map of prefab names to entities, see `spawn_prefab`
+    pub fn load_prefabs(&self, name: &'static str) -> Result<Prefabs, &'static str> {
+        let (mut buffer, file_name) = self.open_file(name, Self::PREFAB_DIR, "ron")?;
+        let mut text = String::new();
+        buffer.read_to_string(&mut text).map_err(|e| {
+            error!("{:?}", e);
+            "Error with loading prefabs"
+        })?;
+        let prefabs = Prefabs::from_ron(&text)?;
+        info!("Loaded prefabs: {:?}", file_name);
+        Ok(prefabs)
+    }
+
     pub fn load_obj(&self, name: &'static str) -> Result<Mesh, &'static str> {
         let (mut buffer, file_name) = self.open_file(name, Self::MODEL_DIR, "obj")?;
         let (mut models, _) = tobj::load_obj_buf(&mut buffer, |_| -> tobj::MTLLoadResult {
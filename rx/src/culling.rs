@@ -0,0 +1,255 @@
+use glm::{Mat4, Vec3, Vec4};
+
+///Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self {
+            min: glm::vec3(0., 0., 0.),
+            max: glm::vec3(0., 0., 0.),
+        }
+    }
+}
+
+impl Aabb {
+    ///Bounds of flat `[x, y, z, x, y, z, ..]` positions
+    pub fn from_positions(positions: &[f32]) -> Self {
+        let mut points = positions.chunks_exact(3);
+        let first = match points.next() {
+            Some(p) => glm::vec3(p[0], p[1], p[2]),
+            None => return Self::default(),
+        };
+        points.fold(Self { min: first, max: first }, |aabb, p| {
+            let p = glm::vec3(p[0], p[1], p[2]);
+            Self {
+                min: glm::min2(&aabb.min, &p),
+                max: glm::max2(&aabb.max, &p),
+            }
+        })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (&self.min, &self.max);
+        [
+            glm::vec3(a.x, a.y, a.z),
+            glm::vec3(b.x, a.y, a.z),
+            glm::vec3(a.x, b.y, a.z),
+            glm::vec3(b.x, b.y, a.z),
+            glm::vec3(a.x, a.y, b.z),
+            glm::vec3(b.x, a.y, b.z),
+            glm::vec3(a.x, b.y, b.z),
+            glm::vec3(b.x, b.y, b.z),
+        ]
+    }
+
+    ///Box enclosing this one after transformation
+    pub fn transformed(&self, model: &Mat4) -> Self {
+        let corners = self.corners();
+        let world = |p: &Vec3| glm::vec4_to_vec3(&(model * glm::vec4(p.x, p.y, p.z, 1.)));
+        let first = world(&corners[0]);
+        corners[1..].iter().fold(Self { min: first, max: first }, |aabb, p| {
+            let p = world(p);
+            Self {
+                min: glm::min2(&aabb.min, &p),
+                max: glm::max2(&aabb.max, &p),
+            }
+        })
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool {
+        (0..3).all(|i| self.min[i] <= point[i] && point[i] <= self.max[i])
+    }
+
+    ///Box covering this one moved anywhere up to `offset`, e.g. along light for shadow casters
+    pub fn swept(&self, offset: &Vec3) -> Self {
+        Self {
+            min: glm::min2(&self.min, &(self.min + offset)),
+            max: glm::max2(&self.max, &(self.max + offset)),
+        }
+    }
+
+    ///Distance along normalized `dir` where ray from `origin` enters the box,
+    ///none if it misses or starts inside
+    pub fn ray_distance(&self, origin: &Vec3, dir: &Vec3) -> Option<f32> {
+        let (mut near, mut far) = (std::f32::NEG_INFINITY, std::f32::INFINITY);
+        for i in 0..3 {
+            if dir[i].abs() < std::f32::EPSILON {
+                //parallel to slab, misses unless already within it
+                if origin[i] < self.min[i] || origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((self.min[i] - origin[i]) / dir[i], (self.max[i] - origin[i]) / dir[i]);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        if near > far || near < 0. {
+            None
+        } else {
+            Some(near)
+        }
+    }
+}
+
+///Distance along normalized `dir` where ray from `origin` hits either side of `triangle`
+pub fn ray_triangle(origin: &Vec3, dir: &Vec3, triangle: &[Vec3; 3]) -> Option<f32> {
+    let [a, b, c] = triangle;
+    let (ab, ac) = (b - a, c - a);
+    let p = glm::cross(dir, &ac);
+    let det = glm::dot(&ab, &p);
+    if det.abs() < std::f32::EPSILON {
+        return None;
+    }
+    let to_origin = origin - a;
+    let u = glm::dot(&to_origin, &p) / det;
+    let q = glm::cross(&to_origin, &ab);
+    let v = glm::dot(dir, &q) / det;
+    if u < 0. || v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = glm::dot(&ac, &q) / det;
+    if t < 0. { None } else { Some(t) }
+}
+
+///View frustum as six inward facing planes `(normal, distance)`
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    ///Extracts planes from GL style (-w..w depth) view-projection matrix
+    pub fn from_view_proj(vp: &Mat4) -> Self {
+        Self::from_rows(vp, false)
+    }
+
+    ///Same for 0..w depth, e.g. `glm::ortho_rh_zo` shadow cascades
+    pub fn from_view_proj_zo(vp: &Mat4) -> Self {
+        Self::from_rows(vp, true)
+    }
+
+    fn from_rows(vp: &Mat4, zero_to_one: bool) -> Self {
+        let row = |i: usize| glm::vec4(vp[(i, 0)], vp[(i, 1)], vp[(i, 2)], vp[(i, 3)]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let normalize = |p: Vec4| p / glm::length(&glm::vec3(p.x, p.y, p.z));
+        Self {
+            planes: [
+                normalize(w + x),
+                normalize(w - x),
+                normalize(w + y),
+                normalize(w - y),
+                normalize(if zero_to_one { z } else { w + z }),
+                normalize(w - z),
+            ],
+        }
+    }
+
+    ///Conservative test, may keep boxes near frustum corners
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            //corner furthest along plane normal
+            let p = glm::vec3(
+                if plane.x >= 0. { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0. { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0. { aabb.max.z } else { aabb.min.z },
+            );
+            plane.x * p.x + plane.y * p.y + plane.z * p.z + plane.w >= 0.
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aabb(min: [f32; 3], max: [f32; 3]) -> Aabb {
+        Aabb { min: glm::vec3(min[0], min[1], min[2]), max: glm::vec3(max[0], max[1], max[2]) }
+    }
+
+    fn camera() -> Frustum {
+        //at origin looking down -z, 1..100
+        let proj = glm::perspective(1., std::f32::consts::FRAC_PI_2, 1., 100.);
+        Frustum::from_view_proj(&proj)
+    }
+
+    #[test]
+    fn transformed_encloses_moved_corners() {
+        let model = glm::translation(&glm::vec3(10., 0., 0.))
+            * glm::rotation(std::f32::consts::FRAC_PI_2, &glm::vec3(0., 0., 1.));
+        let moved = aabb([0., 0., 0.], [2., 1., 1.]).transformed(&model);
+        assert!(glm::distance(&moved.min, &glm::vec3(9., 0., 0.)) < 1e-5);
+        assert!(glm::distance(&moved.max, &glm::vec3(10., 2., 1.)) < 1e-5);
+    }
+
+    #[test]
+    fn ray_enters_box_in_front() {
+        let b = aabb([-1., -1., -1.], [1., 1., 1.]);
+        let dir = glm::vec3(1., 0., 0.);
+        assert_eq!(b.ray_distance(&glm::vec3(-5., 0., 0.), &dir), Some(4.));
+        assert_eq!(b.ray_distance(&glm::vec3(5., 0., 0.), &dir), None);
+        assert_eq!(b.ray_distance(&glm::vec3(-5., 3., 0.), &dir), None);
+    }
+
+    #[test]
+    fn ray_starting_inside_is_not_a_hit() {
+        let b = aabb([-1., -1., -1.], [1., 1., 1.]);
+        assert_eq!(b.ray_distance(&glm::vec3(0., 0., 0.), &glm::vec3(0., 1., 0.)), None);
+    }
+
+    #[test]
+    fn contains_includes_faces() {
+        let b = aabb([-1., -1., -1.], [1., 1., 1.]);
+        assert!(b.contains(&glm::vec3(1., 0., -1.)));
+        assert!(!b.contains(&glm::vec3(0., 1.5, 0.)));
+    }
+
+    #[test]
+    fn ray_hits_triangle_from_both_sides() {
+        let triangle = [glm::vec3(0., 0., 0.), glm::vec3(10., 0., 0.), glm::vec3(0., 0., 10.)];
+        let down = glm::vec3(0., -1., 0.);
+        assert_eq!(ray_triangle(&glm::vec3(2., 5., 2.), &down, &triangle), Some(5.));
+        assert_eq!(ray_triangle(&glm::vec3(2., -5., 2.), &-down, &triangle), Some(5.));
+        //behind origin, outside of edges, parallel
+        assert_eq!(ray_triangle(&glm::vec3(2., -5., 2.), &down, &triangle), None);
+        assert_eq!(ray_triangle(&glm::vec3(8., 5., 8.), &down, &triangle), None);
+        assert_eq!(ray_triangle(&glm::vec3(2., 0., 2.), &glm::vec3(1., 0., 0.), &triangle), None);
+    }
+
+    #[test]
+    fn frustum_keeps_inside_and_straddling_boxes() {
+        let frustum = camera();
+        assert!(frustum.intersects(&aabb([-1., -1., -11.], [1., 1., -9.])));
+        //across near plane and across side plane
+        assert!(frustum.intersects(&aabb([-1., -1., -2.], [1., 1., 0.])));
+        assert!(frustum.intersects(&aabb([9., -1., -11.], [20., 1., -9.])));
+        assert!(!frustum.intersects(&aabb([-1., -1., 1.], [1., 1., 3.])));
+        assert!(!frustum.intersects(&aabb([-1., -1., -150.], [1., 1., -120.])));
+        assert!(!frustum.intersects(&aabb([15., -1., -11.], [20., 1., -9.])));
+    }
+
+    #[test]
+    fn zero_to_one_frustum_has_near_plane_at_zero_depth() {
+        let proj = glm::ortho_rh_zo(-10., 10., -10., 10., 5., 50.);
+        let frustum = Frustum::from_view_proj_zo(&proj);
+        assert!(frustum.intersects(&aabb([-1., -1., -7.], [1., 1., -6.])));
+        assert!(!frustum.intersects(&aabb([-1., -1., -4.], [1., 1., -3.])));
+        assert!(!frustum.intersects(&aabb([-1., -1., -60.], [1., 1., -55.])));
+    }
+
+    #[test]
+    fn swept_box_reaches_into_frustum() {
+        let behind = aabb([-1., -1., 1.], [1., 1., 3.]);
+        assert!(!camera().intersects(&behind));
+        assert!(camera().intersects(&behind.swept(&glm::vec3(0., 0., -20.))));
+    }
+}
@@ -0,0 +1,93 @@
+use glm::{Vec3, Vec4};
+
+use crate::culling::Aabb;
+
+const SPHERE_SEGMENTS: usize = 24;
+
+///Vertex of debug line list, mirrors inputs of `debug.vert`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+///Immediate mode lines, usable from any system as a specs resource
+///
+///Cleared by `EcsLayer` before every rated update, so lines pushed there stay visible until the next one.
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, from: &Vec3, to: &Vec3, color: &Vec4) {
+        let color = [color.x, color.y, color.z, color.w];
+        self.vertices.push(DebugVertex { position: [from.x, from.y, from.z], color });
+        self.vertices.push(DebugVertex { position: [to.x, to.y, to.z], color });
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: &Vec4) {
+        let c = aabb.corners();
+        //corner index bits: x - 1, y - 2, z - 4
+        const EDGES: [(usize, usize); 12] = [
+            (0, 1), (2, 3), (4, 5), (6, 7),
+            (0, 2), (1, 3), (4, 6), (5, 7),
+            (0, 4), (1, 5), (2, 6), (3, 7),
+        ];
+        for (a, b) in EDGES.iter() {
+            self.line(&c[*a], &c[*b], color);
+        }
+    }
+
+    ///Three great circles
+    pub fn sphere(&mut self, center: &Vec3, radius: f32, color: &Vec4) {
+        let point = |i: usize, axis: usize| {
+            let angle = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::PI * 2.;
+            let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+            center + match axis {
+                0 => glm::vec3(0., sin, cos),
+                1 => glm::vec3(cos, 0., sin),
+                _ => glm::vec3(sin, cos, 0.),
+            }
+        };
+        for axis in 0..3 {
+            for i in 0..SPHERE_SEGMENTS {
+                self.line(&point(i, axis), &point(i + 1, axis), color);
+            }
+        }
+    }
+
+    ///x - red, y - green, z - blue
+    pub fn axes(&mut self, origin: &Vec3, size: f32) {
+        self.line(origin, &(origin + glm::vec3(size, 0., 0.)), &glm::vec4(1., 0., 0., 1.));
+        self.line(origin, &(origin + glm::vec3(0., size, 0.)), &glm::vec4(0., 1., 0., 1.));
+        self.line(origin, &(origin + glm::vec3(0., 0., size)), &glm::vec4(0., 0., 1., 1.));
+    }
+
+    ///Square grid on XZ plane with `cells` x `cells` cells of `cell_size`
+    pub fn grid(&mut self, center: &Vec3, cell_size: f32, cells: u32, color: &Vec4) {
+        let half = cell_size * cells as f32 / 2.;
+        for i in 0..=cells {
+            let offset = i as f32 * cell_size - half;
+            self.line(
+                &(center + glm::vec3(offset, 0., -half)),
+                &(center + glm::vec3(offset, 0., half)),
+                color,
+            );
+            self.line(
+                &(center + glm::vec3(-half, 0., offset)),
+                &(center + glm::vec3(half, 0., offset)),
+                color,
+            );
+        }
+    }
+
+    pub fn vertices(&self) -> &[DebugVertex] {
+        &self.vertices
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
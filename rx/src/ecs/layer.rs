@@ -5,9 +5,12 @@ use std::time::Duration;
 use log::{debug, error, info, trace, warn};
 use specs::{DispatcherBuilder, World, WorldExt};
 
+use crate::debug_draw::DebugDraw;
 use crate::ecs::WinitEvents;
 use crate::events::MyEvent;
 use crate::run::Layer;
+use crate::text::TextQueue;
+use crate::ui::Ui;
 
 pub struct EcsLayer<'a> {
     world: specs::World,
@@ -17,20 +20,38 @@ pub struct EcsLayer<'a> {
 }
 
 const UPD_60_PER_SEC_NANOS: u64 = 16600000;
-const DURATION_PER_UPD: Duration = Duration::from_nanos(UPD_60_PER_SEC_NANOS);
+///Step of rated dispatcher, its systems can use it as delta time
+pub const DURATION_PER_UPD: Duration = Duration::from_nanos(UPD_60_PER_SEC_NANOS);
 
 impl<'a> Layer for EcsLayer<'a> {
     fn on_update(&mut self, events: &Vec<MyEvent>, elapsed: Duration) {
         self.lag += elapsed;
         {
+            //UI sees events first, consumed ones are hidden from systems
+            let mut ui = self.world.try_fetch_mut::<Ui>();
             let mut events_resource = self.world.write_resource::<WinitEvents>();
             for e in events.iter() {
+                if ui.as_mut().map_or(false, |ui| ui.handle_event(e)) {
+                    continue;
+                }
                 events_resource.0.push((*e).clone());
             }
         }
 
         while self.lag >= DURATION_PER_UPD {
+            if let Some(mut debug_draw) = self.world.try_fetch_mut::<DebugDraw>() {
+                debug_draw.clear();
+            }
+            if let Some(mut texts) = self.world.try_fetch_mut::<TextQueue>() {
+                texts.clear();
+            }
+            if let Some(mut ui) = self.world.try_fetch_mut::<Ui>() {
+                ui.begin_frame();
+            }
             self.rated_dispatcher.dispatch(&self.world);
+            if let Some(mut ui) = self.world.try_fetch_mut::<Ui>() {
+                ui.end_frame();
+            }
             let mut events_resource = self.world.write_resource::<WinitEvents>();
             events_resource.0.clear();
             self.lag -= DURATION_PER_UPD;
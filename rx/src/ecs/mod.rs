@@ -1,28 +1,33 @@
 use glm::{
     Mat4,
+    Quat,
     radians,
     Vec3,
+    Vec4,
 };
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
-use specs::{Component, Entity, VecStorage};
+use specs::{Component, Entity, NullStorage, VecStorage};
+use specs::storage::GenericReadStorage;
 
-use crate::assets::MeshPtr;
+use crate::assets::{MeshPtr, TexturePtr};
+use crate::culling::Aabb;
 use crate::events::MyEvent;
+use crate::graphics::draw::{ClearMode, LAYER_ALL, LAYER_DEFAULT, View, ViewportRect};
+use crate::graphics::target::TargetPtr;
 
 pub mod layer;
 
+///Global model matrix, made from `Transform` and `Parent` or `Position` and `Rotation`
 #[derive(Component, Debug)]
 #[storage(VecStorage)]
 pub struct Transformation {
-    pub mvp: Mat4,
     pub model: Mat4,
 }
 
 impl Default for Transformation {
     fn default() -> Self {
         Self {
-            mvp: glm::identity(),
             model: glm::identity(),
         }
     }
@@ -34,15 +39,145 @@ pub struct Render {
     pub mesh: MeshPtr
 }
 
+///World space bounds of `Render` mesh, updated with `Transformation`
+#[derive(Component, Debug, Clone, Default)]
+#[storage(VecStorage)]
+pub struct WorldBounds(pub Aabb);
+
+///Entities submitted for drawing and left out by culling in the last frame,
+///submitted ones are in a camera view or may shadow the first screen view
+#[derive(Default, Debug, Clone, Copy)]
+pub struct CullingStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Material {
+    pub base_color: Vec4,
+    pub shininess: f32,
+    pub texture: Option<TexturePtr>,
+    ///drawn after opaque geometry, blended by `base_color` alpha
+    pub transparent: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: glm::vec4(1., 1., 1., 1.),
+            shininess: 32.,
+            texture: None,
+            transparent: false,
+        }
+    }
+}
+
+impl Material {
+    pub fn color(r: f32, g: f32, b: f32) -> Self {
+        Self {
+            base_color: glm::vec4(r, g, b, 1.),
+            ..Default::default()
+        }
+    }
+
+    pub fn transparent(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self {
+            base_color: glm::vec4(r, g, b, a),
+            transparent: true,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct WinitEvents(pub Vec<MyEvent>);
 
+///Entity `TargetCamera`s orbit, by its `Transformation`
 #[derive(Default)]
 pub struct CameraTarget(pub Option<Entity>);
 
+///Camera controlled by input, see `Camera` for cameras drawn on screen
 #[derive(Default)]
 pub struct ActiveCamera(pub Option<Entity>);
 
+///Draws camera entity's view into part of the screen or of `target`, cameras go in `order`
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Camera {
+    pub viewport: ViewportRect,
+    ///entities with any of these `RenderLayers` bits are drawn
+    pub layers: u32,
+    pub clear: ClearMode,
+    ///lower goes first, later cameras draw over earlier ones
+    pub order: i32,
+    ///offscreen target from `Renderer::create_render_target`, none for screen
+    pub target: Option<TargetPtr>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            viewport: ViewportRect::FULL,
+            layers: LAYER_ALL,
+            clear: ClearMode::Background,
+            order: 0,
+            target: None,
+        }
+    }
+}
+
+impl Camera {
+    ///Drawn over cameras with lower order, e.g. minimap or picture-in-picture
+    pub fn inset(viewport: ViewportRect, order: i32) -> Self {
+        Self {
+            viewport,
+            order,
+            ..Default::default()
+        }
+    }
+
+    ///Whole `target`, e.g. for security camera screens or minimap textures
+    pub fn offscreen(target: TargetPtr) -> Self {
+        Self {
+            target: Some(target),
+            ..Default::default()
+        }
+    }
+
+    ///Aspect of viewport, `screen` is used when there's no target
+    pub fn aspect(&self, screen: (u32, u32)) -> f32 {
+        self.viewport.aspect(self.target.map_or(screen, |t| t.extent()))
+    }
+
+    ///Frame view of `cam` drawn where this camera says
+    pub fn view_of(&self, cam: &dyn CameraView) -> View {
+        View {
+            viewport: self.viewport,
+            layers: self.layers,
+            clear: self.clear,
+            target: self.target,
+            ..View::full(cam.view(), cam.projection(), cam.eye())
+        }
+    }
+}
+
+///Blocks `TargetCamera` with `collision_margin`, tested by triangles of its `Render` mesh
+#[derive(Component, Debug, Clone, Copy, Default)]
+#[storage(NullStorage)]
+pub struct CameraObstacle;
+
+///Layer bits matched against `Camera::layers`, `LAYER_DEFAULT` without this component
+#[derive(Component, Debug, Clone, Copy)]
+#[storage(VecStorage)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers(LAYER_DEFAULT)
+    }
+}
+
 pub struct ViewProjection{
     pub view: glm::Mat4,
     pub proj: glm::Mat4
@@ -57,6 +192,68 @@ impl Default for ViewProjection {
     }
 }
 
+///Translation, rotation and scale relative to `Parent`, or to world without one
+#[derive(Component, Debug, Clone, PartialEq)]
+#[storage(VecStorage)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: glm::vec3(0., 0., 0.),
+            rotation: glm::quat_identity(),
+            scale: glm::vec3(1., 1., 1.),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_rotation(self, rotation: Quat) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vec3) -> Self {
+        Self { scale, ..self }
+    }
+
+    ///Turns around local `axis` by `degrees`
+    pub fn rotate(&mut self, axis: &Vec3, degrees: f32) {
+        self.rotation = self.rotation * glm::quat_angle_axis(degrees.to_radians(), &glm::normalize(axis));
+    }
+
+    ///Scale first, then rotation, then translation
+    pub fn matrix(&self) -> Mat4 {
+        glm::translation(&self.translation) * glm::quat_to_mat4(&self.rotation) * glm::scaling(&self.scale)
+    }
+}
+
+///Entity this one's `Transform`, or `Position` and `Rotation` without it, is relative to
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[storage(VecStorage)]
+pub struct Parent(pub Entity);
+
+///Entities with `Parent` pointing here, rebuilt from `Parent` by transform propagation
+#[derive(Component, Debug, Clone, Default)]
+#[storage(VecStorage)]
+pub struct Children(pub Vec<Entity>);
+
+///How scene files refer to an entity, see `scene::Scene`
+#[derive(Component, Debug, Clone, PartialEq)]
+#[storage(VecStorage)]
+pub struct Name(pub String);
+
+///Euler angles in degrees, applied in y, x, z order
 #[derive(Component, Debug)]
 #[storage(VecStorage)]
 pub struct Rotation {
@@ -107,12 +304,103 @@ impl Default for Position {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum LightKind {
+    Point,
+    Directional {
+        direction: Vec3,
+    },
+    ///inner and outer cone angles in degrees
+    Spot {
+        direction: Vec3,
+        inner: f32,
+        outer: f32,
+    },
+}
+
+///Light source, placed by entity `Position` (ignored for directional)
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            color: glm::vec3(1., 1., 1.),
+            intensity: 1.,
+            range: 100.,
+        }
+    }
+}
+
+impl Light {
+    pub fn point(color: Vec3, intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            color,
+            intensity,
+            range,
+        }
+    }
+
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional { direction },
+            color,
+            intensity,
+            range: 0.,
+        }
+    }
+
+    pub fn spot(direction: Vec3, inner: f32, outer: f32, color: Vec3, intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Spot { direction, inner, outer },
+            color,
+            intensity,
+            range,
+        }
+    }
+}
+
+///View, projection and position shared by camera components, see `camera_of`
+pub trait CameraView {
+    fn view(&self) -> Mat4;
+    fn projection(&self) -> Mat4;
+    ///eye in world space
+    fn eye(&self) -> Vec3;
+    ///rebuilds projection for width over height `aspect`
+    fn update_aspect(&mut self, aspect: f32);
+}
+
+///Camera component of `entity`, looked up in `TargetCamera`, `OrthoCamera`, `FlyCamera` order
+pub fn camera_of<'a, T, O, F>(entity: Entity, target: &'a T, ortho: &'a O, fly: &'a F) -> Option<&'a dyn CameraView>
+    where T: GenericReadStorage<Component=TargetCamera>,
+          O: GenericReadStorage<Component=OrthoCamera>,
+          F: GenericReadStorage<Component=FlyCamera> {
+    target.get(entity).map(|c| c as &dyn CameraView)
+        .or_else(|| ortho.get(entity).map(|c| c as &dyn CameraView))
+        .or_else(|| fly.get(entity).map(|c| c as &dyn CameraView))
+}
+
+///Perspective camera orbiting `CameraTarget`
+///
+///`yaw`, `pitch` and `distance` are where the camera goes, it follows them with `smoothing`.
 #[derive(Component, Debug)]
 #[storage(VecStorage)]
 pub struct TargetCamera {
     pub projection: Mat4,
     pub view: Mat4,
-    fov: f32,
+    ///vertical, in degrees
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    aspect: f32,
     //
     offset_y: f32,
     pub distance: f32,
@@ -120,6 +408,17 @@ pub struct TargetCamera {
     pub yaw: f32,
     //angle around x
     pub pitch: f32,
+    ///`pitch` limits in degrees, 90 is straight above target and 180 is level with it
+    pub pitch_range: (f32, f32),
+    pub distance_range: (f32, f32),
+    ///how fast camera catches up per second, 0 - instantly
+    pub smoothing: f32,
+    ///gap kept in front of `CameraObstacle` meshes, none - no collision
+    pub collision_margin: Option<f32>,
+    ///yaw, pitch and distance camera is at
+    current: (f32, f32, f32),
+    ///distance cut short by an obstacle
+    blocked: Option<f32>,
 }
 
 impl Default for TargetCamera {
@@ -130,10 +429,19 @@ impl Default for TargetCamera {
             projection: glm::perspective(aspect_ratio, glm::radians(&glm::vec1(60.)).x, 0.1, 1000.),
             view: glm::identity(),
             fov: 60.,
+            near: 0.1,
+            far: 1000.,
+            aspect: aspect_ratio,
             offset_y: 0.,
             distance: 100.,
             yaw: 180.,
             pitch: 115.,
+            pitch_range: (91., 175.),
+            distance_range: (5., 500.),
+            smoothing: 12.,
+            collision_margin: None,
+            current: (180., 115., 100.),
+            blocked: None,
         }
     }
 }
@@ -141,17 +449,91 @@ impl Default for TargetCamera {
 
 impl TargetCamera {
     pub fn update_aspect(&mut self, aspect_ratio: f32) {
-        self.projection = glm::perspective(aspect_ratio, glm::radians(&glm::vec1(self.fov)).x, 0.1, 1000.);
+        self.aspect = aspect_ratio;
+        self.update_projection();
+    }
+
+    ///Applies changed `fov`, `near` or `far`
+    pub fn update_projection(&mut self) {
+        self.projection = glm::perspective(self.aspect, glm::radians(&glm::vec1(self.fov)).x, self.near, self.far);
+    }
+
+    pub fn position(&self) -> Vec3 {
+        let world = glm::inverse(&self.view) * glm::vec4(0., 0., 0., 1.);
+        glm::vec3(world.x, world.y, world.z)
+    }
+
+    ///Turns and zooms within `pitch_range` and `distance_range`
+    pub fn orbit(&mut self, yaw: f32, pitch: f32, distance: f32) {
+        self.yaw += yaw;
+        self.pitch += pitch;
+        self.distance += distance;
+        self.clamp();
+    }
+
+    pub fn clamp(&mut self) {
+        let ((min_pitch, max_pitch), (min_distance, max_distance)) = (self.pitch_range, self.distance_range);
+        self.pitch = self.pitch.max(min_pitch).min(max_pitch);
+        self.distance = self.distance.max(min_distance).min(max_distance);
+    }
+
+    ///Moves current yaw, pitch and distance toward set ones, `dt` in seconds
+    pub fn follow(&mut self, dt: f32) {
+        self.clamp();
+        let t = if self.smoothing > 0. { 1. - (-self.smoothing * dt).exp() } else { 1. };
+        let (yaw, pitch, distance) = self.current;
+        //shortest way around
+        let turn = (self.yaw - yaw + 180.).rem_euclid(360.) - 180.;
+        self.current = (
+            yaw + turn * t,
+            pitch + (self.pitch - pitch) * t,
+            distance + (self.distance - distance) * t,
+        );
+    }
+
+    ///Jumps to set yaw, pitch and distance without smoothing
+    pub fn snap(&mut self) {
+        self.clamp();
+        self.current = (self.yaw, self.pitch, self.distance);
+    }
+
+    ///Pulls camera in front of the closest obstacle triangle between it and `target`,
+    ///obstacles are world bounds, mesh and model of `CameraObstacle` entities
+    pub fn obstruct<'b, I>(&mut self, target: &Vec3, obstacles: I) where I: Iterator<Item=&'b (Aabb, MeshPtr, Mat4)> {
+        self.blocked = None;
+        let margin = match self.collision_margin {
+            Some(margin) => margin,
+            None => return,
+        };
+        let (yaw, pitch, distance) = self.current;
+        let origin = target + glm::vec3(0., self.offset_y, 0.);
+        let dir = Self::orbit_direction(yaw, pitch);
+        let reach = distance + margin;
+        self.blocked = obstacles
+            //box test first, a ray from inside of it may still hit triangles
+            .filter(|(aabb, ..)| aabb.contains(&origin) || aabb.ray_distance(&origin, &dir).map_or(false, |hit| hit < reach))
+            .filter_map(|(_, mesh, model)| mesh.ray_distance(model, &origin, &dir))
+            .filter(|hit| *hit < reach)
+            .fold(None, |closest: Option<f32>, hit| Some(closest.map_or(hit, |c| c.min(hit))))
+            .map(|hit| (hit - margin).max(0.).min(distance));
+    }
+
+    ///Unit vector from target to camera
+    fn orbit_direction(yaw: f32, pitch: f32) -> Vec3 {
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        glm::vec3(-pitch.cos() * yaw.sin(), pitch.sin(), -pitch.cos() * yaw.cos())
     }
 
     pub fn target_at(&mut self, position: &Vec3, _rotation: &Vec3) -> Mat4 {
         let (x, y, z) = (position.x, position.y, position.z);
-        let theta = radians(&glm::vec1(self.yaw));
-        let pitch_rad = radians(&glm::vec1(self.pitch));
+        let (yaw, pitch, distance) = self.current;
+        let distance = self.blocked.unwrap_or(distance);
+        let theta = radians(&glm::vec1(yaw));
+        let pitch_rad = radians(&glm::vec1(pitch));
 
         let (horiz, vert) = (
-            self.distance * glm::cos(&pitch_rad),
-            self.distance * glm::sin(&pitch_rad)
+            distance * glm::cos(&pitch_rad),
+            distance * glm::sin(&pitch_rad)
         );
 
         let (offset_x, offset_z) = (
@@ -165,8 +547,8 @@ impl TargetCamera {
             -(z - offset_z),
         );
         let cam_rot: Vec3 = glm::vec3(
-            self.pitch,
-            180_f32 - self.yaw,
+            pitch,
+            180_f32 - yaw,
             0_f32,
         );
         self.view = Self::get_view(&cam_pos, &cam_rot);
@@ -195,4 +577,212 @@ impl TargetCamera {
         mtx = glm::translate(&mtx, &glm::vec3(pos.x, pos.y, pos.z));
         mtx
     }
-}
\ No newline at end of file
+}
+impl CameraView for TargetCamera {
+    fn view(&self) -> Mat4 {
+        self.view
+    }
+
+    fn projection(&self) -> Mat4 {
+        self.projection
+    }
+
+    fn eye(&self) -> Vec3 {
+        self.position()
+    }
+
+    fn update_aspect(&mut self, aspect: f32) {
+        TargetCamera::update_aspect(self, aspect)
+    }
+}
+
+///Orthographic camera at `position` looking along yaw and pitch, straight down by default,
+///relative to `Parent` if there is one
+#[derive(Component, Debug)]
+#[storage(VecStorage)]
+pub struct OrthoCamera {
+    pub projection: Mat4,
+    pub view: Mat4,
+    pub position: Vec3,
+    ///angle around y, in degrees
+    pub yaw: f32,
+    ///angle around x, in degrees, 90 looks down
+    pub pitch: f32,
+    ///world units visible vertically, width follows aspect
+    pub height: f32,
+    pub near: f32,
+    pub far: f32,
+    aspect: f32,
+}
+
+impl Default for OrthoCamera {
+    fn default() -> Self {
+        let mut camera = Self {
+            projection: glm::identity(),
+            view: glm::identity(),
+            position: glm::vec3(0., 500., 0.),
+            yaw: 0.,
+            pitch: 90.,
+            height: 200.,
+            near: 0.1,
+            far: 1000.,
+            aspect: 6. / 4.,
+        };
+        camera.update_projection();
+        camera.update_view();
+        camera
+    }
+}
+
+impl OrthoCamera {
+    ///Looking down on `center` from `altitude` above it, `height` world units tall
+    pub fn top_down(center: Vec3, altitude: f32, height: f32) -> Self {
+        let mut camera = Self {
+            position: center + glm::vec3(0., altitude, 0.),
+            height,
+            far: altitude * 2.,
+            ..Default::default()
+        };
+        camera.update_projection();
+        camera.update_view();
+        camera
+    }
+
+    ///Applies changed `height`, `near` or `far`
+    pub fn update_projection(&mut self) {
+        let (w, h) = (self.height * self.aspect / 2., self.height / 2.);
+        self.projection = glm::ortho(-w, w, -h, h, self.near, self.far);
+    }
+
+    ///Applies changed `position`, `yaw` or `pitch`
+    pub fn update_view(&mut self) {
+        self.view = TargetCamera::get_view(&-self.position, &glm::vec3(self.pitch, self.yaw, 0.));
+    }
+}
+
+impl CameraView for OrthoCamera {
+    fn view(&self) -> Mat4 {
+        self.view
+    }
+
+    fn projection(&self) -> Mat4 {
+        self.projection
+    }
+
+    fn eye(&self) -> Vec3 {
+        //view may be moved by `Parent`
+        glm::vec4_to_vec3(&(glm::inverse(&self.view) * glm::vec4(0., 0., 0., 1.)))
+    }
+
+    fn update_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+        self.update_projection();
+    }
+}
+
+///Perspective camera moved freely, e.g. for editor views
+#[derive(Component, Debug)]
+#[storage(VecStorage)]
+pub struct FlyCamera {
+    pub projection: Mat4,
+    pub view: Mat4,
+    pub position: Vec3,
+    ///angle around y, in degrees
+    pub yaw: f32,
+    ///angle around x, in degrees, kept within -89..89
+    pub pitch: f32,
+    ///vertical, in degrees
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    ///world units per `fly` step
+    pub speed: f32,
+    aspect: f32,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        let mut camera = Self {
+            projection: glm::identity(),
+            view: glm::identity(),
+            position: glm::vec3(0., 50., 100.),
+            yaw: 0.,
+            pitch: 20.,
+            fov: 60.,
+            near: 0.1,
+            far: 1000.,
+            speed: 1.,
+            aspect: 6. / 4.,
+        };
+        camera.update_projection();
+        camera.update_view();
+        camera
+    }
+}
+
+impl FlyCamera {
+    pub fn at(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        let mut camera = Self {
+            position,
+            yaw,
+            pitch,
+            ..Default::default()
+        };
+        camera.update_view();
+        camera
+    }
+
+    ///Applies changed `fov`, `near` or `far`
+    pub fn update_projection(&mut self) {
+        self.projection = glm::perspective(self.aspect, glm::radians(&glm::vec1(self.fov)).x, self.near, self.far);
+    }
+
+    ///Applies changed `position`, `yaw` or `pitch`
+    pub fn update_view(&mut self) {
+        self.pitch = self.pitch.max(-89.).min(89.);
+        self.view = TargetCamera::get_view(&-self.position, &glm::vec3(self.pitch, self.yaw, 0.));
+    }
+
+    ///Where the camera looks, in world space
+    pub fn forward(&self) -> Vec3 {
+        glm::vec4_to_vec3(&(glm::inverse(&self.view) * glm::vec4(0., 0., -1., 0.)))
+    }
+
+    pub fn right(&self) -> Vec3 {
+        glm::vec4_to_vec3(&(glm::inverse(&self.view) * glm::vec4(1., 0., 0., 0.)))
+    }
+
+    ///Moves along own axes and world up, scaled by `speed`
+    pub fn fly(&mut self, forward: f32, right: f32, up: f32) {
+        let step = self.forward() * forward + self.right() * right + glm::vec3(0., up, 0.);
+        self.position += step * self.speed;
+        self.update_view();
+    }
+
+    ///Turns by degrees, pitch is clamped
+    pub fn look(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch += pitch;
+        self.update_view();
+    }
+}
+
+impl CameraView for FlyCamera {
+    fn view(&self) -> Mat4 {
+        self.view
+    }
+
+    fn projection(&self) -> Mat4 {
+        self.projection
+    }
+
+    fn eye(&self) -> Vec3 {
+        //view may be moved by `Parent`
+        glm::vec4_to_vec3(&(glm::inverse(&self.view) * glm::vec4(0., 0., 0., 1.)))
+    }
+
+    fn update_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+        self.update_projection();
+    }
+}
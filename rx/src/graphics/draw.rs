@@ -0,0 +1,398 @@
+use hal::pso::Rect;
+
+use crate::ecs::{Light, LightKind};
+use crate::glm;
+use crate::graphics::shadow::{MAX_CASCADES, ShadowCascades, ShadowConfig};
+use crate::graphics::target::TargetPtr;
+
+pub const MAX_LIGHTS: usize = 16;
+
+const LIGHT_POINT: f32 = 0.;
+const LIGHT_DIRECTIONAL: f32 = 1.;
+const LIGHT_SPOT: f32 = 2.;
+
+///What main pass outputs, values mirror `DEBUG_*` constants in `one.frag`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMode {
+    Filled = 0,
+    ///needs `NON_FILL_POLYGON_MODE` device feature
+    Wireframe = 1,
+    Normals = 2,
+    Uv = 3,
+    Depth = 4,
+}
+
+impl Default for DebugMode {
+    fn default() -> Self {
+        DebugMode::Filled
+    }
+}
+
+impl DebugMode {
+    ///Cycles through all modes
+    pub fn next(self) -> Self {
+        match self {
+            DebugMode::Filled => DebugMode::Wireframe,
+            DebugMode::Wireframe => DebugMode::Normals,
+            DebugMode::Normals => DebugMode::Uv,
+            DebugMode::Uv => DebugMode::Depth,
+            DebugMode::Depth => DebugMode::Filled,
+        }
+    }
+}
+
+///Views drawn in one frame, extra cameras are dropped
+pub const MAX_VIEWS: usize = 8;
+///Layer of entities without `RenderLayers`
+pub const LAYER_DEFAULT: u32 = 1;
+pub const LAYER_ALL: u32 = !0;
+
+///Part of render target in 0..1 of its size, origin at top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ViewportRect {
+    pub const FULL: ViewportRect = ViewportRect { x: 0., y: 0., width: 1., height: 1. };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    ///Width over height for `extent` sized target
+    pub fn aspect(&self, extent: (u32, u32)) -> f32 {
+        (self.width * extent.0 as f32) / (self.height * extent.1 as f32).max(1.)
+    }
+
+    ///Pixel rect clamped to target
+    pub fn to_rect(&self, extent: (u32, u32)) -> Rect {
+        let clamp = |v: f32| v.max(0.).min(1.);
+        let (x0, y0) = (clamp(self.x), clamp(self.y));
+        let (x1, y1) = (clamp(self.x + self.width), clamp(self.y + self.height));
+        let (w, h) = (extent.0 as f32, extent.1 as f32);
+        Rect {
+            x: (x0 * w).round() as i16,
+            y: (y0 * h).round() as i16,
+            w: ((x1 - x0) * w).round() as i16,
+            h: ((y1 - y0) * h).round() as i16,
+        }
+    }
+}
+
+impl Default for ViewportRect {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+///What is left in viewport before a view draws into it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearMode {
+    ///renderer clear color and skybox
+    Background,
+    ///solid color, without skybox
+    Color(glm::Vec4),
+    ///keeps color of views drawn before, e.g. for overlays
+    DepthOnly,
+    ///keeps both color and depth
+    None,
+}
+
+impl Default for ClearMode {
+    fn default() -> Self {
+        ClearMode::Background
+    }
+}
+
+///Camera of a frame, views are drawn in the order they were pushed
+#[derive(Debug, Clone)]
+pub struct View {
+    pub view: glm::Mat4,
+    pub proj: glm::Mat4,
+    pub position: glm::Vec3,
+    pub viewport: ViewportRect,
+    ///drawn entities have at least one of these layer bits
+    pub layers: u32,
+    pub clear: ClearMode,
+    ///drawn into offscreen target instead of screen, before all screen views
+    pub target: Option<TargetPtr>,
+}
+
+impl View {
+    ///Whole screen, all layers
+    pub fn full(view: glm::Mat4, proj: glm::Mat4, position: glm::Vec3) -> Self {
+        Self {
+            view,
+            proj,
+            position,
+            viewport: ViewportRect::FULL,
+            layers: LAYER_ALL,
+            clear: ClearMode::Background,
+            target: None,
+        }
+    }
+
+    ///Pixel size of what the view draws into
+    pub fn target_extent(&self, screen: (u32, u32)) -> (u32, u32) {
+        self.target.map_or(screen, |t| t.extent())
+    }
+}
+
+///Per-frame state collected from [`RenderCommand`]s, kept until the next `PushState`
+///
+///[`RenderCommand`]: ../../render/enum.RenderCommand.html
+#[derive(Debug, Clone)]
+pub struct FrameState {
+    pub view: glm::Mat4,
+    pub proj: glm::Mat4,
+    pub camera_position: glm::Vec3,
+    pub ambient: glm::Vec3,
+    pub lights: Vec<(glm::Vec3, Light)>,
+    pub debug_mode: DebugMode,
+}
+
+impl Default for FrameState {
+    fn default() -> Self {
+        Self {
+            view: glm::identity(),
+            proj: glm::identity(),
+            camera_position: glm::vec3(0., 0., 0.),
+            ambient: glm::vec3(0.2, 0.2, 0.2),
+            lights: Vec::new(),
+            debug_mode: DebugMode::default(),
+        }
+    }
+}
+
+///std140 mirror of `Light` struct in shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LightUniform {
+    ///xyz - position, w - kind
+    pub position: [f32; 4],
+    ///xyz - direction, w - range
+    pub direction: [f32; 4],
+    ///rgb - color, a - intensity
+    pub color: [f32; 4],
+    ///x - cos of inner cone, y - cos of outer cone
+    pub cone: [f32; 4],
+}
+
+///std140 mirror of `FrameState` block in shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FrameUniform {
+    pub view: [f32; 16],
+    pub proj: [f32; 16],
+    pub camera_position: [f32; 4],
+    pub ambient: [f32; 4],
+    pub light_count: [u32; 4],
+    pub lights: [LightUniform; MAX_LIGHTS],
+    pub shadow_view_proj: [[f32; 16]; MAX_CASCADES],
+    ///far view distance of every cascade
+    pub cascade_splits: [f32; 4],
+    ///x - cascade count (0 - no shadows), y - depth bias
+    pub shadow_params: [f32; 4],
+    ///x - `DebugMode`, y - near, z - far
+    pub debug: [f32; 4],
+}
+
+impl LightUniform {
+    pub fn new(position: &glm::Vec3, light: &Light) -> Self {
+        let (kind, direction, cone) = match light.kind {
+            LightKind::Point => (LIGHT_POINT, glm::vec3(0., -1., 0.), [-1., -1.]),
+            LightKind::Directional { direction } => (LIGHT_DIRECTIONAL, direction, [-1., -1.]),
+            LightKind::Spot { direction, inner, outer } => (
+                LIGHT_SPOT,
+                direction,
+                [inner.to_radians().cos(), outer.to_radians().cos()]
+            ),
+        };
+        let direction = glm::normalize(&direction);
+        Self {
+            position: [position.x, position.y, position.z, kind],
+            direction: [direction.x, direction.y, direction.z, light.range],
+            color: [light.color.x, light.color.y, light.color.z, light.intensity],
+            cone: [cone[0], cone[1], 0., 0.],
+        }
+    }
+}
+
+///Render pass a draw goes to, highest bits of [`SortKey`]
+///
+///[`SortKey`]: struct.SortKey.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PassKind {
+    Opaque = 0,
+    Transparent = 1,
+}
+
+///Pipeline a draw is recorded with, below [`PassKind`] in [`SortKey`]
+///
+///[`PassKind`]: enum.PassKind.html
+///[`SortKey`]: struct.SortKey.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PipelineKind {
+    Opaque = 0,
+    Transparent = 1,
+    Wireframe = 2,
+    Shadow = 3,
+}
+
+const DEPTH_BITS: u64 = 24;
+const MESH_BITS: u64 = 20;
+const MATERIAL_BITS: u64 = 12;
+const PIPELINE_BITS: u64 = 6;
+
+///Draw order, compared as a plain integer
+///
+///Opaque: `pass | pipeline | material | mesh | depth`, state grouped and front-to-back within a batch.
+///Transparent: `pass | pipeline | !depth | material | mesh`, strictly back-to-front.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey(pub u64);
+
+impl SortKey {
+    ///`depth` is view distance normalized to 0..1, `mesh` is a per-frame id below `1 << 20`
+    pub fn new(pass: PassKind, pipeline: PipelineKind, material: u32, mesh: u32, depth: f32) -> Self {
+        let field = |value: u64, bits: u64| value & ((1 << bits) - 1);
+        let depth = (depth.max(0.).min(1.) * ((1 << DEPTH_BITS) - 1) as f32) as u64;
+        let pipeline = pipeline as u64;
+        let material = field(material as u64, MATERIAL_BITS);
+        let mesh = field(mesh as u64, MESH_BITS);
+        let head = (pass as u64) << (64 - 2) | pipeline << (64 - 2 - PIPELINE_BITS);
+        let tail = match pass {
+            PassKind::Opaque => (material << MESH_BITS | mesh) << DEPTH_BITS | depth,
+            PassKind::Transparent => {
+                field(!depth, DEPTH_BITS) << (MATERIAL_BITS + MESH_BITS) | material << MESH_BITS | mesh
+            }
+        };
+        SortKey(head | tail)
+    }
+
+    pub fn pass(&self) -> PassKind {
+        if self.0 >> (64 - 2) == PassKind::Transparent as u64 {
+            PassKind::Transparent
+        } else {
+            PassKind::Opaque
+        }
+    }
+}
+
+impl FrameState {
+    ///Same lights and settings seen from `view`
+    pub fn for_view(&self, view: &View) -> Self {
+        Self {
+            view: view.view,
+            proj: view.proj,
+            camera_position: view.position,
+            ..self.clone()
+        }
+    }
+
+    ///near and far planes of GL style perspective or orthographic `proj`
+    pub fn clip_planes(&self) -> (f32, f32) {
+        let (m22, m23) = (self.proj[(2, 2)], self.proj[(2, 3)]);
+        if self.proj[(3, 3)] != 0. {
+            //orthographic: m(2,2) = -2/(f-n), m(2,3) = -(f+n)/(f-n)
+            return ((m23 + 1.) / m22, (m23 - 1.) / m22);
+        }
+        //perspective: m(2,2) = -(f+n)/(f-n), m(2,3) = -2fn/(f-n)
+        (m23 / (m22 - 1.), m23 / (m22 + 1.))
+    }
+
+    ///Normalized view distance of `point` for sort keys
+    pub fn sort_depth(&self, point: &glm::Vec3) -> f32 {
+        let (near, far) = self.clip_planes();
+        let view = self.view * glm::vec4(point.x, point.y, point.z, 1.);
+        (-view.z - near) / (far - near)
+    }
+
+    pub fn as_uniform(&self, shadows: Option<&ShadowCascades>, shadow_config: &ShadowConfig) -> FrameUniform {
+        let mut view = [0_f32; 16];
+        let mut proj = [0_f32; 16];
+        view.copy_from_slice(self.view.as_slice());
+        proj.copy_from_slice(self.proj.as_slice());
+
+        let mut lights = [LightUniform::default(); MAX_LIGHTS];
+        for (dst, (position, light)) in lights.iter_mut().zip(self.lights.iter()) {
+            *dst = LightUniform::new(position, light);
+        }
+        let mut shadow_view_proj = [[0_f32; 16]; MAX_CASCADES];
+        let mut cascade_splits = [0_f32; 4];
+        let mut cascade_count = 0;
+        if let Some(cascades) = shadows {
+            for (i, (vp, split)) in cascades.view_proj.iter().zip(cascades.splits.iter()).enumerate() {
+                shadow_view_proj[i].copy_from_slice(vp.as_slice());
+                cascade_splits[i] = *split;
+            }
+            cascade_count = cascades.view_proj.len();
+        }
+
+        let p = &self.camera_position;
+        let a = &self.ambient;
+        let (near, far) = self.clip_planes();
+        FrameUniform {
+            view,
+            proj,
+            camera_position: [p.x, p.y, p.z, 1.],
+            ambient: [a.x, a.y, a.z, 1.],
+            light_count: [self.lights.len().min(MAX_LIGHTS) as u32, 0, 0, 0],
+            lights,
+            shadow_view_proj,
+            cascade_splits,
+            shadow_params: [cascade_count as f32, shadow_config.bias, 0., 0.],
+            debug: [self.debug_mode as u32 as f32, near, far, 0.],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opaque_keys_go_front_to_back_within_batch() {
+        let near = SortKey::new(PassKind::Opaque, PipelineKind::Opaque, 3, 7, 0.2);
+        let far = SortKey::new(PassKind::Opaque, PipelineKind::Opaque, 3, 7, 0.8);
+        assert!(near < far);
+
+        //state changes outrank depth
+        let other_mesh = SortKey::new(PassKind::Opaque, PipelineKind::Opaque, 3, 8, 0.);
+        assert!(far < other_mesh);
+        let other_material = SortKey::new(PassKind::Opaque, PipelineKind::Opaque, 4, 0, 0.);
+        assert!(other_mesh < other_material);
+    }
+
+    #[test]
+    fn transparent_keys_go_back_to_front_across_materials() {
+        let far = SortKey::new(PassKind::Transparent, PipelineKind::Transparent, 9, 1, 0.9);
+        let middle = SortKey::new(PassKind::Transparent, PipelineKind::Transparent, 0, 5, 0.5);
+        let near = SortKey::new(PassKind::Transparent, PipelineKind::Transparent, 5, 2, 0.1);
+        assert!(far < middle);
+        assert!(middle < near);
+    }
+
+    #[test]
+    fn opaque_pass_goes_before_transparent() {
+        let transparent = SortKey::new(PassKind::Transparent, PipelineKind::Transparent, 0, 0, 1.);
+        let wireframe = SortKey::new(PassKind::Opaque, PipelineKind::Wireframe, 0xfff, 0xfffff, 1.);
+        assert!(wireframe < transparent);
+    }
+
+    #[test]
+    fn pass_round_trips() {
+        let pipelines = [PipelineKind::Opaque, PipelineKind::Transparent, PipelineKind::Wireframe, PipelineKind::Shadow];
+        for &pass in &[PassKind::Opaque, PassKind::Transparent] {
+            for &pipeline in &pipelines {
+                for &depth in &[0., 0.5, 1.] {
+                    assert_eq!(SortKey::new(pass, pipeline, 0xfff, 0xfffff, depth).pass(), pass);
+                    assert_eq!(SortKey::new(pass, pipeline, 0, 0, depth).pass(), pass);
+                }
+            }
+        }
+    }
+}
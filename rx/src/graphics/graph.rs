@@ -0,0 +1,736 @@
+use hal::{
+    Backend,
+    device::Device,
+    format::{Aspects, Format},
+    image::{Access, Layout, NumSamples, Usage},
+    memory::Dependencies,
+    pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, SubpassDependency, SubpassDesc},
+    pso::PipelineStage,
+};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+///Extent class of a graph resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    ///follows swapchain, can be aliased with other swapchain sized resources
+    Swapchain,
+    ///picked by resource owner (e.g. shadow resolution), never aliased
+    Fixed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceDesc {
+    pub name: &'static str,
+    pub format: Format,
+    pub samples: NumSamples,
+    pub size: Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UseKind {
+    Color,
+    Depth,
+    Resolve,
+    Sampled,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Use {
+    resource: ResourceId,
+    kind: UseKind,
+    clear: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Resource {
+    desc: ResourceDesc,
+    ///final layout of image owned outside the frame, e.g. `Present` for swapchain
+    imported: Option<Layout>,
+}
+
+#[derive(Debug, Clone)]
+struct Pass {
+    name: &'static str,
+    uses: Vec<Use>,
+}
+
+///Declarative description of frame passes and images they touch
+///
+///Passes run in declaration order. Each declares attachments it writes and images it samples,
+///`compile` derives load/store ops, layouts, external dependencies and transient images from
+///that alone, without a device.
+#[derive(Debug, Clone, Default)]
+pub struct RenderGraph {
+    resources: Vec<Resource>,
+    passes: Vec<Pass>,
+}
+
+pub struct PassBuilder<'a> {
+    graph: &'a mut RenderGraph,
+    pass: usize,
+}
+
+impl<'a> PassBuilder<'a> {
+    fn with(self, resource: ResourceId, kind: UseKind, clear: bool) -> Self {
+        self.graph.passes[self.pass].uses.push(Use { resource, kind, clear });
+        self
+    }
+
+    ///Attachments are numbered in declaration order, framebuffers must follow it
+    pub fn color(self, resource: ResourceId, clear: bool) -> Self {
+        self.with(resource, UseKind::Color, clear)
+    }
+
+    pub fn depth(self, resource: ResourceId, clear: bool) -> Self {
+        self.with(resource, UseKind::Depth, clear)
+    }
+
+    ///Resolves color attachment declared at the same position
+    pub fn resolve(self, resource: ResourceId) -> Self {
+        self.with(resource, UseKind::Resolve, false)
+    }
+
+    pub fn sample(self, resource: ResourceId) -> Self {
+        self.with(resource, UseKind::Sampled, false)
+    }
+
+    pub fn id(&self) -> PassId {
+        PassId(self.pass)
+    }
+}
+
+impl RenderGraph {
+    ///Image created and owned for the graph, its contents don't outlive the frame
+    pub fn create(&mut self, desc: ResourceDesc) -> ResourceId {
+        self.resources.push(Resource { desc, imported: None });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    ///Image owned elsewhere and left in `final_layout` after its last use
+    pub fn import(&mut self, desc: ResourceDesc, final_layout: Layout) -> ResourceId {
+        self.resources.push(Resource { desc, imported: Some(final_layout) });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, name: &'static str) -> PassBuilder {
+        self.passes.push(Pass { name, uses: Vec::new() });
+        let pass = self.passes.len() - 1;
+        PassBuilder { graph: self, pass }
+    }
+
+    pub fn compile(&self) -> Result<CompiledGraph, &'static str> {
+        self.validate()?;
+        let alive = self.alive_passes();
+        //uses of every resource by alive passes, in execution order
+        let mut timeline: Vec<Vec<(usize, Use)>> = vec![Vec::new(); self.resources.len()];
+        for (p, pass) in self.passes.iter().enumerate().filter(|(p, _)| alive[*p]) {
+            for u in pass.uses.iter() {
+                timeline[u.resource.0].push((p, *u));
+            }
+        }
+
+        let mut passes = Vec::with_capacity(self.passes.len());
+        for (p, pass) in self.passes.iter().enumerate() {
+            if !alive[p] {
+                debug!("Render graph: pass '{}' culled", pass.name);
+                passes.push(None);
+                continue;
+            }
+            passes.push(Some(self.compile_pass(p, &timeline)));
+        }
+
+        let resources = self.compile_resources(&timeline);
+        Ok(CompiledGraph { passes, resources })
+    }
+
+    fn validate(&self) -> Result<(), &'static str> {
+        let mut written = vec![false; self.resources.len()];
+        for pass in self.passes.iter() {
+            let attachments: Vec<_> = pass.uses.iter().filter(|u| u.kind != UseKind::Sampled).collect();
+            if attachments.iter().filter(|u| u.kind == UseKind::Depth).count() > 1 {
+                return Err("Render graph pass has more than one depth attachment!");
+            }
+            let colors = attachments.iter().filter(|u| u.kind == UseKind::Color).count();
+            let resolves = attachments.iter().filter(|u| u.kind == UseKind::Resolve).count();
+            if resolves > 0 && resolves != colors {
+                return Err("Render graph pass must resolve all color attachments or none!");
+            }
+            let samples: Vec<_> = attachments.iter()
+                .filter(|u| u.kind != UseKind::Resolve)
+                .map(|u| self.resources[u.resource.0].desc.samples)
+                .collect();
+            if samples.windows(2).any(|w| w[0] != w[1]) {
+                return Err("Render graph pass attachments differ in sample count!");
+            }
+            for u in pass.uses.iter() {
+                if u.kind == UseKind::Resolve && self.resources[u.resource.0].desc.samples != 1 {
+                    return Err("Render graph resolve target must be single sampled!");
+                }
+                if u.kind == UseKind::Sampled {
+                    if attachments.iter().any(|a| a.resource == u.resource) {
+                        return Err("Render graph pass samples its own attachment!");
+                    }
+                    let imported = self.resources[u.resource.0].imported.is_some();
+                    if !written[u.resource.0] && !imported {
+                        return Err("Render graph pass samples resource nothing wrote before!");
+                    }
+                }
+            }
+            for a in attachments {
+                written[a.resource.0] = true;
+            }
+        }
+        Ok(())
+    }
+
+    ///Pass is alive if it writes imported resource or something sampled by alive pass
+    fn alive_passes(&self) -> Vec<bool> {
+        let mut alive = vec![false; self.passes.len()];
+        let mut needed: Vec<bool> = self.resources.iter().map(|r| r.imported.is_some()).collect();
+        for (p, pass) in self.passes.iter().enumerate().rev() {
+            let writes_needed = pass.uses.iter()
+                .any(|u| u.kind != UseKind::Sampled && needed[u.resource.0]);
+            if writes_needed {
+                alive[p] = true;
+                for u in pass.uses.iter().filter(|u| u.kind == UseKind::Sampled) {
+                    needed[u.resource.0] = true;
+                }
+            }
+        }
+        alive
+    }
+
+    fn is_depth(&self, resource: ResourceId) -> bool {
+        self.resources[resource.0].desc.format.surface_desc().aspects.contains(Aspects::DEPTH)
+    }
+
+    fn layout(&self, u: &Use) -> Layout {
+        match u.kind {
+            UseKind::Color | UseKind::Resolve => Layout::ColorAttachmentOptimal,
+            UseKind::Depth => Layout::DepthStencilAttachmentOptimal,
+            UseKind::Sampled if self.is_depth(u.resource) => Layout::DepthStencilReadOnlyOptimal,
+            UseKind::Sampled => Layout::ShaderReadOnlyOptimal,
+        }
+    }
+
+    fn compile_pass(&self, p: usize, timeline: &[Vec<(usize, Use)>]) -> CompiledPass {
+        let pass = &self.passes[p];
+        let mut attachments = Vec::new();
+        let mut colors = Vec::new();
+        let mut resolves = Vec::new();
+        let mut depth = None;
+        let mut incoming = (PipelineStage::empty(), Access::empty(), PipelineStage::empty(), Access::empty());
+        let mut outgoing = (PipelineStage::empty(), Access::empty(), PipelineStage::empty(), Access::empty());
+
+        for u in pass.uses.iter() {
+            let resource = &self.resources[u.resource.0];
+            let uses = &timeline[u.resource.0];
+            let at = uses.iter().position(|(up, uu)| *up == p && uu.kind == u.kind)
+                .expect("pass uses are in timeline");
+            let previous = uses[..at].iter().rev().find(|(up, _)| *up != p);
+            let next = uses[at + 1..].iter().find(|(up, _)| *up != p);
+
+            //hazards with previous use, or with the last one of previous frame
+            let (dst_stage, dst_access) = stage_access(u.kind);
+            let wrapped = match (previous, resource.imported) {
+                (Some(prev), _) => Some(prev),
+                (None, None) => uses.last(),
+                (None, Some(_)) => None,
+            };
+            match wrapped {
+                Some((_, prev)) => {
+                    let (src_stage, src_access) = stage_access(prev.kind);
+                    incoming.0 |= src_stage;
+                    incoming.1 |= src_access;
+                }
+                //imported image is handed over at color output, e.g. by swapchain acquire
+                None => incoming.0 |= PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            }
+            incoming.2 |= dst_stage;
+            incoming.3 |= dst_access;
+            if let Some((_, next)) = next {
+                let (next_stage, next_access) = stage_access(next.kind);
+                outgoing.0 |= dst_stage;
+                outgoing.1 |= dst_access & (Access::COLOR_ATTACHMENT_WRITE | Access::DEPTH_STENCIL_ATTACHMENT_WRITE);
+                outgoing.2 |= next_stage;
+                outgoing.3 |= next_access;
+            }
+
+            if u.kind == UseKind::Sampled {
+                continue;
+            }
+            let written_before = previous.map_or(false, |(_, prev)| prev.kind != UseKind::Sampled);
+            let load = if u.clear {
+                AttachmentLoadOp::Clear
+            } else if written_before {
+                AttachmentLoadOp::Load
+            } else {
+                AttachmentLoadOp::DontCare
+            };
+            let store = if next.is_some() || resource.imported.is_some() {
+                AttachmentStoreOp::Store
+            } else {
+                AttachmentStoreOp::DontCare
+            };
+            let initial = match (load, previous) {
+                (AttachmentLoadOp::Load, Some((_, prev))) => self.layout(prev),
+                _ => Layout::Undefined,
+            };
+            let last = match (next, resource.imported) {
+                (Some((_, next)), _) => self.layout(next),
+                (None, Some(layout)) => layout,
+                (None, None) => self.layout(u),
+            };
+            let index = attachments.len();
+            attachments.push((u.resource, Attachment {
+                format: Some(resource.desc.format),
+                samples: resource.desc.samples,
+                ops: AttachmentOps { load, store },
+                stencil_ops: AttachmentOps::DONT_CARE,
+                layouts: initial..last,
+            }));
+            match u.kind {
+                UseKind::Color => colors.push((index, Layout::ColorAttachmentOptimal)),
+                UseKind::Resolve => resolves.push((index, Layout::ColorAttachmentOptimal)),
+                UseKind::Depth => depth = Some((index, Layout::DepthStencilAttachmentOptimal)),
+                UseKind::Sampled => (),
+            }
+        }
+
+        let mut dependencies = vec![SubpassDependency {
+            passes: None..Some(0),
+            stages: incoming.0..incoming.2,
+            accesses: incoming.1..incoming.3,
+            flags: Dependencies::empty(),
+        }];
+        if !outgoing.0.is_empty() {
+            dependencies.push(SubpassDependency {
+                passes: Some(0)..None,
+                stages: outgoing.0..outgoing.2,
+                accesses: outgoing.1..outgoing.3,
+                flags: Dependencies::empty(),
+            });
+        }
+        CompiledPass {
+            name: pass.name,
+            attachments,
+            colors,
+            resolves,
+            depth,
+            dependencies,
+        }
+    }
+
+    fn compile_resources(&self, timeline: &[Vec<(usize, Use)>]) -> Vec<CompiledResource> {
+        let mut compiled: Vec<CompiledResource> = Vec::with_capacity(self.resources.len());
+        //physical images and last pass using them
+        let mut physical: Vec<(ResourceDesc, usize)> = Vec::new();
+        for (r, resource) in self.resources.iter().enumerate() {
+            let uses = &timeline[r];
+            let mut usage = uses.iter().fold(Usage::empty(), |usage, (_, u)| usage | match u.kind {
+                UseKind::Color | UseKind::Resolve => Usage::COLOR_ATTACHMENT,
+                UseKind::Depth => Usage::DEPTH_STENCIL_ATTACHMENT,
+                UseKind::Sampled => Usage::SAMPLED,
+            });
+            let first = uses.first().map_or(0, |(p, _)| *p);
+            let last = uses.last().map_or(0, |(p, _)| *p);
+            //written and dropped within one pass, may live in tile memory only
+            let transient = resource.imported.is_none() && !uses.is_empty() && first == last
+                && uses.iter().all(|(_, u)| u.kind != UseKind::Sampled);
+            if transient {
+                usage |= Usage::TRANSIENT_ATTACHMENT;
+            }
+            let slot = match (resource.imported, resource.desc.size) {
+                (None, Size::Swapchain) if !uses.is_empty() => {
+                    let free = physical.iter().position(|(desc, end)| {
+                        desc.format == resource.desc.format && desc.samples == resource.desc.samples
+                            && desc.size == Size::Swapchain && *end < first
+                    });
+                    match free {
+                        Some(slot) => {
+                            physical[slot].1 = last;
+                            slot
+                        }
+                        None => {
+                            physical.push((resource.desc, last));
+                            physical.len() - 1
+                        }
+                    }
+                }
+                _ => {
+                    physical.push((resource.desc, last));
+                    physical.len() - 1
+                }
+            };
+            compiled.push(CompiledResource { usage, transient, physical: slot });
+        }
+        compiled
+    }
+}
+
+fn stage_access(kind: UseKind) -> (PipelineStage, Access) {
+    match kind {
+        UseKind::Color | UseKind::Resolve => (
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE,
+        ),
+        UseKind::Depth => (
+            PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            Access::DEPTH_STENCIL_ATTACHMENT_READ | Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        UseKind::Sampled => (PipelineStage::FRAGMENT_SHADER, Access::SHADER_READ),
+    }
+}
+
+///Single subpass render pass derived by `RenderGraph::compile`
+#[derive(Debug, Clone)]
+pub struct CompiledPass {
+    pub name: &'static str,
+    ///framebuffer order
+    pub attachments: Vec<(ResourceId, Attachment)>,
+    pub colors: Vec<(usize, Layout)>,
+    pub resolves: Vec<(usize, Layout)>,
+    pub depth: Option<(usize, Layout)>,
+    pub dependencies: Vec<SubpassDependency>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompiledResource {
+    ///image usage covering all passes
+    pub usage: Usage,
+    pub transient: bool,
+    ///resources with the same slot don't overlap in time and may share an image
+    pub physical: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompiledGraph {
+    ///none for culled passes
+    passes: Vec<Option<CompiledPass>>,
+    resources: Vec<CompiledResource>,
+}
+
+impl CompiledGraph {
+    pub fn pass(&self, pass: PassId) -> Option<&CompiledPass> {
+        self.passes[pass.0].as_ref()
+    }
+
+    pub fn resource(&self, resource: ResourceId) -> &CompiledResource {
+        &self.resources[resource.0]
+    }
+
+    pub fn create_render_pass<B: Backend>(&self, device: &B::Device, pass: PassId) -> Result<B::RenderPass, &'static str> {
+        let pass = self.pass(pass).ok_or("Render graph pass was culled!")?;
+        let subpass = SubpassDesc {
+            colors: &pass.colors,
+            depth_stencil: pass.depth.as_ref(),
+            inputs: &[],
+            resolves: &pass.resolves,
+            preserves: &[],
+        };
+        unsafe {
+            device
+                .create_render_pass(
+                    pass.attachments.iter().map(|(_, a)| a.clone()),
+                    &[subpass],
+                    &pass.dependencies,
+                )
+                .map_err(|e| {
+                    error!("{}: {:?}", pass.name, e);
+                    "Couldn't create a render pass!"
+                })
+        }
+    }
+}
+
+///Passes and images of one frame: shadow cascades, scene, post-processing chain, present
+pub struct FrameGraph {
+    pub graph: CompiledGraph,
+    pub shadow: PassId,
+    pub scene: PassId,
+    ///first effect, samples `hdr` and writes the first post target
+    pub post: PassId,
+    ///later effects, each samples the other post target, see `post_pass`
+    pub post_pong: PassId,
+    pub post_ping: PassId,
+    pub present: PassId,
+    ///multisampled scene color, none without MSAA
+    pub msaa: Option<ResourceId>,
+    pub depth: ResourceId,
+    pub hdr: ResourceId,
+    ///post chain alternates between them, effect `i` writes `i % 2`
+    pub post_targets: [ResourceId; 2],
+}
+
+impl FrameGraph {
+    pub fn new(samples: NumSamples, hdr_format: Format, swapchain_format: Format) -> Result<Self, &'static str> {
+        let mut graph = RenderGraph::default();
+        let swapchain_sized = |name, format, samples| ResourceDesc {
+            name,
+            format,
+            samples,
+            size: Size::Swapchain,
+        };
+        let shadow_map = graph.create(ResourceDesc {
+            name: "shadow_map",
+            format: Format::D32Sfloat,
+            samples: 1,
+            size: Size::Fixed,
+        });
+        let msaa = if samples > 1 {
+            Some(graph.create(swapchain_sized("msaa", hdr_format, samples)))
+        } else {
+            None
+        };
+        let depth = graph.create(swapchain_sized("depth", Format::D32Sfloat, samples));
+        let hdr = graph.create(swapchain_sized("hdr", hdr_format, 1));
+        let ping = graph.create(swapchain_sized("post_ping", hdr_format, 1));
+        let pong = graph.create(swapchain_sized("post_pong", hdr_format, 1));
+        let backbuffer = graph.import(swapchain_sized("backbuffer", swapchain_format, 1), Layout::Present);
+
+        let shadow = graph.add_pass("shadow").depth(shadow_map, true).id();
+        let scene = match msaa {
+            Some(msaa) => graph.add_pass("scene")
+                .color(msaa, true)
+                .depth(depth, true)
+                .resolve(hdr)
+                .sample(shadow_map)
+                .id(),
+            None => graph.add_pass("scene")
+                .color(hdr, true)
+                .depth(depth, true)
+                .sample(shadow_map)
+                .id(),
+        };
+        let post = graph.add_pass("post").color(ping, false).sample(hdr).id();
+        let post_pong = graph.add_pass("post_pong").color(pong, false).sample(ping).id();
+        let post_ping = graph.add_pass("post_ping").color(ping, false).sample(pong).id();
+        let present = graph.add_pass("present")
+            .color(backbuffer, false)
+            .sample(hdr)
+            .sample(ping)
+            .sample(pong)
+            .id();
+
+        Ok(Self {
+            graph: graph.compile()?,
+            shadow,
+            scene,
+            post,
+            post_pong,
+            post_ping,
+            present,
+            msaa,
+            depth,
+            hdr,
+            post_targets: [ping, pong],
+        })
+    }
+
+    ///Pass of effect `i` in post chain, its render pass carries barriers for reading the previous result
+    pub fn post_pass(&self, effect: usize) -> PassId {
+        match effect {
+            0 => self.post,
+            i if i % 2 == 1 => self.post_pong,
+            _ => self.post_ping,
+        }
+    }
+
+    pub fn create_render_pass<B: Backend>(&self, device: &B::Device, pass: PassId) -> Result<B::RenderPass, &'static str> {
+        self.graph.create_render_pass::<B>(device, pass)
+    }
+
+    pub fn usage(&self, resource: ResourceId) -> Usage {
+        self.graph.resource(resource).usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: Format = Format::Rgba16Sfloat;
+
+    fn desc(name: &'static str, format: Format, samples: NumSamples, size: Size) -> ResourceDesc {
+        ResourceDesc { name, format, samples, size }
+    }
+
+    fn backbuffer(graph: &mut RenderGraph) -> ResourceId {
+        graph.import(desc("backbuffer", Format::Bgra8Srgb, 1, Size::Swapchain), Layout::Present)
+    }
+
+    #[test]
+    fn ops_and_layouts_follow_neighbour_uses() {
+        let mut graph = RenderGraph::default();
+        let color = graph.create(desc("color", COLOR, 1, Size::Swapchain));
+        let back = backbuffer(&mut graph);
+        let draw = graph.add_pass("draw").color(color, true).id();
+        let overlay = graph.add_pass("overlay").color(color, false).id();
+        let blit = graph.add_pass("blit").color(back, false).sample(color).id();
+        let compiled = graph.compile().unwrap();
+
+        let draw = &compiled.pass(draw).unwrap().attachments[0].1;
+        assert_eq!(draw.ops.load, AttachmentLoadOp::Clear);
+        assert_eq!(draw.ops.store, AttachmentStoreOp::Store);
+        assert_eq!(draw.layouts, Layout::Undefined..Layout::ColorAttachmentOptimal);
+
+        let overlay = &compiled.pass(overlay).unwrap().attachments[0].1;
+        assert_eq!(overlay.ops.load, AttachmentLoadOp::Load);
+        assert_eq!(overlay.ops.store, AttachmentStoreOp::Store);
+        assert_eq!(overlay.layouts, Layout::ColorAttachmentOptimal..Layout::ShaderReadOnlyOptimal);
+
+        let blit = &compiled.pass(blit).unwrap().attachments[0].1;
+        assert_eq!(blit.ops.load, AttachmentLoadOp::DontCare);
+        assert_eq!(blit.ops.store, AttachmentStoreOp::Store);
+        assert_eq!(blit.layouts, Layout::Undefined..Layout::Present);
+    }
+
+    #[test]
+    fn last_use_is_not_stored() {
+        let mut graph = RenderGraph::default();
+        let color = graph.create(desc("color", COLOR, 1, Size::Swapchain));
+        let depth = graph.create(desc("depth", Format::D32Sfloat, 1, Size::Swapchain));
+        let back = backbuffer(&mut graph);
+        let scene = graph.add_pass("scene").color(color, true).depth(depth, true).id();
+        graph.add_pass("blit").color(back, false).sample(color);
+        let compiled = graph.compile().unwrap();
+
+        let scene = compiled.pass(scene).unwrap();
+        assert_eq!(scene.depth, Some((1, Layout::DepthStencilAttachmentOptimal)));
+        let depth = &scene.attachments[1].1;
+        assert_eq!(depth.ops.store, AttachmentStoreOp::DontCare);
+        assert_eq!(depth.layouts, Layout::Undefined..Layout::DepthStencilAttachmentOptimal);
+    }
+
+    #[test]
+    fn dependencies_cover_previous_and_next_use() {
+        let mut graph = RenderGraph::default();
+        let color = graph.create(desc("color", COLOR, 1, Size::Swapchain));
+        let back = backbuffer(&mut graph);
+        let draw = graph.add_pass("draw").color(color, true).id();
+        let blit = graph.add_pass("blit").color(back, false).sample(color).id();
+        let compiled = graph.compile().unwrap();
+        let color_rw = Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE;
+
+        //written after being sampled by the previous frame
+        let draw = &compiled.pass(draw).unwrap().dependencies;
+        assert_eq!(draw.len(), 2);
+        assert_eq!(draw[0].passes, None..Some(0));
+        assert_eq!(draw[0].stages, PipelineStage::FRAGMENT_SHADER..PipelineStage::COLOR_ATTACHMENT_OUTPUT);
+        assert_eq!(draw[0].accesses, Access::SHADER_READ..color_rw);
+        assert_eq!(draw[1].passes, Some(0)..None);
+        assert_eq!(draw[1].stages, PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::FRAGMENT_SHADER);
+        assert_eq!(draw[1].accesses, Access::COLOR_ATTACHMENT_WRITE..Access::SHADER_READ);
+
+        //samples what draw wrote, backbuffer comes from acquire
+        let blit = &compiled.pass(blit).unwrap().dependencies;
+        assert_eq!(blit.len(), 1);
+        assert_eq!(
+            blit[0].stages,
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::COLOR_ATTACHMENT_OUTPUT | PipelineStage::FRAGMENT_SHADER
+        );
+        assert_eq!(blit[0].accesses, color_rw..color_rw | Access::SHADER_READ);
+    }
+
+    #[test]
+    fn unread_passes_are_culled() {
+        let mut graph = RenderGraph::default();
+        let unused = graph.create(desc("unused", COLOR, 1, Size::Swapchain));
+        let feeds_unused = graph.create(desc("feeds_unused", COLOR, 1, Size::Swapchain));
+        let used = graph.create(desc("used", COLOR, 1, Size::Swapchain));
+        let back = backbuffer(&mut graph);
+        let first = graph.add_pass("first").color(feeds_unused, true).id();
+        let second = graph.add_pass("second").color(unused, true).sample(feeds_unused).id();
+        let kept = graph.add_pass("kept").color(used, true).id();
+        let blit = graph.add_pass("blit").color(back, false).sample(used).id();
+        let compiled = graph.compile().unwrap();
+
+        assert!(compiled.pass(first).is_none());
+        assert!(compiled.pass(second).is_none());
+        assert!(compiled.pass(kept).is_some());
+        assert!(compiled.pass(blit).is_some());
+    }
+
+    #[test]
+    fn transient_and_aliased_resources() {
+        let mut graph = RenderGraph::default();
+        let msaa = graph.create(desc("msaa", COLOR, 4, Size::Swapchain));
+        let a = graph.create(desc("a", COLOR, 1, Size::Swapchain));
+        let b = graph.create(desc("b", COLOR, 1, Size::Swapchain));
+        let c = graph.create(desc("c", COLOR, 1, Size::Swapchain));
+        let fixed = graph.create(desc("fixed", COLOR, 1, Size::Fixed));
+        let back = backbuffer(&mut graph);
+        graph.add_pass("scene").color(msaa, true).resolve(a);
+        graph.add_pass("b").color(b, true).sample(a);
+        graph.add_pass("c").color(c, true).color(fixed, true).sample(b);
+        graph.add_pass("blit").color(back, false).sample(c).sample(fixed);
+        let compiled = graph.compile().unwrap();
+
+        assert!(compiled.resource(msaa).transient);
+        assert!(compiled.resource(msaa).usage.contains(Usage::TRANSIENT_ATTACHMENT));
+        assert!(!compiled.resource(a).transient);
+        assert!(!compiled.resource(back).transient);
+        assert!(compiled.resource(a).usage.contains(Usage::COLOR_ATTACHMENT | Usage::SAMPLED));
+
+        //`c` starts after `a` is last sampled, `b` overlaps both
+        assert_eq!(compiled.resource(c).physical, compiled.resource(a).physical);
+        assert_ne!(compiled.resource(b).physical, compiled.resource(a).physical);
+        assert_ne!(compiled.resource(fixed).physical, compiled.resource(a).physical);
+        assert_ne!(compiled.resource(msaa).physical, compiled.resource(a).physical);
+        assert_ne!(compiled.resource(back).physical, compiled.resource(a).physical);
+    }
+
+    #[test]
+    fn sampling_own_attachment_is_rejected() {
+        let mut graph = RenderGraph::default();
+        let color = graph.create(desc("color", COLOR, 1, Size::Swapchain));
+        graph.add_pass("first").color(color, true);
+        graph.add_pass("feedback").color(color, false).sample(color);
+        assert_eq!(graph.compile().unwrap_err(), "Render graph pass samples its own attachment!");
+    }
+
+    #[test]
+    fn reading_before_write_is_rejected() {
+        let mut graph = RenderGraph::default();
+        let color = graph.create(desc("color", COLOR, 1, Size::Swapchain));
+        let back = backbuffer(&mut graph);
+        graph.add_pass("blit").color(back, false).sample(color);
+        graph.add_pass("draw").color(color, true);
+        assert_eq!(graph.compile().unwrap_err(), "Render graph pass samples resource nothing wrote before!");
+    }
+
+    #[test]
+    fn mixed_sample_counts_are_rejected() {
+        let mut graph = RenderGraph::default();
+        let msaa = graph.create(desc("msaa", COLOR, 4, Size::Swapchain));
+        let depth = graph.create(desc("depth", Format::D32Sfloat, 1, Size::Swapchain));
+        graph.add_pass("scene").color(msaa, true).depth(depth, true);
+        assert_eq!(graph.compile().unwrap_err(), "Render graph pass attachments differ in sample count!");
+    }
+
+    #[test]
+    fn post_chain_reads_previous_effect() {
+        let frame = FrameGraph::new(4, COLOR, Format::Bgra8Srgb).unwrap();
+        let [ping, pong] = frame.post_targets;
+        assert_eq!(frame.post_pass(0), frame.post);
+        assert_eq!(frame.post_pass(1), frame.post_pong);
+        assert_eq!(frame.post_pass(2), frame.post_ping);
+        assert_eq!(frame.post_pass(3), frame.post_pong);
+
+        let pong_pass = frame.graph.pass(frame.post_pong).unwrap();
+        assert_eq!(pong_pass.attachments[0].0, pong);
+        //waits for ping written by the previous effect
+        assert!(pong_pass.dependencies[0].stages.start.contains(PipelineStage::COLOR_ATTACHMENT_OUTPUT));
+        assert!(pong_pass.dependencies[0].accesses.end.contains(Access::SHADER_READ));
+        let ping_pass = frame.graph.pass(frame.post_ping).unwrap();
+        assert_eq!(ping_pass.attachments[0].0, ping);
+        assert!(ping_pass.dependencies[0].stages.start.contains(PipelineStage::COLOR_ATTACHMENT_OUTPUT));
+    }
+}
@@ -1,16 +1,28 @@
-use std::mem::ManuallyDrop;
-use std::ptr::read;
+use std::mem::{ManuallyDrop, size_of};
+use std::ptr::{self, read};
 
 use hal::{
     adapter::{Adapter, PhysicalDevice},
     Backend,
+    buffer,
+    command,
+    command::CommandBuffer,
     device::Device,
-    image::SubresourceRange,
+    format::Format,
+    image::{Layout, NumSamples, SubresourceRange, ViewCapabilities, ViewKind, WrapMode},
+    Limits,
     memory::Properties,
     memory::Requirements,
     MemoryTypeId,
+    pool::CommandPool,
+    pso::PipelineStage,
+    queue::CommandQueue,
     window::Extent2D,
 };
+use image::RgbaImage;
+
+use crate::graphics::memory::BufBundle;
+use crate::graphics::swapchain::DeviceDrop;
 
 pub fn get_mem_id<B>(
     adapter: &Adapter<B>,
@@ -46,17 +58,64 @@ impl<B: Backend> DepthImage<B> {
         adapter: &Adapter<B>,
         device: &B::Device,
         extent: Extent2D,
+    ) -> Result<Self, &'static str> {
+        Self::multisampled(adapter, device, extent, 1)
+    }
+
+    pub fn multisampled(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        extent: Extent2D,
+        samples: NumSamples,
+    ) -> Result<Self, &'static str> {
+        Self::create(
+            adapter,
+            device,
+            extent,
+            1,
+            samples,
+            hal::image::Usage::DEPTH_STENCIL_ATTACHMENT,
+            hal::image::ViewKind::D2,
+        )
+    }
+
+    ///Depth array which can be sampled, `image_view` covers all layers
+    pub fn layered(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        extent: Extent2D,
+        layers: u16,
+    ) -> Result<Self, &'static str> {
+        Self::create(
+            adapter,
+            device,
+            extent,
+            layers,
+            1,
+            hal::image::Usage::DEPTH_STENCIL_ATTACHMENT | hal::image::Usage::SAMPLED,
+            hal::image::ViewKind::D2Array,
+        )
+    }
+
+    fn create(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        extent: Extent2D,
+        layers: u16,
+        samples: NumSamples,
+        usage: hal::image::Usage,
+        view_kind: hal::image::ViewKind,
     ) -> Result<Self, &'static str> {
         unsafe {
             use hal::format::Aspects;
             use hal::format::Format;
             let mut the_image = device
                 .create_image(
-                    hal::image::Kind::D2(extent.width, extent.height, 1, 1),
+                    hal::image::Kind::D2(extent.width, extent.height, layers, samples),
                     1,
                     Format::D32Sfloat,
                     hal::image::Tiling::Optimal,
-                    hal::image::Usage::DEPTH_STENCIL_ATTACHMENT,
+                    usage,
                     hal::image::ViewCapabilities::empty(),
                 )
                 .map_err(|_| "Couldn't crate the image!")?;
@@ -72,12 +131,97 @@ impl<B: Backend> DepthImage<B> {
             let image_view = device
                 .create_image_view(
                     &the_image,
-                    hal::image::ViewKind::D2,
+                    view_kind,
                     Format::D32Sfloat,
                     hal::format::Swizzle::NO,
                     SubresourceRange {
                         aspects: Aspects::DEPTH,
                         levels: 0..1,
+                        layers: 0..layers,
+                    },
+                )
+                .map_err(|_| "Couldn't create the image view!")?;
+            Ok(Self {
+                image: ManuallyDrop::new(the_image),
+                requirements,
+                memory: ManuallyDrop::new(memory),
+                image_view: ManuallyDrop::new(image_view),
+            })
+        }
+    }
+
+    ///Single layer view, to be used as attachment
+    pub fn layer_view(&self, device: &B::Device, layer: u16) -> Result<B::ImageView, &'static str> {
+        unsafe {
+            device
+                .create_image_view(
+                    &self.image,
+                    hal::image::ViewKind::D2,
+                    hal::format::Format::D32Sfloat,
+                    hal::format::Swizzle::NO,
+                    SubresourceRange {
+                        aspects: hal::format::Aspects::DEPTH,
+                        levels: 0..1,
+                        layers: layer..layer + 1,
+                    },
+                )
+                .map_err(|_| "Couldn't create the image view!")
+        }
+    }
+
+    pub unsafe fn manually_drop(&self, device: &B::Device) {
+        device.destroy_image_view(ManuallyDrop::into_inner(read(&self.image_view)));
+        device.destroy_image(ManuallyDrop::into_inner(read(&self.image)));
+        device.free_memory(ManuallyDrop::into_inner(read(&self.memory)));
+    }
+}
+
+///Transient color attachment, e.g. multisampled target resolved into swapchain image
+pub struct ColorImage<B: Backend> {
+    pub image: ManuallyDrop<B::Image>,
+    pub requirements: Requirements,
+    pub memory: ManuallyDrop<B::Memory>,
+    pub image_view: ManuallyDrop<B::ImageView>,
+}
+
+impl<B: Backend> ColorImage<B> {
+    ///`usage` comes from render graph, see `FrameGraph::usage`
+    pub fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        extent: Extent2D,
+        format: Format,
+        samples: NumSamples,
+        usage: hal::image::Usage,
+    ) -> Result<Self, &'static str> {
+        unsafe {
+            let mut the_image = device
+                .create_image(
+                    hal::image::Kind::D2(extent.width, extent.height, 1, samples),
+                    1,
+                    format,
+                    hal::image::Tiling::Optimal,
+                    usage,
+                    hal::image::ViewCapabilities::empty(),
+                )
+                .map_err(|_| "Couldn't crate the image!")?;
+            let requirements = device.get_image_requirements(&the_image);
+            let memory_type_id = get_mem_id(adapter, requirements, Properties::DEVICE_LOCAL)?;
+            let memory = device
+                .allocate_memory(memory_type_id, requirements.size)
+                .map_err(|_| "Couldn't allocate image memory!")?;
+            device
+                .bind_image_memory(&memory, 0, &mut the_image)
+                .map_err(|_| "Couldn't bind the image memory!")?;
+            let image_view = device
+                .create_image_view(
+                    &the_image,
+                    hal::image::ViewKind::D2,
+                    format,
+                    hal::format::Swizzle::NO,
+                    SubresourceRange {
+                        aspects: hal::format::Aspects::COLOR,
+                        levels: 0..1,
                         layers: 0..1,
                     },
                 )
@@ -98,212 +242,264 @@ impl<B: Backend> DepthImage<B> {
     }
 }
 
-//pub struct LoadedImage<B: Backend> {
-//    pub image: ManuallyDrop<B::Image>,
-//    pub requirements: Requirements,
-//    pub memory: ManuallyDrop<B::Memory>,
-//    pub image_view: ManuallyDrop<B::ImageView>,
-//    pub sampler: ManuallyDrop<B::Sampler>,
-//}
-
-//impl<B: Backend> LoadedImage<B> {
-//    pub fn new(
-//        adapter: &Adapter<B>,
-//        device: &B::Device,
-//        command_pool: &mut <B as Backend>::CommandPool,
-//        command_queue: &mut <B as Backend>::CommandQueue,
-//        img: image::RgbaImage,
-//    ) -> Result<Self, &'static str> {
-//        unsafe {
-//            // 0. First we compute some memory related values.
-//            let pixel_size = size_of::<image::Rgba<u8>>();
-//            let row_size = pixel_size * (img.width() as usize);
-//            let limits: Limits = adapter.physical_device.limits();
-//            let row_alignment_mask = limits.optimal_buffer_copy_pitch_alignment as u32 - 1;
-//            let row_pitch = ((row_size as u32 + row_alignment_mask) & !row_alignment_mask) as usize;
-//            debug_assert!(row_pitch as usize >= row_size);
-//
-//            // 1. make a staging buffer with enough memory for the image, and a
-//            //    transfer_src usage
-//            let required_bytes = row_pitch * img.height() as usize;
-//            let staging_bundle =
-//                BufferBundle::new(&adapter, device, required_bytes, Usage::TRANSFER_SRC)?;
-//
-//            // 2. use mapping writer to put the image data into that buffer
-//            let mem_ref = staging_bundle.mem_ref();
-//            let writer = device
-//                .map_memory(mem_ref, 0..staging_bundle.requirements.size)
-//                .map_err(|_| "Couldn't acquire a mapping writer to the staging buffer!")?;
-//
-//            for y in 0..img.height() as usize {
-//                let row = &(*img)[y * row_size..(y + 1) * row_size];
-//                ptr::copy_nonoverlapping(
-//                    row.as_ptr(),
-//                    writer.offset(y as isize * row_pitch as isize),
-//                    row_size,
-//                );
-//            }
-//            device
-//                .flush_mapped_memory_ranges(iter::once((
-//                    mem_ref,
-//                    0..staging_bundle.requirements.size,
-//                )))
-//                .map_err(|_| "Couldn't flush the memory range!")?;
-//            device.unmap_memory(mem_ref);
-//
-//            // 3. Make an image with transfer_dst and SAMPLED usage
-//            let mut the_image = device
-//                .create_image(
-//                    hal::image::Kind::D2(img.width(), img.height(), 1, 1),
-//                    1,
-//                    hal::format::Format::Rgba8Srgb,
-//                    hal::image::Tiling::Optimal,
-//                    hal::image::Usage::TRANSFER_DST | hal::image::Usage::SAMPLED,
-//                    hal::image::ViewCapabilities::empty(),
-//                )
-//                .map_err(|_| "Couldn't create the image!")?;
-//
-//            // 4. allocate memory for the image and bind it
-//            let requirements = device.get_image_requirements(&the_image);
-//            let memory_type_id = adapter
-//                .physical_device
-//                .memory_properties()
-//                .memory_types
-//                .iter()
-//                .enumerate()
-//                .find(|&(id, memory_type)| {
-//                    // BIG NOTE: THIS IS DEVICE LOCAL NOT CPU VISIBLE
-//                    requirements.type_mask & (1 << id) != 0
-//                        && memory_type.properties.contains(Properties::DEVICE_LOCAL)
-//                })
-//                .map(|(id, _)| MemoryTypeId(id))
-//                .ok_or("Couldn't find a memory type to support the image!")?;
-//            let memory = device
-//                .allocate_memory(memory_type_id, requirements.size)
-//                .map_err(|_| "Couldn't allocate image memory!")?;
-//            device
-//                .bind_image_memory(&memory, 0, &mut the_image)
-//                .map_err(|_| "Couldn't bind the image memory!")?;
-//
-//            // 5. create image view and sampler
-//            let image_view = device
-//                .create_image_view(
-//                    &the_image,
-//                    hal::image::ViewKind::D2,
-//                    hal::format::Format::Rgba8Srgb,
-//                    hal::format::Swizzle::NO,
-//                    SubresourceRange {
-//                        aspects: hal::format::Aspects::COLOR,
-//                        levels: 0..1,
-//                        layers: 0..1,
-//                    },
-//                )
-//                .map_err(|_| "Couldn't create the image view!")?;
-//            let sampler = device
-//                .create_sampler(&hal::image::SamplerDesc::new(
-//                    hal::image::Filter::Nearest,
-//                    hal::image::WrapMode::Tile,
-//                ))
-//                .map_err(|_| "Couldn't create the sampler!")?;
-//            // 6. create a command buffer
-//            let mut cmd_buffer: B::CommandBuffer =
-//                command_pool.allocate_one(command::Level::Primary);
-//            cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
-//            // 7. Use a pipeline barrier to transition the image from empty/undefined
-//            //    to TRANSFER_WRITE/TransferDstOptimal
-//            use hal::image::Layout;
-//            let image_barrier = hal::memory::Barrier::Image {
-//                states: (hal::image::Access::empty(), Layout::Undefined)
-//                    ..(
-//                    hal::image::Access::TRANSFER_WRITE,
-//                    Layout::TransferDstOptimal,
-//                ),
-//                target: &the_image,
-//                families: None,
-//                range: SubresourceRange {
-//                    aspects: hal::format::Aspects::COLOR,
-//                    levels: 0..1,
-//                    layers: 0..1,
-//                },
-//            };
-//            cmd_buffer.pipeline_barrier(
-//                PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
-//                hal::memory::Dependencies::empty(),
-//                &[image_barrier],
-//            );
-//            // 8. perform copy from staging buffer to image
-//            cmd_buffer.copy_buffer_to_image(
-//                &staging_bundle.buffer,
-//                &the_image,
-//                Layout::TransferDstOptimal,
-//                &[hal::command::BufferImageCopy {
-//                    buffer_offset: 0,
-//                    buffer_width: (row_pitch / pixel_size) as u32,
-//                    buffer_height: img.height(),
-//                    image_layers: hal::image::SubresourceLayers {
-//                        aspects: hal::format::Aspects::COLOR,
-//                        level: 0,
-//                        layers: 0..1,
-//                    },
-//                    image_offset: hal::image::Offset { x: 0, y: 0, z: 0 },
-//                    image_extent: hal::image::Extent {
-//                        width: img.width(),
-//                        height: img.height(),
-//                        depth: 1,
-//                    },
-//                }],
-//            );
-//            // 9. use pipeline barrier to transition the image to SHADER_READ access/
-//            //    ShaderReadOnlyOptimal layout
-//            let image_barrier = hal::memory::Barrier::Image {
-//                states: (
-//                    hal::image::Access::TRANSFER_WRITE,
-//                    Layout::TransferDstOptimal,
-//                )
-//                    ..(
-//                    hal::image::Access::SHADER_READ,
-//                    Layout::ShaderReadOnlyOptimal,
-//                ),
-//                target: &the_image,
-//                families: None,
-//                range: SubresourceRange {
-//                    aspects: hal::format::Aspects::COLOR,
-//                    levels: 0..1,
-//                    layers: 0..1,
-//                },
-//            };
-//            cmd_buffer.pipeline_barrier(
-//                PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
-//                hal::memory::Dependencies::empty(),
-//                &[image_barrier],
-//            );
-//            // 10. Submit the cmd buffer to queue and wait for it
-//            cmd_buffer.finish();
-//            let upload_fence = device
-//                .create_fence(false)
-//                .map_err(|_| "Couldn't create an upload fence!")?;
-//            command_queue.submit_without_semaphores(Some(&cmd_buffer), Some(&upload_fence));
-//            device
-//                .wait_for_fence(&upload_fence, core::u64::MAX)
-//                .map_err(|_| "Couldn't wait for the fence!")?;
-//            device.destroy_fence(upload_fence);
-//            // 11. Destroy the staging bundle and one shot buffer now that we're done
-//            staging_bundle.manually_drop(device);
-//            command_pool.free(Some(cmd_buffer));
-//            Ok(Self {
-//                image: ManuallyDrop::new(the_image),
-//                requirements,
-//                memory: ManuallyDrop::new(memory),
-//                image_view: ManuallyDrop::new(image_view),
-//                sampler: ManuallyDrop::new(sampler),
-//            })
-//        }
-//    }
-//
-//    pub unsafe fn manually_drop(&self, device: &B::Device) {
-//        device.destroy_sampler(ManuallyDrop::into_inner(read(&self.sampler)));
-//        device.destroy_image_view(ManuallyDrop::into_inner(read(&self.image_view)));
-//        device.destroy_image(ManuallyDrop::into_inner(read(&self.image)));
-//        device.free_memory(ManuallyDrop::into_inner(read(&self.memory)));
-//    }
-//}
\ No newline at end of file
+///Highest supported sample count not above requested one
+pub fn supported_samples<B: Backend>(adapter: &Adapter<B>, requested: NumSamples) -> NumSamples {
+    let limits = adapter.physical_device.limits();
+    let mask = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+    let mut samples = requested.max(1).next_power_of_two();
+    while samples > 1 && mask & samples == 0 {
+        samples >>= 1;
+    }
+    samples
+}
+
+pub struct LoadedImage<B: Backend> {
+    pub image: ManuallyDrop<B::Image>,
+    pub requirements: Requirements,
+    pub memory: ManuallyDrop<B::Memory>,
+    pub image_view: ManuallyDrop<B::ImageView>,
+    pub sampler: ManuallyDrop<B::Sampler>,
+}
+
+impl<B: Backend> LoadedImage<B> {
+    pub fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        command_pool: &mut B::CommandPool,
+        command_queue: &mut B::CommandQueue,
+        img: RgbaImage,
+    ) -> Result<Self, &'static str> {
+        Self::create(
+            adapter,
+            device,
+            command_pool,
+            command_queue,
+            &[img],
+            false,
+        )
+    }
+
+    ///Cube image viewed as `ViewKind::Cube`, faces go in +X, -X, +Y, -Y, +Z, -Z order
+    pub fn cube(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        command_pool: &mut B::CommandPool,
+        command_queue: &mut B::CommandQueue,
+        faces: &[RgbaImage; 6],
+    ) -> Result<Self, &'static str> {
+        let size = faces[0].width();
+        if faces.iter().any(|f| f.width() != size || f.height() != size) {
+            return Err("Cube faces must be square and of the same size!");
+        }
+        Self::create(
+            adapter,
+            device,
+            command_pool,
+            command_queue,
+            &faces[..],
+            true,
+        )
+    }
+
+    ///One layer per image, all of the same size
+    fn create(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        command_pool: &mut B::CommandPool,
+        command_queue: &mut B::CommandQueue,
+        layers: &[RgbaImage],
+        cube: bool,
+    ) -> Result<Self, &'static str> {
+        let img = &layers[0];
+        let layer_count = layers.len() as u16;
+        let (view_kind, capabilities, wrap) = if cube {
+            (ViewKind::Cube, ViewCapabilities::KIND_CUBE, WrapMode::Clamp)
+        } else {
+            (ViewKind::D2, ViewCapabilities::empty(), WrapMode::Tile)
+        };
+        unsafe {
+            // 0. First we compute some memory related values.
+            let pixel_size = size_of::<image::Rgba<u8>>();
+            let row_size = pixel_size * (img.width() as usize);
+            let limits: Limits = adapter.physical_device.limits();
+            let row_alignment_mask = limits.optimal_buffer_copy_pitch_alignment as u32 - 1;
+            let row_pitch = ((row_size as u32 + row_alignment_mask) & !row_alignment_mask) as usize;
+            debug_assert!(row_pitch as usize >= row_size);
+
+            // 1. make a staging buffer with enough memory for all layers, and a
+            //    transfer_src usage
+            let layer_bytes = row_pitch * img.height() as usize;
+            let required_bytes = layer_bytes * layers.len();
+            let mut staging_bundle = BufBundle::new(
+                device,
+                &adapter.physical_device.memory_properties(),
+                required_bytes,
+                buffer::Usage::TRANSFER_SRC,
+                Properties::CPU_VISIBLE,
+            )?;
+
+            // 2. put the image data into that buffer
+            let range = 0..staging_bundle.requirements().size;
+            let writer = staging_bundle.map_mem_range(device, range.clone())?;
+            for (layer, img) in layers.iter().enumerate() {
+                for y in 0..img.height() as usize {
+                    let row = &(**img)[y * row_size..(y + 1) * row_size];
+                    ptr::copy_nonoverlapping(
+                        row.as_ptr(),
+                        writer.offset((layer * layer_bytes + y * row_pitch) as isize),
+                        row_size,
+                    );
+                }
+            }
+            staging_bundle.flush_mem_range(device, range)?;
+            staging_bundle.unmap(device)?;
+
+            // 3. Make an image with transfer_dst and SAMPLED usage
+            let mut the_image = device
+                .create_image(
+                    hal::image::Kind::D2(img.width(), img.height(), layer_count, 1),
+                    1,
+                    hal::format::Format::Rgba8Srgb,
+                    hal::image::Tiling::Optimal,
+                    hal::image::Usage::TRANSFER_DST | hal::image::Usage::SAMPLED,
+                    capabilities,
+                )
+                .map_err(|_| "Couldn't create the image!")?;
+
+            // 4. allocate memory for the image and bind it
+            let requirements = device.get_image_requirements(&the_image);
+            let memory_type_id = get_mem_id(adapter, requirements, Properties::DEVICE_LOCAL)?;
+            let memory = device
+                .allocate_memory(memory_type_id, requirements.size)
+                .map_err(|_| "Couldn't allocate image memory!")?;
+            device
+                .bind_image_memory(&memory, 0, &mut the_image)
+                .map_err(|_| "Couldn't bind the image memory!")?;
+
+            // 5. create image view and sampler
+            let image_view = device
+                .create_image_view(
+                    &the_image,
+                    view_kind,
+                    hal::format::Format::Rgba8Srgb,
+                    hal::format::Swizzle::NO,
+                    SubresourceRange {
+                        aspects: hal::format::Aspects::COLOR,
+                        levels: 0..1,
+                        layers: 0..layer_count,
+                    },
+                )
+                .map_err(|_| "Couldn't create the image view!")?;
+            let sampler = device
+                .create_sampler(&hal::image::SamplerDesc::new(
+                    hal::image::Filter::Nearest,
+                    wrap,
+                ))
+                .map_err(|_| "Couldn't create the sampler!")?;
+
+            // 6. create a command buffer
+            let mut cmd_buffer = command_pool.allocate_one(command::Level::Primary);
+            cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
+
+            // 7. Use a pipeline barrier to transition the image from empty/undefined
+            //    to TRANSFER_WRITE/TransferDstOptimal
+            let image_barrier = hal::memory::Barrier::Image {
+                states: (hal::image::Access::empty(), Layout::Undefined)
+                    ..(
+                    hal::image::Access::TRANSFER_WRITE,
+                    Layout::TransferDstOptimal,
+                ),
+                target: &the_image,
+                families: None,
+                range: SubresourceRange {
+                    aspects: hal::format::Aspects::COLOR,
+                    levels: 0..1,
+                    layers: 0..layer_count,
+                },
+            };
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
+                hal::memory::Dependencies::empty(),
+                &[image_barrier],
+            );
+
+            // 8. perform copy from staging buffer to image, layer by layer
+            let copies = (0..layer_count).map(|layer| command::BufferImageCopy {
+                buffer_offset: (layer as usize * layer_bytes) as u64,
+                buffer_width: (row_pitch / pixel_size) as u32,
+                buffer_height: img.height(),
+                image_layers: hal::image::SubresourceLayers {
+                    aspects: hal::format::Aspects::COLOR,
+                    level: 0,
+                    layers: layer..layer + 1,
+                },
+                image_offset: hal::image::Offset { x: 0, y: 0, z: 0 },
+                image_extent: hal::image::Extent {
+                    width: img.width(),
+                    height: img.height(),
+                    depth: 1,
+                },
+            });
+            cmd_buffer.copy_buffer_to_image(
+                &staging_bundle.buffer,
+                &the_image,
+                Layout::TransferDstOptimal,
+                copies,
+            );
+
+            // 9. use pipeline barrier to transition the image to SHADER_READ access/
+            //    ShaderReadOnlyOptimal layout
+            let image_barrier = hal::memory::Barrier::Image {
+                states: (
+                    hal::image::Access::TRANSFER_WRITE,
+                    Layout::TransferDstOptimal,
+                )
+                    ..(
+                    hal::image::Access::SHADER_READ,
+                    Layout::ShaderReadOnlyOptimal,
+                ),
+                target: &the_image,
+                families: None,
+                range: SubresourceRange {
+                    aspects: hal::format::Aspects::COLOR,
+                    levels: 0..1,
+                    layers: 0..layer_count,
+                },
+            };
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
+                hal::memory::Dependencies::empty(),
+                &[image_barrier],
+            );
+
+            // 10. Submit the cmd buffer to queue and wait for it
+            cmd_buffer.finish();
+            let upload_fence = device
+                .create_fence(false)
+                .map_err(|_| "Couldn't create an upload fence!")?;
+            command_queue.submit_without_semaphores(Some(&cmd_buffer), Some(&upload_fence));
+            device
+                .wait_for_fence(&upload_fence, core::u64::MAX)
+                .map_err(|_| "Couldn't wait for the fence!")?;
+            device.destroy_fence(upload_fence);
+
+            // 11. Destroy the staging bundle and one shot buffer now that we're done
+            staging_bundle.manually_drop(device);
+            command_pool.free(Some(cmd_buffer));
+            Ok(Self {
+                image: ManuallyDrop::new(the_image),
+                requirements,
+                memory: ManuallyDrop::new(memory),
+                image_view: ManuallyDrop::new(image_view),
+                sampler: ManuallyDrop::new(sampler),
+            })
+        }
+    }
+
+    pub unsafe fn manually_drop(&self, device: &B::Device) {
+        device.destroy_sampler(ManuallyDrop::into_inner(read(&self.sampler)));
+        device.destroy_image_view(ManuallyDrop::into_inner(read(&self.image_view)));
+        device.destroy_image(ManuallyDrop::into_inner(read(&self.image)));
+        device.free_memory(ManuallyDrop::into_inner(read(&self.memory)));
+    }
+}
@@ -9,6 +9,8 @@ use hal::{
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
+use crate::graphics::draw::MAX_VIEWS;
+use crate::graphics::pipelines::INSTANCE_STRIDE;
 use crate::graphics::state::HalStateV2;
 use crate::graphics::swapchain::DeviceDrop;
 
@@ -17,8 +19,13 @@ pub struct MemoryManager<B: Backend> {
     pub(crate)mesh_bundle: BufBundle<B>,
     pub(crate)idx_bundle: BufBundle<B>,
     pub(crate)instanced_bundle: BufBundle<B>,
+    pub(crate)uniform_bundle: BufBundle<B>,
+    pub(crate)debug_bundle: BufBundle<B>,
+    pub(crate)ui_bundle: BufBundle<B>,
+    pub(crate)text_bundle: BufBundle<B>,
     instanced_mem: usize,
     instanced_par_count: usize,
+    uniform_mem: usize,
 }
 
 impl<B: Backend> DeviceDrop<B> for MemoryManager<B> {
@@ -26,12 +33,28 @@ impl<B: Backend> DeviceDrop<B> for MemoryManager<B> {
         self.mesh_bundle.manually_drop(device);
         self.idx_bundle.manually_drop(device);
         self.instanced_bundle.manually_drop(device);
+        self.uniform_bundle.manually_drop(device);
+        self.debug_bundle.manually_drop(device);
+        self.ui_bundle.manually_drop(device);
+        self.text_bundle.manually_drop(device);
     }
 }
 
 const MESH_MEMORY_SIZE: usize = 1_000_000;
 const IDX_MEMORY_SIZE: usize = 1_000_000;
-const INSTANCE_MEMORY_SIZE: usize = (64 + 4) * 40_000;
+///instances per swapchain image the buffer starts with
+const INITIAL_INSTANCES: usize = 16_384;
+///instance buffer never grows past it, extra instances are dropped
+pub const MAX_INSTANCES: usize = 262_144;
+///per view, multiple of any sane min_uniform_buffer_offset_alignment
+pub const VIEW_UNIFORM_SIZE: usize = 2 * 1024;
+const UNIFORM_MEMORY_SIZE: usize = VIEW_UNIFORM_SIZE * MAX_VIEWS;
+///per image, for `DebugDraw` lines
+pub const DEBUG_MEMORY_SIZE: usize = 1024 * 1024;
+///per image, for `Ui` triangles
+pub const UI_MEMORY_SIZE: usize = 512 * 1024;
+///per image, for glyph quads
+pub const TEXT_MEMORY_SIZE: usize = 512 * 1024;
 
 impl<B: Backend> MemoryManager<B> {
     pub unsafe fn new(state: &HalStateV2<B>, images_cnt: u32) -> Result<Self, &'static str> {
@@ -53,7 +76,7 @@ impl<B: Backend> MemoryManager<B> {
             buffer::Usage::INDEX,
             memory::Properties::CPU_VISIBLE,
         )?;
-        let insatnced_mem = INSTANCE_MEMORY_SIZE * images_cnt as usize;
+        let insatnced_mem = INITIAL_INSTANCES * INSTANCE_STRIDE * images_cnt as usize;
 
         let insatnced_storage = BufBundle::new(
             state.device_ref(),
@@ -63,13 +86,51 @@ impl<B: Backend> MemoryManager<B> {
             memory::Properties::CPU_VISIBLE,
         )?;
 
+        let uniform_mem = UNIFORM_MEMORY_SIZE * images_cnt as usize;
+        let uniform_storage = BufBundle::new(
+            state.device_ref(),
+            &mem_props,
+            uniform_mem,
+            buffer::Usage::UNIFORM,
+            memory::Properties::CPU_VISIBLE,
+        )?;
+
+        let debug_storage = BufBundle::new(
+            state.device_ref(),
+            &mem_props,
+            DEBUG_MEMORY_SIZE * images_cnt as usize,
+            buffer::Usage::VERTEX,
+            memory::Properties::CPU_VISIBLE,
+        )?;
+
+        let ui_storage = BufBundle::new(
+            state.device_ref(),
+            &mem_props,
+            UI_MEMORY_SIZE * images_cnt as usize,
+            buffer::Usage::VERTEX,
+            memory::Properties::CPU_VISIBLE,
+        )?;
+
+        let text_storage = BufBundle::new(
+            state.device_ref(),
+            &mem_props,
+            TEXT_MEMORY_SIZE * images_cnt as usize,
+            buffer::Usage::VERTEX,
+            memory::Properties::CPU_VISIBLE,
+        )?;
+
         Ok(Self {
             memory_properties: mem_props,
             mesh_bundle: mesh_storage,
             idx_bundle: idx_storage,
             instanced_bundle: insatnced_storage,
+            uniform_bundle: uniform_storage,
+            debug_bundle: debug_storage,
+            ui_bundle: ui_storage,
+            text_bundle: text_storage,
             instanced_mem: insatnced_mem,
-            instanced_par_count: images_cnt as usize
+            instanced_par_count: images_cnt as usize,
+            uniform_mem,
         })
     }
 
@@ -77,6 +138,52 @@ impl<B: Backend> MemoryManager<B> {
         let offset = (self.instanced_mem / self.instanced_par_count) * index;
         offset..offset + self.instanced_mem / self.instanced_par_count
     }
+
+    pub fn instances_per_frame(&self) -> usize {
+        self.instanced_mem / self.instanced_par_count / INSTANCE_STRIDE
+    }
+
+    ///Recreates instance buffer, device must be idle, old content is lost
+    pub(crate) unsafe fn grow_instances(&mut self, device: &B::Device, per_frame: usize) -> Result<(), &'static str> {
+        let instanced_mem = per_frame * INSTANCE_STRIDE * self.instanced_par_count;
+        let bundle = BufBundle::new(
+            device,
+            &self.memory_properties,
+            instanced_mem,
+            buffer::Usage::VERTEX,
+            memory::Properties::CPU_VISIBLE,
+        )?;
+        let mut old = std::mem::replace(&mut self.instanced_bundle, bundle);
+        old.manually_drop(device);
+        self.instanced_mem = instanced_mem;
+        Ok(())
+    }
+
+    pub fn debug_offset(&self, index: usize) -> Range<usize> {
+        let offset = DEBUG_MEMORY_SIZE * index;
+        offset..offset + DEBUG_MEMORY_SIZE
+    }
+
+    pub fn ui_offset(&self, index: usize) -> Range<usize> {
+        let offset = UI_MEMORY_SIZE * index;
+        offset..offset + UI_MEMORY_SIZE
+    }
+
+    pub fn text_offset(&self, index: usize) -> Range<usize> {
+        let offset = TEXT_MEMORY_SIZE * index;
+        offset..offset + TEXT_MEMORY_SIZE
+    }
+
+    pub fn uniform_offset(&self, index: usize) -> Range<usize> {
+        let offset = (self.uniform_mem / self.instanced_par_count) * index;
+        offset..offset + self.uniform_mem / self.instanced_par_count
+    }
+
+    ///Slot of one view inside image's uniform range, `view` < `MAX_VIEWS`
+    pub fn view_uniform_offset(&self, index: usize, view: usize) -> Range<usize> {
+        let offset = self.uniform_offset(index).start + VIEW_UNIFORM_SIZE * view;
+        offset..offset + VIEW_UNIFORM_SIZE
+    }
 }
 
 pub struct BufBundle<B: Backend> {
@@ -94,7 +201,7 @@ impl<B: Backend> DeviceDrop<B> for BufBundle<B> {
 }
 
 impl<B: Backend> BufBundle<B> {
-    unsafe fn new(
+    pub(crate) unsafe fn new(
         device: &B::Device,
         mem_props: &adapter::MemoryProperties,
         size: usize,
@@ -179,6 +286,6 @@ fn get_mem_id<B>(
 pub fn to_seg(range: &Range<u64>) -> Segment {
     Segment {
         offset: range.start,
-        size: Some((range.end) + (range.start)),
+        size: Some(range.end - range.start),
     }
 }
\ No newline at end of file
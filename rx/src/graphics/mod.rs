@@ -1,7 +1,13 @@
 pub mod draw;
+pub mod graph;
 pub mod hal_utils;
 pub mod memory;
 pub mod pipelines;
+pub mod post;
+pub mod shadow;
 pub mod state;
 pub mod swapchain;
+pub mod target;
+pub mod texture;
+pub mod timing;
 pub mod wrapper;
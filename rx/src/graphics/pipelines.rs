@@ -7,51 +7,70 @@ use hal::{
     pso::{
         AttributeDesc, BlendDesc, BlendOp, BlendState, ColorBlendDesc, ColorMask,
         Comparison, DepthStencilDesc, DepthTest, Element, EntryPoint, Face, Factor, FrontFace, GraphicsPipelineDesc, GraphicsShaderSet,
-        InputAssemblerDesc, LogicOp, PipelineCreationFlags, Primitive, Rasterizer,
+        InputAssemblerDesc, LogicOp, Multisampling, PipelineCreationFlags, Primitive, Rasterizer,
         ShaderStageFlags, Specialization, VertexBufferDesc,
     },
     window::Extent2D,
 };
 use hal::pass::Subpass;
-use hal::pso::{BasePipeline, PolygonMode, VertexInputRate};
+use hal::buffer::SubRange;
+use hal::pso::{
+    BasePipeline, BufferDescriptorFormat, BufferDescriptorType, Descriptor, DescriptorPool,
+    DescriptorPoolCreateFlags, DescriptorRangeDesc, DescriptorSetLayoutBinding,
+    DescriptorSetWrite, DescriptorType, PolygonMode, VertexInputRate,
+};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
+use crate::debug_draw::DebugVertex;
+use crate::graphics::draw::MAX_VIEWS;
+use crate::ui::UiVertex;
+use crate::graphics::memory::MemoryManager;
+use crate::graphics::post::PostShader;
 use crate::graphics::swapchain::DeviceDrop;
 use crate::hal::pso;
 use crate::hal::pso::State;
-use std::ops::Range;
+use std::ops::{Deref, Range};
 
 pub struct PipelineV0<B: Backend> {
-    //    pub(crate)descriptor_set: ManuallyDrop<B::DescriptorSet>,
-//    descriptor_pool: ManuallyDrop<B::DescriptorPool>,
-//    descriptor_set_layouts: Vec<B::DescriptorSetLayout>,
+    pub(crate)descriptor_sets: Vec<B::DescriptorSet>,
+    descriptor_pool: ManuallyDrop<B::DescriptorPool>,
+    descriptor_set_layouts: Vec<B::DescriptorSetLayout>,
     pub(crate)pipeline_layout: ManuallyDrop<B::PipelineLayout>,
     pub(crate)graphics_pipeline: ManuallyDrop<B::GraphicsPipeline>,
+    ///same layout, alpha blended without depth writes
+    pub(crate)transparent_pipeline: ManuallyDrop<B::GraphicsPipeline>,
+    ///opaque pipeline with line polygon mode, if device supports it
+    pub(crate)wireframe_pipeline: Option<ManuallyDrop<B::GraphicsPipeline>>,
 }
 
 impl<B: Backend> DeviceDrop<B> for PipelineV0<B> {
     unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
         use std::ptr::read;
-//            self.descriptor_pool
-//                .free_sets(Some(ManuallyDrop::into_inner(read(
-//                    &mut self.descriptor_set,
-//                ))));
-//            device
-//                .destroy_descriptor_pool(ManuallyDrop::into_inner(read(&mut self.descriptor_pool)));
-//
-//            for dsl in self.descriptor_set_layouts.drain(..) {
-//                device.destroy_descriptor_set_layout(dsl);
-//            }
+        self.descriptor_pool.free_sets(self.descriptor_sets.drain(..));
+        device
+            .destroy_descriptor_pool(ManuallyDrop::into_inner(read(&mut self.descriptor_pool)));
+        for dsl in self.descriptor_set_layouts.drain(..) {
+            device.destroy_descriptor_set_layout(dsl);
+        }
         device
             .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
         device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(
             &mut self.graphics_pipeline,
         )));
+        device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(
+            &mut self.transparent_pipeline,
+        )));
+        if let Some(wireframe) = self.wireframe_pipeline.take() {
+            device.destroy_graphics_pipeline(ManuallyDrop::into_inner(wireframe));
+        }
     }
 }
 
 
+///model, material color, material params
+pub const INSTANCE_STRIDE: usize = size_of::<f32>() * (16 + 4 + 4);
+
 pub const VERTEX_SOURCE: &'static str = include_str!("../../../shaders/one.vert");
 
 pub const FRAGMENT_SOURCE: &'static str = include_str!("../../../shaders/one.frag");
@@ -62,6 +81,12 @@ impl<B: Backend> PipelineV0<B> {
         device: &B::Device,
         _extent: Extent2D,
         render_pass: &<B as Backend>::RenderPass,
+        storage: &MemoryManager<B>,
+        texture_layout: &B::DescriptorSetLayout,
+        shadow_layout: &B::DescriptorSetLayout,
+        img_count: usize,
+        samples: hal::image::NumSamples,
+        features: hal::Features,
     ) -> Result<Self, &'static str> {
 
 //        #[cfg(not(target_arch = "wasm32"))]
@@ -135,7 +160,7 @@ impl<B: Backend> PipelineV0<B> {
         //instanced
         vertex_buffers.push(VertexBufferDesc {
             binding: 1,
-            stride: (size_of::<f32>() * 16 * 2) as u32,
+            stride: INSTANCE_STRIDE as u32,
             rate: VertexInputRate::Instance(1),
         });
         let mut attributes: Vec<AttributeDesc> = vec![
@@ -165,8 +190,19 @@ impl<B: Backend> PipelineV0<B> {
             },
         ];
 
-        //instanced1
-        for i in 0..8 {
+        //model
+        for i in 0..4 {
+            attributes.push(AttributeDesc {
+                location: 3 + i,
+                binding: 1,
+                element: Element {
+                    format: hal::format::Format::Rgba32Sfloat,
+                    offset: (size_of::<f32>() * 4) as u32 * i,
+                },
+            });
+        }
+        //material color and params
+        for i in 4..6 {
             attributes.push(AttributeDesc {
                 location: 3 + i,
                 binding: 1,
@@ -226,68 +262,85 @@ impl<B: Backend> PipelineV0<B> {
 
         let baked_states = Default::default();
 
-//        let descriptor_set_layouts: Vec<<B as Backend>::DescriptorSetLayout> = vec![unsafe {
-//            device
-//                .create_descriptor_set_layout(
-//                    &[
-//                        DescriptorSetLayoutBinding {
-//                            binding: 0,
-//                            ty: hal::pso::DescriptorType::SampledImage,
-//                            count: 1,
-//                            stage_flags: ShaderStageFlags::FRAGMENT,
-//                            immutable_samplers: false,
-//                        },
-//                        DescriptorSetLayoutBinding {
-//                            binding: 1,
-//                            ty: hal::pso::DescriptorType::Sampler,
-//                            count: 1,
-//                            stage_flags: ShaderStageFlags::FRAGMENT,
-//                            immutable_samplers: false,
-//                        },
-//                    ],
-//                    &[],
-//                )
-//                .map_err(|_| "Couldn't make a DescriptorSetLayout")?
-//        }];
-//        let mut descriptor_pool = unsafe {
-//            device
-//                .create_descriptor_pool(
-//                    1, // sets
-//                    &[
-//                        hal::pso::DescriptorRangeDesc {
-//                            ty: hal::pso::DescriptorType::SampledImage,
-//                            count: 1,
-//                        },
-//                        hal::pso::DescriptorRangeDesc {
-//                            ty: hal::pso::DescriptorType::Sampler,
-//                            count: 1,
-//                        },
-//                    ],
-//                    hal::pso::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
-//                )
-//                .map_err(|_| "Couldn't create a descriptor pool!")?
-//        };
-//        let descriptor_set = unsafe {
-//            descriptor_pool
-//                .allocate_set(&descriptor_set_layouts[0])
-//                .map_err(|_| "Couldn't make a Descriptor Set!")?
-//        };
+        let descriptor_set_layouts: Vec<<B as Backend>::DescriptorSetLayout> = vec![unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &[
+                        DescriptorSetLayoutBinding {
+                            binding: 0,
+                            ty: DescriptorType::Buffer {
+                                ty: BufferDescriptorType::Uniform,
+                                format: BufferDescriptorFormat::Structured {
+                                    dynamic_offset: false
+                                },
+                            },
+                            count: 1,
+                            stage_flags: ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                    ],
+                    &[],
+                )
+                .map_err(|_| "Couldn't make a DescriptorSetLayout")?
+        }];
+        //one set per swapchain image and view, each pointing to its own uniform slot
+        let set_count = img_count * MAX_VIEWS;
+        let mut descriptor_pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    set_count,
+                    &[
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Buffer {
+                                ty: BufferDescriptorType::Uniform,
+                                format: BufferDescriptorFormat::Structured {
+                                    dynamic_offset: false
+                                },
+                            },
+                            count: set_count,
+                        },
+                    ],
+                    DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+                )
+                .map_err(|_| "Couldn't create a descriptor pool!")?
+        };
+        let descriptor_sets = (0..set_count)
+            .map(|_| unsafe {
+                descriptor_pool
+                    .allocate_set(&descriptor_set_layouts[0])
+                    .map_err(|_| "Couldn't make a Descriptor Set!")
+            })
+            .collect::<Result<Vec<_>, &str>>()?;
+        unsafe {
+            device.write_descriptor_sets(descriptor_sets.iter().enumerate().map(|(i, set)| {
+                let range = storage.view_uniform_offset(i / MAX_VIEWS, i % MAX_VIEWS);
+                DescriptorSetWrite {
+                    set,
+                    binding: 0,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Buffer(
+                        storage.uniform_bundle.buffer.deref(),
+                        SubRange {
+                            offset: range.start as u64,
+                            size: Some((range.end - range.start) as u64),
+                        },
+                    )),
+                }
+            }));
+        }
+        debug!("DescriptorSets done");
 
-        //            (ShaderStageFlags::FRAGMENT, 0..4),
-        let descriptor_set_layouts: Vec<<B as Backend>::DescriptorSetLayout> = vec![];
-        let push_constants: Vec<(ShaderStageFlags, Range<u32>)>= vec![
-//            (ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT, 0..64)
-        ];
+        let push_constants: Vec<(ShaderStageFlags, Range<u32>)> = vec![];
         let layout = unsafe {
             device
                 .create_pipeline_layout(
-                    &descriptor_set_layouts,
+                    vec![&descriptor_set_layouts[0], texture_layout, shadow_layout],
                     push_constants)
                 .map_err(|_| "Couldn't create a pipeline layout")?
         };
         debug!("PipelineLayout done {:?}", layout);
 
-        let pipeline_desc = GraphicsPipelineDesc {
+        let mut pipeline_desc = GraphicsPipelineDesc {
             shaders,
             rasterizer,
             vertex_buffers,
@@ -295,7 +348,7 @@ impl<B: Backend> PipelineV0<B> {
             input_assembler: input_assembler_desc,
             blender,
             depth_stencil,
-            multisampling: None,
+            multisampling: multisampling(samples),
             baked_states,
             layout: &layout,
             subpass: Subpass {
@@ -318,12 +371,223 @@ impl<B: Backend> PipelineV0<B> {
         debug!("GraphicsPipeline done");
         info!("{:?}", pipeline);
         let pipeline = pipeline.expect("");
+
+        let wireframe_pipeline = if features.contains(hal::Features::NON_FILL_POLYGON_MODE) {
+            pipeline_desc.rasterizer.polygon_mode = PolygonMode::Line;
+            let wireframe = unsafe { device.create_graphics_pipeline(&pipeline_desc, None) };
+            pipeline_desc.rasterizer.polygon_mode = PolygonMode::Fill;
+            match wireframe {
+                Ok(wireframe) => Some(ManuallyDrop::new(wireframe)),
+                Err(e) => {
+                    warn!("Couldn't create a wireframe pipeline: {:?}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        //logic op would disable blending
+        pipeline_desc.blender = BlendDesc {
+            logic_op: None,
+            targets: vec![ColorBlendDesc {
+                mask: ColorMask::ALL,
+                blend: Some(BlendState {
+                    color: BlendOp::Add {
+                        src: Factor::SrcAlpha,
+                        dst: Factor::OneMinusSrcAlpha,
+                    },
+                    alpha: BlendOp::Add {
+                        src: Factor::One,
+                        dst: Factor::OneMinusSrcAlpha,
+                    },
+                }),
+            }],
+        };
+        pipeline_desc.depth_stencil.depth = Some(DepthTest {
+            fun: Comparison::LessEqual,
+            write: false,
+        });
+        let transparent_pipeline = unsafe {
+            device
+                .create_graphics_pipeline(&pipeline_desc, None)
+                .map_err(|e| {
+                    error!("{:?}", e);
+                    "Couldn't create a transparent graphics pipeline!"
+                })?
+        };
+        debug!("Transparent GraphicsPipeline done");
         unsafe { device.destroy_shader_module(vertex_shader_module) };
         unsafe { device.destroy_shader_module(fragment_shader_module) };
         Ok(Self {
-//            descriptor_set_layouts,
-//            descriptor_pool: ManuallyDrop::new(descriptor_pool),
-//            descriptor_set: ManuallyDrop::new(descriptor_set),
+            descriptor_sets,
+            descriptor_pool: ManuallyDrop::new(descriptor_pool),
+            descriptor_set_layouts,
+            pipeline_layout: ManuallyDrop::new(layout),
+            graphics_pipeline: ManuallyDrop::new(pipeline),
+            transparent_pipeline: ManuallyDrop::new(transparent_pipeline),
+            wireframe_pipeline,
+        })
+    }
+}
+
+fn multisampling(samples: hal::image::NumSamples) -> Option<Multisampling> {
+    if samples > 1 {
+        Some(Multisampling {
+            rasterization_samples: samples,
+            sample_shading: None,
+            sample_mask: !0,
+            alpha_coverage: false,
+            alpha_to_one: false,
+        })
+    } else {
+        None
+    }
+}
+
+impl<B: Backend> PipelineV0<B> {
+    ///Layout of frame uniform set, other pipelines in main pass share it as set 0
+    pub fn frame_layout(&self) -> &B::DescriptorSetLayout {
+        &self.descriptor_set_layouts[0]
+    }
+
+    ///Frame uniform of `view` for swapchain image `frame`
+    pub fn frame_set(&self, frame: usize, view: usize) -> &B::DescriptorSet {
+        &self.descriptor_sets[frame * MAX_VIEWS + view]
+    }
+}
+
+///Depth only pipeline for `ShadowMap` render pass, light matrix is pushed per cascade
+pub struct ShadowPipeline<B: Backend> {
+    pub(crate)pipeline_layout: ManuallyDrop<B::PipelineLayout>,
+    pub(crate)graphics_pipeline: ManuallyDrop<B::GraphicsPipeline>,
+}
+
+impl<B: Backend> DeviceDrop<B> for ShadowPipeline<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        device
+            .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
+        device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(
+            &mut self.graphics_pipeline,
+        )));
+    }
+}
+
+impl<B: Backend> ShadowPipeline<B> {
+    pub fn new(
+        device: &B::Device,
+        render_pass: &<B as Backend>::RenderPass,
+    ) -> Result<Self, &'static str> {
+        let vertex_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/shadow.vert.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let shaders = GraphicsShaderSet {
+            vertex: EntryPoint {
+                entry: "main",
+                module: &vertex_shader_module,
+                specialization: Specialization::default(),
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: None,
+        };
+        let vertex_buffers: Vec<VertexBufferDesc> = vec![
+            VertexBufferDesc {
+                binding: 0,
+                stride: (size_of::<f32>() * (3 + 2 + 3)) as u32,
+                rate: VertexInputRate::Vertex,
+            },
+            VertexBufferDesc {
+                binding: 1,
+                stride: INSTANCE_STRIDE as u32,
+                rate: VertexInputRate::Instance(1),
+            },
+        ];
+        let mut attributes: Vec<AttributeDesc> = vec![
+            AttributeDesc {
+                location: 0,
+                binding: 0,
+                element: Element {
+                    format: hal::format::Format::Rgb32Sfloat,
+                    offset: 0,
+                },
+            },
+        ];
+        //model
+        for i in 0..4 {
+            attributes.push(AttributeDesc {
+                location: 1 + i,
+                binding: 1,
+                element: Element {
+                    format: hal::format::Format::Rgba32Sfloat,
+                    offset: (size_of::<f32>() * 4) as u32 * i,
+                },
+            });
+        }
+
+        let rasterizer = Rasterizer {
+            polygon_mode: PolygonMode::Fill,
+            cull_face: Face::NONE,
+            front_face: FrontFace::CounterClockwise,
+            depth_clamping: false,
+            depth_bias: None,
+            conservative: false,
+            line_width: State::Dynamic,
+        };
+        let depth_stencil = DepthStencilDesc {
+            depth: Some(DepthTest {
+                fun: Comparison::LessEqual,
+                write: true,
+            }),
+            depth_bounds: false,
+            stencil: None,
+        };
+        let push_constants: Vec<(ShaderStageFlags, Range<u32>)> = vec![
+            (ShaderStageFlags::VERTEX, 0..(size_of::<f32>() * 16) as u32)
+        ];
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(Vec::<B::DescriptorSetLayout>::new(), push_constants)
+                .map_err(|_| "Couldn't create a pipeline layout")?
+        };
+
+        let pipeline_desc = GraphicsPipelineDesc {
+            shaders,
+            rasterizer,
+            vertex_buffers,
+            attributes,
+            input_assembler: InputAssemblerDesc {
+                primitive: Primitive::TriangleList,
+                with_adjacency: false,
+                restart_index: None,
+            },
+            blender: BlendDesc {
+                logic_op: None,
+                targets: vec![],
+            },
+            depth_stencil,
+            multisampling: None,
+            baked_states: Default::default(),
+            layout: &layout,
+            subpass: Subpass {
+                index: 0,
+                main_pass: render_pass,
+            },
+            flags: PipelineCreationFlags::empty(),
+            parent: BasePipeline::None,
+        };
+        let pipeline = unsafe { device.create_graphics_pipeline(&pipeline_desc, None) }
+            .map_err(|e| {
+                error!("{:?}", e);
+                "Couldn't create a shadow pipeline!"
+            })?;
+        unsafe { device.destroy_shader_module(vertex_shader_module) };
+        debug!("ShadowPipeline done");
+        Ok(Self {
             pipeline_layout: ManuallyDrop::new(layout),
             graphics_pipeline: ManuallyDrop::new(pipeline),
         })
@@ -350,4 +614,593 @@ pub mod shader {
                 "Couldn't compile vertex shader!"
             })
     }
-}
\ No newline at end of file
+}
+///Line list pipeline for `DebugDraw`, drawn in main pass with frame uniform at set 0
+pub struct DebugPipeline<B: Backend> {
+    pub(crate)pipeline_layout: ManuallyDrop<B::PipelineLayout>,
+    pub(crate)graphics_pipeline: ManuallyDrop<B::GraphicsPipeline>,
+}
+
+impl<B: Backend> DeviceDrop<B> for DebugPipeline<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        device
+            .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
+        device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(
+            &mut self.graphics_pipeline,
+        )));
+    }
+}
+
+impl<B: Backend> DebugPipeline<B> {
+    pub fn new(
+        device: &B::Device,
+        render_pass: &<B as Backend>::RenderPass,
+        frame_layout: &B::DescriptorSetLayout,
+        samples: hal::image::NumSamples,
+    ) -> Result<Self, &'static str> {
+        let vertex_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/debug.vert.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let fragment_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/debug.frag.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let shaders = GraphicsShaderSet {
+            vertex: EntryPoint {
+                entry: "main",
+                module: &vertex_shader_module,
+                specialization: Specialization::default(),
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: Some(EntryPoint {
+                entry: "main",
+                module: &fragment_shader_module,
+                specialization: Specialization::default(),
+            }),
+        };
+        let vertex_buffers: Vec<VertexBufferDesc> = vec![
+            VertexBufferDesc {
+                binding: 0,
+                stride: size_of::<DebugVertex>() as u32,
+                rate: VertexInputRate::Vertex,
+            },
+        ];
+        let attributes: Vec<AttributeDesc> = vec![
+            AttributeDesc {
+                location: 0,
+                binding: 0,
+                element: Element {
+                    format: hal::format::Format::Rgb32Sfloat,
+                    offset: 0,
+                },
+            },
+            AttributeDesc {
+                location: 1,
+                binding: 0,
+                element: Element {
+                    format: hal::format::Format::Rgba32Sfloat,
+                    offset: (size_of::<f32>() * 3) as u32,
+                },
+            },
+        ];
+
+        let rasterizer = Rasterizer {
+            polygon_mode: PolygonMode::Fill,
+            cull_face: Face::NONE,
+            front_face: FrontFace::CounterClockwise,
+            depth_clamping: false,
+            depth_bias: None,
+            conservative: false,
+            line_width: State::Dynamic,
+        };
+        //tested against scene, but never hides it
+        let depth_stencil = DepthStencilDesc {
+            depth: Some(DepthTest {
+                fun: Comparison::LessEqual,
+                write: false,
+            }),
+            depth_bounds: false,
+            stencil: None,
+        };
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(vec![frame_layout], Vec::<(ShaderStageFlags, Range<u32>)>::new())
+                .map_err(|_| "Couldn't create a pipeline layout")?
+        };
+
+        let pipeline_desc = GraphicsPipelineDesc {
+            shaders,
+            rasterizer,
+            vertex_buffers,
+            attributes,
+            input_assembler: InputAssemblerDesc {
+                primitive: Primitive::LineList,
+                with_adjacency: false,
+                restart_index: None,
+            },
+            blender: BlendDesc {
+                logic_op: None,
+                targets: vec![ColorBlendDesc {
+                    mask: ColorMask::ALL,
+                    blend: Some(BlendState {
+                        color: BlendOp::Add {
+                            src: Factor::SrcAlpha,
+                            dst: Factor::OneMinusSrcAlpha,
+                        },
+                        alpha: BlendOp::Add {
+                            src: Factor::One,
+                            dst: Factor::OneMinusSrcAlpha,
+                        },
+                    }),
+                }],
+            },
+            depth_stencil,
+            multisampling: multisampling(samples),
+            baked_states: Default::default(),
+            layout: &layout,
+            subpass: Subpass {
+                index: 0,
+                main_pass: render_pass,
+            },
+            flags: PipelineCreationFlags::empty(),
+            parent: BasePipeline::None,
+        };
+        let pipeline = unsafe { device.create_graphics_pipeline(&pipeline_desc, None) }
+            .map_err(|e| {
+                error!("{:?}", e);
+                "Couldn't create a debug pipeline!"
+            })?;
+        unsafe { device.destroy_shader_module(vertex_shader_module) };
+        unsafe { device.destroy_shader_module(fragment_shader_module) };
+        debug!("DebugPipeline done");
+        Ok(Self {
+            pipeline_layout: ManuallyDrop::new(layout),
+            graphics_pipeline: ManuallyDrop::new(pipeline),
+        })
+    }
+}
+
+///Screen space textured triangles for `Ui` and text, drawn in present pass after post-processing
+pub struct UiPipeline<B: Backend> {
+    pub(crate)pipeline_layout: ManuallyDrop<B::PipelineLayout>,
+    pub(crate)graphics_pipeline: ManuallyDrop<B::GraphicsPipeline>,
+}
+
+impl<B: Backend> DeviceDrop<B> for UiPipeline<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        device
+            .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
+        device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(
+            &mut self.graphics_pipeline,
+        )));
+    }
+}
+
+impl<B: Backend> UiPipeline<B> {
+    pub fn new(
+        device: &B::Device,
+        render_pass: &<B as Backend>::RenderPass,
+        texture_layout: &B::DescriptorSetLayout,
+        samples: hal::image::NumSamples,
+    ) -> Result<Self, &'static str> {
+        Self::with_fragment(device, render_pass, texture_layout, samples,
+                            &include_bytes!("../../../assets/ui.frag.spv")[..])
+    }
+
+    ///Same layout, but texture alpha is glyph coverage tinted by vertex color, see `FontAtlas`
+    pub fn text(
+        device: &B::Device,
+        render_pass: &<B as Backend>::RenderPass,
+        texture_layout: &B::DescriptorSetLayout,
+        samples: hal::image::NumSamples,
+    ) -> Result<Self, &'static str> {
+        Self::with_fragment(device, render_pass, texture_layout, samples,
+                            &include_bytes!("../../../assets/text.frag.spv")[..])
+    }
+
+    fn with_fragment(
+        device: &B::Device,
+        render_pass: &<B as Backend>::RenderPass,
+        texture_layout: &B::DescriptorSetLayout,
+        samples: hal::image::NumSamples,
+        fragment: &[u8],
+    ) -> Result<Self, &'static str> {
+        let vertex_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/ui.vert.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let fragment_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(fragment))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let shaders = GraphicsShaderSet {
+            vertex: EntryPoint {
+                entry: "main",
+                module: &vertex_shader_module,
+                specialization: Specialization::default(),
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: Some(EntryPoint {
+                entry: "main",
+                module: &fragment_shader_module,
+                specialization: Specialization::default(),
+            }),
+        };
+        let vertex_buffers: Vec<VertexBufferDesc> = vec![
+            VertexBufferDesc {
+                binding: 0,
+                stride: size_of::<UiVertex>() as u32,
+                rate: VertexInputRate::Vertex,
+            },
+        ];
+        let attributes: Vec<AttributeDesc> = vec![
+            AttributeDesc {
+                location: 0,
+                binding: 0,
+                element: Element {
+                    format: hal::format::Format::Rg32Sfloat,
+                    offset: 0,
+                },
+            },
+            AttributeDesc {
+                location: 1,
+                binding: 0,
+                element: Element {
+                    format: hal::format::Format::Rg32Sfloat,
+                    offset: (size_of::<f32>() * 2) as u32,
+                },
+            },
+            AttributeDesc {
+                location: 2,
+                binding: 0,
+                element: Element {
+                    format: hal::format::Format::Rgba32Sfloat,
+                    offset: (size_of::<f32>() * 4) as u32,
+                },
+            },
+        ];
+
+        let rasterizer = Rasterizer {
+            polygon_mode: PolygonMode::Fill,
+            cull_face: Face::NONE,
+            front_face: FrontFace::CounterClockwise,
+            depth_clamping: false,
+            depth_bias: None,
+            conservative: false,
+            line_width: State::Dynamic,
+        };
+        //screen size in pixels
+        let push_constants: Vec<(ShaderStageFlags, Range<u32>)> = vec![
+            (ShaderStageFlags::VERTEX, 0..(size_of::<f32>() * 2) as u32)
+        ];
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(vec![texture_layout], push_constants)
+                .map_err(|_| "Couldn't create a pipeline layout")?
+        };
+
+        let pipeline_desc = GraphicsPipelineDesc {
+            shaders,
+            rasterizer,
+            vertex_buffers,
+            attributes,
+            input_assembler: InputAssemblerDesc {
+                primitive: Primitive::TriangleList,
+                with_adjacency: false,
+                restart_index: None,
+            },
+            blender: BlendDesc {
+                logic_op: None,
+                targets: vec![ColorBlendDesc {
+                    mask: ColorMask::ALL,
+                    blend: Some(BlendState {
+                        color: BlendOp::Add {
+                            src: Factor::SrcAlpha,
+                            dst: Factor::OneMinusSrcAlpha,
+                        },
+                        alpha: BlendOp::Add {
+                            src: Factor::One,
+                            dst: Factor::OneMinusSrcAlpha,
+                        },
+                    }),
+                }],
+            },
+            depth_stencil: DepthStencilDesc {
+                depth: None,
+                depth_bounds: false,
+                stencil: None,
+            },
+            multisampling: multisampling(samples),
+            baked_states: Default::default(),
+            layout: &layout,
+            subpass: Subpass {
+                index: 0,
+                main_pass: render_pass,
+            },
+            flags: PipelineCreationFlags::empty(),
+            parent: BasePipeline::None,
+        };
+        let pipeline = unsafe { device.create_graphics_pipeline(&pipeline_desc, None) }
+            .map_err(|e| {
+                error!("{:?}", e);
+                "Couldn't create a ui pipeline!"
+            })?;
+        unsafe { device.destroy_shader_module(vertex_shader_module) };
+        unsafe { device.destroy_shader_module(fragment_shader_module) };
+        debug!("UiPipeline done");
+        Ok(Self {
+            pipeline_layout: ManuallyDrop::new(layout),
+            graphics_pipeline: ManuallyDrop::new(pipeline),
+        })
+    }
+}
+
+///Fullscreen triangle at far plane sampling equirectangular sky, frame uniform at set 0, sky at set 1
+pub struct SkyboxPipeline<B: Backend> {
+    pub(crate)pipeline_layout: ManuallyDrop<B::PipelineLayout>,
+    pub(crate)graphics_pipeline: ManuallyDrop<B::GraphicsPipeline>,
+}
+
+impl<B: Backend> DeviceDrop<B> for SkyboxPipeline<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        device
+            .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
+        device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(
+            &mut self.graphics_pipeline,
+        )));
+    }
+}
+
+impl<B: Backend> SkyboxPipeline<B> {
+    pub fn new(
+        device: &B::Device,
+        render_pass: &<B as Backend>::RenderPass,
+        frame_layout: &B::DescriptorSetLayout,
+        texture_layout: &B::DescriptorSetLayout,
+        samples: hal::image::NumSamples,
+    ) -> Result<Self, &'static str> {
+        let vertex_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/skybox.vert.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let fragment_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/skybox.frag.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let shaders = GraphicsShaderSet {
+            vertex: EntryPoint {
+                entry: "main",
+                module: &vertex_shader_module,
+                specialization: Specialization::default(),
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: Some(EntryPoint {
+                entry: "main",
+                module: &fragment_shader_module,
+                specialization: Specialization::default(),
+            }),
+        };
+
+        let rasterizer = Rasterizer {
+            polygon_mode: PolygonMode::Fill,
+            cull_face: Face::NONE,
+            front_face: FrontFace::CounterClockwise,
+            depth_clamping: false,
+            depth_bias: None,
+            conservative: false,
+            line_width: State::Dynamic,
+        };
+        //only where no geometry was drawn, depth is cleared to far plane
+        let depth_stencil = DepthStencilDesc {
+            depth: Some(DepthTest {
+                fun: Comparison::LessEqual,
+                write: false,
+            }),
+            depth_bounds: false,
+            stencil: None,
+        };
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(vec![frame_layout, texture_layout], Vec::<(ShaderStageFlags, Range<u32>)>::new())
+                .map_err(|_| "Couldn't create a pipeline layout")?
+        };
+
+        let pipeline_desc = GraphicsPipelineDesc {
+            shaders,
+            rasterizer,
+            //vertices are generated from gl_VertexIndex
+            vertex_buffers: Vec::new(),
+            attributes: Vec::new(),
+            input_assembler: InputAssemblerDesc {
+                primitive: Primitive::TriangleList,
+                with_adjacency: false,
+                restart_index: None,
+            },
+            blender: BlendDesc {
+                logic_op: None,
+                targets: vec![ColorBlendDesc {
+                    mask: ColorMask::ALL,
+                    blend: None,
+                }],
+            },
+            depth_stencil,
+            multisampling: multisampling(samples),
+            baked_states: Default::default(),
+            layout: &layout,
+            subpass: Subpass {
+                index: 0,
+                main_pass: render_pass,
+            },
+            flags: PipelineCreationFlags::empty(),
+            parent: BasePipeline::None,
+        };
+        let pipeline = unsafe { device.create_graphics_pipeline(&pipeline_desc, None) }
+            .map_err(|e| {
+                error!("{:?}", e);
+                "Couldn't create a skybox pipeline!"
+            })?;
+        unsafe { device.destroy_shader_module(vertex_shader_module) };
+        unsafe { device.destroy_shader_module(fragment_shader_module) };
+        debug!("SkyboxPipeline done");
+        Ok(Self {
+            pipeline_layout: ManuallyDrop::new(layout),
+            graphics_pipeline: ManuallyDrop::new(pipeline),
+        })
+    }
+}
+
+///Push constants of full-screen passes: effect params, then texel size
+pub const POST_CONSTANTS: usize = 8;
+
+fn post_fragment(shader: PostShader) -> &'static [u8] {
+    match shader {
+        PostShader::ToneMap => &include_bytes!("../../../assets/tonemap.frag.spv")[..],
+        PostShader::Gamma => &include_bytes!("../../../assets/gamma.frag.spv")[..],
+        PostShader::Fxaa => &include_bytes!("../../../assets/fxaa.frag.spv")[..],
+        PostShader::Vignette => &include_bytes!("../../../assets/vignette.frag.spv")[..],
+        PostShader::Bloom => &include_bytes!("../../../assets/bloom.frag.spv")[..],
+    }
+}
+
+///Full-screen triangle pipelines sampling input at set 0, one per `PostShader` plus
+///`present` copying chain result into swapchain image
+pub struct PostPipeline<B: Backend> {
+    pub(crate)pipeline_layout: ManuallyDrop<B::PipelineLayout>,
+    ///indexed by `PostShader`
+    pipelines: Vec<B::GraphicsPipeline>,
+    pub(crate)present: ManuallyDrop<B::GraphicsPipeline>,
+}
+
+impl<B: Backend> DeviceDrop<B> for PostPipeline<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        for pipeline in self.pipelines.drain(..) {
+            device.destroy_graphics_pipeline(pipeline);
+        }
+        device.destroy_graphics_pipeline(ManuallyDrop::into_inner(read(&mut self.present)));
+        device
+            .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
+    }
+}
+
+impl<B: Backend> PostPipeline<B> {
+    pub fn new(
+        device: &B::Device,
+        post_pass: &<B as Backend>::RenderPass,
+        present_pass: &<B as Backend>::RenderPass,
+        input_layout: &B::DescriptorSetLayout,
+    ) -> Result<Self, &'static str> {
+        let push_constants: Vec<(ShaderStageFlags, Range<u32>)> = vec![
+            (ShaderStageFlags::FRAGMENT, 0..(size_of::<f32>() * POST_CONSTANTS) as u32)
+        ];
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(vec![input_layout], push_constants)
+                .map_err(|_| "Couldn't create a pipeline layout")?
+        };
+        let vertex_shader_module = {
+            let spirv = pso::read_spirv(Cursor::new(&include_bytes!("../../../assets/post.vert.spv")[..]))
+                .unwrap();
+            unsafe { device.create_shader_module(&spirv) }.unwrap()
+        };
+        let create = |fragment: &[u8], render_pass: &B::RenderPass| {
+            let fragment_shader_module = {
+                let spirv = pso::read_spirv(Cursor::new(fragment)).unwrap();
+                unsafe { device.create_shader_module(&spirv) }.unwrap()
+            };
+            let pipeline_desc = GraphicsPipelineDesc {
+                shaders: GraphicsShaderSet {
+                    vertex: EntryPoint {
+                        entry: "main",
+                        module: &vertex_shader_module,
+                        specialization: Specialization::default(),
+                    },
+                    hull: None,
+                    domain: None,
+                    geometry: None,
+                    fragment: Some(EntryPoint {
+                        entry: "main",
+                        module: &fragment_shader_module,
+                        specialization: Specialization::default(),
+                    }),
+                },
+                rasterizer: Rasterizer {
+                    polygon_mode: PolygonMode::Fill,
+                    cull_face: Face::NONE,
+                    front_face: FrontFace::CounterClockwise,
+                    depth_clamping: false,
+                    depth_bias: None,
+                    conservative: false,
+                    line_width: State::Dynamic,
+                },
+                //vertices are generated from gl_VertexIndex
+                vertex_buffers: Vec::new(),
+                attributes: Vec::new(),
+                input_assembler: InputAssemblerDesc {
+                    primitive: Primitive::TriangleList,
+                    with_adjacency: false,
+                    restart_index: None,
+                },
+                blender: BlendDesc {
+                    logic_op: None,
+                    targets: vec![ColorBlendDesc {
+                        mask: ColorMask::ALL,
+                        blend: None,
+                    }],
+                },
+                depth_stencil: DepthStencilDesc {
+                    depth: None,
+                    depth_bounds: false,
+                    stencil: None,
+                },
+                multisampling: None,
+                baked_states: Default::default(),
+                layout: &layout,
+                subpass: Subpass {
+                    index: 0,
+                    main_pass: render_pass,
+                },
+                flags: PipelineCreationFlags::empty(),
+                parent: BasePipeline::None,
+            };
+            let pipeline = unsafe { device.create_graphics_pipeline(&pipeline_desc, None) }
+                .map_err(|e| {
+                    error!("{:?}", e);
+                    "Couldn't create a post-processing pipeline!"
+                });
+            unsafe { device.destroy_shader_module(fragment_shader_module) };
+            pipeline
+        };
+        let pipelines = PostShader::ALL.iter()
+            .map(|shader| create(post_fragment(*shader), post_pass))
+            .collect::<Result<Vec<_>, &str>>()?;
+        let present = create(&include_bytes!("../../../assets/blit.frag.spv")[..], present_pass)?;
+        unsafe { device.destroy_shader_module(vertex_shader_module) };
+        debug!("PostPipeline done");
+        Ok(Self {
+            pipeline_layout: ManuallyDrop::new(layout),
+            pipelines,
+            present: ManuallyDrop::new(present),
+        })
+    }
+
+    pub fn pipeline(&self, shader: PostShader) -> &B::GraphicsPipeline {
+        &self.pipelines[shader as usize]
+    }
+}
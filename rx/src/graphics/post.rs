@@ -0,0 +1,301 @@
+use std::mem::ManuallyDrop;
+
+use hal::{
+    adapter::Adapter,
+    Backend,
+    device::Device,
+    image::{Extent, Layout, Usage},
+    pso::{
+        Descriptor, DescriptorPool, DescriptorPoolCreateFlags, DescriptorRangeDesc,
+        DescriptorSetLayoutBinding, DescriptorSetWrite, DescriptorType, ImageDescriptorType,
+        ShaderStageFlags,
+    },
+    window::Extent2D,
+};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::graphics::graph::FrameGraph;
+use crate::graphics::hal_utils::ColorImage;
+use crate::graphics::swapchain::{DeviceDrop, HDR_FORMAT};
+
+///Fragment shader of a full-screen pass, see `PostEffect` constructors for its params
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostShader {
+    ToneMap = 0,
+    Gamma = 1,
+    Fxaa = 2,
+    Vignette = 3,
+    Bloom = 4,
+}
+
+impl PostShader {
+    pub const ALL: [PostShader; 5] = [
+        PostShader::ToneMap,
+        PostShader::Gamma,
+        PostShader::Fxaa,
+        PostShader::Vignette,
+        PostShader::Bloom,
+    ];
+}
+
+///One pass of post-processing chain, `params` are pushed as is to its shader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostEffect {
+    pub shader: PostShader,
+    pub params: [f32; 4],
+}
+
+impl PostEffect {
+    ///ACES filmic curve, HDR to 0..1
+    pub fn tone_map(exposure: f32) -> Self {
+        Self { shader: PostShader::ToneMap, params: [exposure, 0., 0., 0.] }
+    }
+
+    ///Only for linear swapchain formats, sRGB ones are encoded by hardware
+    pub fn gamma(gamma: f32) -> Self {
+        Self { shader: PostShader::Gamma, params: [gamma, 0., 0., 0.] }
+    }
+
+    ///Expects tone mapped input
+    pub fn fxaa() -> Self {
+        Self { shader: PostShader::Fxaa, params: [0.; 4] }
+    }
+
+    ///Darkens corners, `radius` is where darkening starts relative to half diagonal
+    pub fn vignette(strength: f32, radius: f32) -> Self {
+        Self { shader: PostShader::Vignette, params: [strength, radius, 0., 0.] }
+    }
+
+    ///Adds blurred colors brighter than `threshold`, `radius` is in pixels
+    pub fn bloom(threshold: f32, intensity: f32, radius: f32) -> Self {
+        Self { shader: PostShader::Bloom, params: [threshold, intensity, radius, 0.] }
+    }
+
+    ///Chain used until `Renderer::set_post_effects`
+    pub fn default_chain() -> Vec<Self> {
+        vec![PostEffect::tone_map(1.)]
+    }
+}
+
+///Ping-pong HDR targets for post-processing chain, two per swapchain image
+///
+///Input sets sample, in order: resolved scene, first target, second target.
+///Render passes are those of the first three effects, later ones repeat the last two.
+pub struct PostProcess<B: Backend> {
+    render_passes: Vec<B::RenderPass>,
+    pub(crate) layout: ManuallyDrop<B::DescriptorSetLayout>,
+    sampler: ManuallyDrop<B::Sampler>,
+    pool: ManuallyDrop<B::DescriptorPool>,
+    sets: Vec<Vec<B::DescriptorSet>>,
+    usage: Usage,
+    targets: Vec<Vec<ColorImage<B>>>,
+    framebuffers: Vec<Vec<B::Framebuffer>>,
+}
+
+impl<B: Backend> DeviceDrop<B> for PostProcess<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        self.drop_targets(device);
+        for sets in self.sets.drain(..) {
+            self.pool.free_sets(sets);
+        }
+        device.destroy_descriptor_pool(ManuallyDrop::into_inner(read(&mut self.pool)));
+        device.destroy_descriptor_set_layout(ManuallyDrop::into_inner(read(&mut self.layout)));
+        device.destroy_sampler(ManuallyDrop::into_inner(read(&mut self.sampler)));
+        for render_pass in self.render_passes.drain(..) {
+            device.destroy_render_pass(render_pass);
+        }
+    }
+}
+
+impl<B: Backend> PostProcess<B> {
+    pub fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        graph: &FrameGraph,
+        extent: Extent2D,
+        hdr_views: &[&B::ImageView],
+    ) -> Result<Self, &'static str> {
+        let img_count = hdr_views.len();
+        let render_passes = (0..3)
+            .map(|effect| graph.create_render_pass::<B>(device, graph.post_pass(effect)))
+            .collect::<Result<Vec<_>, &str>>()?;
+        let usage = graph.usage(graph.post_targets[0]);
+        let sampler = unsafe {
+            device
+                .create_sampler(&hal::image::SamplerDesc::new(
+                    hal::image::Filter::Linear,
+                    hal::image::WrapMode::Clamp,
+                ))
+                .map_err(|_| "Couldn't create the sampler!")?
+        };
+        let layout = unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &[
+                        DescriptorSetLayoutBinding {
+                            binding: 0,
+                            ty: DescriptorType::Image {
+                                ty: ImageDescriptorType::Sampled {
+                                    with_sampler: false
+                                },
+                            },
+                            count: 1,
+                            stage_flags: ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                        DescriptorSetLayoutBinding {
+                            binding: 1,
+                            ty: DescriptorType::Sampler,
+                            count: 1,
+                            stage_flags: ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                    ],
+                    &[],
+                )
+                .map_err(|_| "Couldn't make a DescriptorSetLayout")?
+        };
+        let mut pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    img_count * 3,
+                    &[
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Image {
+                                ty: ImageDescriptorType::Sampled {
+                                    with_sampler: false
+                                },
+                            },
+                            count: img_count * 3,
+                        },
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Sampler,
+                            count: img_count * 3,
+                        },
+                    ],
+                    DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+                )
+                .map_err(|_| "Couldn't create a descriptor pool!")?
+        };
+        let sets = (0..img_count)
+            .map(|_| {
+                (0..3)
+                    .map(|_| unsafe { pool.allocate_set(&layout) }.map_err(|_| "Couldn't make a Descriptor Set!"))
+                    .collect::<Result<Vec<_>, &str>>()
+            })
+            .collect::<Result<Vec<_>, &str>>()?;
+        //post passes are compatible, framebuffers are shared
+        let (targets, framebuffers) = Self::create_targets(adapter, device, &render_passes[0], usage, extent, img_count)?;
+
+        let post = Self {
+            render_passes,
+            layout: ManuallyDrop::new(layout),
+            sampler: ManuallyDrop::new(sampler),
+            pool: ManuallyDrop::new(pool),
+            sets,
+            usage,
+            targets,
+            framebuffers,
+        };
+        post.write_descriptor_sets(device, hdr_views);
+        Ok(post)
+    }
+
+    ///Follows swapchain recreation, device must be idle
+    pub fn resize(
+        &mut self,
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        extent: Extent2D,
+        hdr_views: &[&B::ImageView],
+    ) -> Result<(), &'static str> {
+        unsafe { self.drop_targets(device) };
+        let (targets, framebuffers) =
+            Self::create_targets(adapter, device, &self.render_passes[0], self.usage, extent, hdr_views.len())?;
+        self.targets = targets;
+        self.framebuffers = framebuffers;
+        self.write_descriptor_sets(device, hdr_views);
+        Ok(())
+    }
+
+    ///Of effect `i` in the chain, see `FrameGraph::post_pass`
+    pub fn render_pass(&self, effect: usize) -> &B::RenderPass {
+        match effect {
+            0 => &self.render_passes[0],
+            i => &self.render_passes[2 - i % 2],
+        }
+    }
+
+    ///`source` - 0 for scene, 1 and 2 for targets
+    pub fn input_set(&self, frame: usize, source: usize) -> &B::DescriptorSet {
+        &self.sets[frame][source]
+    }
+
+    pub fn framebuffer(&self, frame: usize, target: usize) -> &B::Framebuffer {
+        &self.framebuffers[frame][target]
+    }
+
+    fn write_descriptor_sets(&self, device: &B::Device, hdr_views: &[&B::ImageView]) {
+        let mut writes = Vec::new();
+        for ((sets, hdr), targets) in self.sets.iter().zip(hdr_views.iter()).zip(self.targets.iter()) {
+            let views = [*hdr, &*targets[0].image_view, &*targets[1].image_view];
+            for (set, view) in sets.iter().zip(views.iter()) {
+                writes.push(DescriptorSetWrite {
+                    set,
+                    binding: 0,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Image(*view, Layout::ShaderReadOnlyOptimal)),
+                });
+                writes.push(DescriptorSetWrite {
+                    set,
+                    binding: 1,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Sampler(&*self.sampler)),
+                });
+            }
+        }
+        unsafe { device.write_descriptor_sets(writes) };
+    }
+
+    unsafe fn drop_targets(&mut self, device: &B::Device) {
+        for fb in self.framebuffers.drain(..).flatten() {
+            device.destroy_framebuffer(fb);
+        }
+        for target in self.targets.drain(..).flatten() {
+            target.manually_drop(device);
+        }
+    }
+
+    fn create_targets(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        render_pass: &B::RenderPass,
+        usage: Usage,
+        extent: Extent2D,
+        img_count: usize,
+    ) -> Result<(Vec<Vec<ColorImage<B>>>, Vec<Vec<B::Framebuffer>>), &'static str> {
+        let mut targets = Vec::with_capacity(img_count);
+        let mut framebuffers = Vec::with_capacity(img_count);
+        let framebuffer_extent = Extent {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        };
+        for _ in 0..img_count {
+            let pair = vec![
+                ColorImage::new(adapter, device, extent, HDR_FORMAT, 1, usage)?,
+                ColorImage::new(adapter, device, extent, HDR_FORMAT, 1, usage)?,
+            ];
+            let create = |image: &ColorImage<B>| unsafe {
+                device
+                    .create_framebuffer(render_pass, Some(&*image.image_view), framebuffer_extent)
+                    .map_err(|_| "Failed to create a framebuffer!")
+            };
+            framebuffers.push(vec![create(&pair[0])?, create(&pair[1])?]);
+            targets.push(pair);
+        }
+        Ok((targets, framebuffers))
+    }
+}
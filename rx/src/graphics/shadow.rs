@@ -0,0 +1,332 @@
+use std::mem::ManuallyDrop;
+
+use hal::{
+    adapter::Adapter,
+    Backend,
+    device::Device,
+    image::{Extent, Layout},
+    pso::{
+        Comparison, Descriptor, DescriptorPool, DescriptorPoolCreateFlags, DescriptorRangeDesc,
+        DescriptorSetLayoutBinding, DescriptorSetWrite, DescriptorType, ImageDescriptorType,
+        ShaderStageFlags,
+    },
+    window::Extent2D,
+};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::ecs::LightKind;
+use crate::glm;
+use crate::graphics::draw::FrameState;
+use crate::graphics::graph::FrameGraph;
+use crate::graphics::hal_utils::DepthImage;
+use crate::graphics::swapchain::DeviceDrop;
+
+pub const MAX_CASCADES: usize = 4;
+///How far toward the light cascades reach past their slice, casters further away cast no shadow
+pub const CASTER_DISTANCE: f32 = 200.;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowConfig {
+    ///width and height of every cascade
+    pub resolution: u32,
+    ///1..=MAX_CASCADES
+    pub cascades: u32,
+    ///view distance covered by cascades
+    pub distance: f32,
+    ///blend between uniform (0.) and logarithmic (1.) cascade splits
+    pub split_lambda: f32,
+    pub bias: f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            cascades: 3,
+            distance: 400.,
+            split_lambda: 0.75,
+            bias: 0.002,
+        }
+    }
+}
+
+///Light space matrices and far split (view distance) of each cascade
+#[derive(Debug, Clone)]
+pub struct ShadowCascades {
+    pub view_proj: Vec<glm::Mat4>,
+    pub splits: Vec<f32>,
+}
+
+impl ShadowCascades {
+    ///Fits cascades of the first directional light around camera frustum slices
+    pub fn compute(state: &FrameState, config: &ShadowConfig) -> Option<Self> {
+        let direction = state.lights.iter()
+            .filter_map(|(_, light)| match light.kind {
+                LightKind::Directional { direction } => Some(direction),
+                _ => None
+            })
+            .next()?;
+        let direction = glm::normalize(&direction);
+
+        let (near, full_far) = state.clip_planes();
+        let far = full_far.min(near + config.distance);
+
+        let inv_vp = glm::inverse(&(state.proj * state.view));
+        let corners = |z: f32| -> Vec<glm::Vec3> {
+            [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].iter()
+                .map(|(x, y)| {
+                    let p = inv_vp * glm::vec4(*x, *y, z, 1.);
+                    glm::vec3(p.x, p.y, p.z) / p.w
+                })
+                .collect()
+        };
+        let (near_corners, far_corners) = (corners(-1.), corners(1.));
+        //frustum slice corners at view distance
+        let at = |d: f32| -> Vec<glm::Vec3> {
+            let t = (d - near) / (full_far - near);
+            near_corners.iter().zip(far_corners.iter())
+                .map(|(n, f)| n + (f - n) * t)
+                .collect()
+        };
+
+        let count = (config.cascades as usize).max(1).min(MAX_CASCADES);
+        let splits: Vec<f32> = (1..=count)
+            .map(|i| {
+                let p = i as f32 / count as f32;
+                let log = near * (far / near).powf(p);
+                let uniform = near + (far - near) * p;
+                config.split_lambda * log + (1. - config.split_lambda) * uniform
+            })
+            .collect();
+
+        let up = if direction.y.abs() > 0.99 { glm::vec3(0., 0., 1.) } else { glm::vec3(0., 1., 0.) };
+        let mut last = near;
+        let view_proj = splits.iter()
+            .map(|split| {
+                let slice: Vec<glm::Vec3> = at(last).into_iter().chain(at(*split).into_iter()).collect();
+                last = *split;
+                let center = slice.iter().fold(glm::vec3(0., 0., 0.), |acc, c| acc + c) / slice.len() as f32;
+                let radius = slice.iter()
+                    .map(|c| glm::distance(c, &center))
+                    .fold(0_f32, f32::max)
+                    .ceil();
+                //pulled back to catch casters outside of the slice, they'd be depth clipped otherwise
+                let eye = center - direction * (radius + CASTER_DISTANCE);
+                let view = glm::look_at(&eye, &center, &up);
+                let proj = glm::ortho_rh_zo(-radius, radius, -radius, radius, 0., CASTER_DISTANCE + radius * 2.);
+                proj * view
+            })
+            .collect();
+        Some(Self { view_proj, splits })
+    }
+}
+
+///Layered shadow depth map, one layer and framebuffer per cascade
+pub struct ShadowMap<B: Backend> {
+    pub(crate) config: ShadowConfig,
+    pub(crate) render_pass: ManuallyDrop<B::RenderPass>,
+    pub(crate) framebuffers: Vec<B::Framebuffer>,
+    layer_views: Vec<B::ImageView>,
+    depth: DepthImage<B>,
+    sampler: ManuallyDrop<B::Sampler>,
+    pub(crate) layout: ManuallyDrop<B::DescriptorSetLayout>,
+    pool: ManuallyDrop<B::DescriptorPool>,
+    pub(crate) descriptor_set: ManuallyDrop<B::DescriptorSet>,
+}
+
+impl<B: Backend> DeviceDrop<B> for ShadowMap<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        self.drop_targets(device);
+        self.pool.free_sets(Some(ManuallyDrop::into_inner(read(&mut self.descriptor_set))));
+        device.destroy_descriptor_pool(ManuallyDrop::into_inner(read(&mut self.pool)));
+        device.destroy_descriptor_set_layout(ManuallyDrop::into_inner(read(&mut self.layout)));
+        device.destroy_sampler(ManuallyDrop::into_inner(read(&mut self.sampler)));
+        device.destroy_render_pass(ManuallyDrop::into_inner(read(&mut self.render_pass)));
+    }
+}
+
+impl<B: Backend> ShadowMap<B> {
+    pub fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        graph: &FrameGraph,
+        config: ShadowConfig,
+    ) -> Result<Self, &'static str> {
+        let render_pass = graph.create_render_pass::<B>(device, graph.shadow)?;
+        let sampler = unsafe {
+            device
+                .create_sampler(&hal::image::SamplerDesc {
+                    comparison: Some(Comparison::LessEqual),
+                    ..hal::image::SamplerDesc::new(
+                        hal::image::Filter::Linear,
+                        hal::image::WrapMode::Clamp,
+                    )
+                })
+                .map_err(|_| "Couldn't create the sampler!")?
+        };
+        let layout = unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &[
+                        DescriptorSetLayoutBinding {
+                            binding: 0,
+                            ty: DescriptorType::Image {
+                                ty: ImageDescriptorType::Sampled {
+                                    with_sampler: false
+                                },
+                            },
+                            count: 1,
+                            stage_flags: ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                        DescriptorSetLayoutBinding {
+                            binding: 1,
+                            ty: DescriptorType::Sampler,
+                            count: 1,
+                            stage_flags: ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                    ],
+                    &[],
+                )
+                .map_err(|_| "Couldn't make a DescriptorSetLayout")?
+        };
+        let mut pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    1,
+                    &[
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Image {
+                                ty: ImageDescriptorType::Sampled {
+                                    with_sampler: false
+                                },
+                            },
+                            count: 1,
+                        },
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Sampler,
+                            count: 1,
+                        },
+                    ],
+                    DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+                )
+                .map_err(|_| "Couldn't create a descriptor pool!")?
+        };
+        let descriptor_set = unsafe {
+            pool
+                .allocate_set(&layout)
+                .map_err(|_| "Couldn't make a Descriptor Set!")?
+        };
+        let (depth, layer_views, framebuffers) =
+            Self::create_targets(adapter, device, &render_pass, &config)?;
+
+        let shadow = Self {
+            config,
+            render_pass: ManuallyDrop::new(render_pass),
+            framebuffers,
+            layer_views,
+            depth,
+            sampler: ManuallyDrop::new(sampler),
+            layout: ManuallyDrop::new(layout),
+            pool: ManuallyDrop::new(pool),
+            descriptor_set: ManuallyDrop::new(descriptor_set),
+        };
+        shadow.write_descriptor_set(device);
+        Ok(shadow)
+    }
+
+    ///Recreates depth targets, device must be idle
+    pub fn reconfigure(
+        &mut self,
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        config: ShadowConfig,
+    ) -> Result<(), &'static str> {
+        unsafe { self.drop_targets(device) };
+        let (depth, layer_views, framebuffers) =
+            Self::create_targets(adapter, device, &self.render_pass, &config)?;
+        self.depth = depth;
+        self.layer_views = layer_views;
+        self.framebuffers = framebuffers;
+        self.config = config;
+        self.write_descriptor_set(device);
+        info!("Shadow map: {:?}", self.config);
+        Ok(())
+    }
+
+    pub fn extent(&self) -> Extent2D {
+        Extent2D {
+            width: self.config.resolution,
+            height: self.config.resolution,
+        }
+    }
+
+    fn write_descriptor_set(&self, device: &B::Device) {
+        unsafe {
+            device.write_descriptor_sets(vec![
+                DescriptorSetWrite {
+                    set: &*self.descriptor_set,
+                    binding: 0,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Image(
+                        &*self.depth.image_view,
+                        Layout::DepthStencilReadOnlyOptimal,
+                    )),
+                },
+                DescriptorSetWrite {
+                    set: &*self.descriptor_set,
+                    binding: 1,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Sampler(&*self.sampler)),
+                },
+            ]);
+        }
+    }
+
+    unsafe fn drop_targets(&mut self, device: &B::Device) {
+        for fb in self.framebuffers.drain(..) {
+            device.destroy_framebuffer(fb);
+        }
+        for iv in self.layer_views.drain(..) {
+            device.destroy_image_view(iv);
+        }
+        self.depth.manually_drop(device);
+    }
+
+    fn create_targets(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        render_pass: &B::RenderPass,
+        config: &ShadowConfig,
+    ) -> Result<(DepthImage<B>, Vec<B::ImageView>, Vec<B::Framebuffer>), &'static str> {
+        let layers = (config.cascades as usize).max(1).min(MAX_CASCADES) as u16;
+        let extent = Extent2D {
+            width: config.resolution,
+            height: config.resolution,
+        };
+        let depth = DepthImage::layered(adapter, device, extent, layers)?;
+        let layer_views = (0..layers)
+            .map(|layer| depth.layer_view(device, layer))
+            .collect::<Result<Vec<_>, &str>>()?;
+        let framebuffers = layer_views
+            .iter()
+            .map(|view| unsafe {
+                device
+                    .create_framebuffer(
+                        render_pass,
+                        Some(view),
+                        Extent {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                    )
+                    .map_err(|_| "Failed to create a framebuffer!")
+            })
+            .collect::<Result<Vec<_>, &str>>()?;
+        Ok((depth, layer_views, framebuffers))
+    }
+}
@@ -18,6 +18,8 @@ pub struct HalStateV2<B: Backend> {
     pub(crate) _adapter: hal::adapter::Adapter<B>,
     pub(crate) _surface: ManuallyDrop<B::Surface>,
     pub(crate) _instance: Option<ManuallyDrop<B::Instance>>,
+    ///optional features the device was opened with
+    pub(crate) features: hal::Features,
 }
 
 impl<B: Backend> HalStateV2<B> {
@@ -47,20 +49,22 @@ impl<B: Backend> HalStateV2<B> {
             .ok_or("Couldn't find a graphical Adapter!")?;
         info!("{:?}", adapter);
         //device stuff
-        let (device, queue_group) = {
+        let (device, queue_group, features) = {
             let queue_family = adapter
                 .queue_families
                 .iter()
                 .find(|qf| qf.queue_type().supports_graphics() && surface.supports_queue_family(qf))
                 .ok_or("Couldn't find a QueueFamily with graphics!")?;
 
+            let features = adapter.physical_device.features() & hal::Features::NON_FILL_POLYGON_MODE;
+            info!("Enabled features: {:?}", features);
             let Gpu {
                 device,
                 mut queue_groups,
             } = unsafe {
                 adapter
                     .physical_device
-                    .open(&[(&queue_family, &[1.0; 1])], hal::Features::empty())
+                    .open(&[(&queue_family, &[1.0; 1])], features)
                     .map_err(|_| "Couldn't open the PhysicalDevice!")?
             };
 
@@ -70,7 +74,7 @@ impl<B: Backend> HalStateV2<B> {
             } else {
                 Err("The QueueGroup did not have any CommandQueues available!")
             }?;
-            (device, queue_group)
+            (device, queue_group, features)
         };
 
         Ok((
@@ -82,6 +86,7 @@ impl<B: Backend> HalStateV2<B> {
                     None => None,
                     Some(i) => Some(ManuallyDrop::new(i)),
                 },
+                features,
             },
             queue_group,
         ))
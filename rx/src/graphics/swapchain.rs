@@ -4,19 +4,22 @@ use arrayvec::ArrayVec;
 use hal::{
     Backend,
     device::Device,
-    format::{ChannelType, Swizzle},
-    image::{Extent, SubresourceRange, ViewKind},
+    format::{ChannelType, Format, Swizzle},
+    image::{Extent, NumSamples, SubresourceRange, ViewKind},
     pool::CommandPool,
     pso::*, queue::*, window::*, window::Surface,
 };
-use hal::pass::{SubpassDependency, SubpassId};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use winit::dpi::PhysicalSize;
 
-use crate::graphics::hal_utils::DepthImage;
+use crate::graphics::graph::FrameGraph;
+use crate::graphics::hal_utils::{ColorImage, DepthImage};
 use crate::graphics::state::HalStateV2;
 
+///Scene color before post-processing, see `PostProcess`
+pub const HDR_FORMAT: Format = Format::Rgba16Sfloat;
+
 pub trait DeviceDrop<B: Backend> {
     unsafe fn manually_drop(&mut self, device: &B::Device);
 }
@@ -30,7 +33,12 @@ pub struct CommonSwapchain<B: Backend> {
     command_buffers: Vec<B::CommandBuffer>,
     command_pool: ManuallyDrop<B::CommandPool>,
     queue_group: ManuallyDrop<QueueGroup<B>>,
+    ///scene into HDR target
     render_pass: ManuallyDrop<B::RenderPass>,
+    ///last post-processing pass and overlays into swapchain image
+    present_pass: ManuallyDrop<B::RenderPass>,
+    samples: NumSamples,
+    graph: FrameGraph,
 
     swapchain_config: SwapchainConfig,
     base: BaseSwapchain<B>,
@@ -55,14 +63,20 @@ impl<B: Backend> DeviceDrop<B> for CommonSwapchain<B> {
         device.destroy_command_pool(ManuallyDrop::into_inner(read(&mut self.command_pool)));
         ManuallyDrop::drop(&mut self.queue_group);
         device.destroy_render_pass(ManuallyDrop::into_inner(read(&mut self.render_pass)));
+        device.destroy_render_pass(ManuallyDrop::into_inner(read(&mut self.present_pass)));
     }
 }
 
 pub struct BaseSwapchain<B: Backend> {
     img_count: usize,
     framebuffers: Vec<B::Framebuffer>,
+    present_framebuffers: Vec<B::Framebuffer>,
     image_views: Vec<B::ImageView>,
     depth_images: Vec<DepthImage<B>>,
+    ///multisampled color targets, empty without MSAA
+    msaa_images: Vec<ColorImage<B>>,
+    ///resolved scene color, sampled by post-processing
+    hdr_images: Vec<ColorImage<B>>,
     swapchain: ManuallyDrop<B::Swapchain>,
     extent: Extent2D,
 }
@@ -72,12 +86,21 @@ impl<B: Backend> DeviceDrop<B> for BaseSwapchain<B> {
         for fb in self.framebuffers.drain(..) {
             device.destroy_framebuffer(fb);
         }
+        for fb in self.present_framebuffers.drain(..) {
+            device.destroy_framebuffer(fb);
+        }
         for iv in self.image_views.drain(..) {
             device.destroy_image_view(iv);
         }
         for di in self.depth_images.drain(..) {
             di.manually_drop(device);
         }
+        for ci in self.msaa_images.drain(..) {
+            ci.manually_drop(device);
+        }
+        for ci in self.hdr_images.drain(..) {
+            ci.manually_drop(device);
+        }
         use std::ptr::read;
         device.destroy_swapchain(ManuallyDrop::into_inner(read(&mut self.swapchain)));
     }
@@ -89,12 +112,21 @@ impl<B: Backend> BaseSwapchain<B> {
             for fb in self.framebuffers.drain(..) {
                 device.destroy_framebuffer(fb);
             }
+            for fb in self.present_framebuffers.drain(..) {
+                device.destroy_framebuffer(fb);
+            }
             for iv in self.image_views.drain(..) {
                 device.destroy_image_view(iv);
             }
             for di in self.depth_images.drain(..) {
                 di.manually_drop(device);
             }
+            for ci in self.msaa_images.drain(..) {
+                ci.manually_drop(device);
+            }
+            for ci in self.hdr_images.drain(..) {
+                ci.manually_drop(device);
+            }
         }
         use std::ptr::read;
         unsafe { ManuallyDrop::into_inner(read(&mut self.swapchain)) }
@@ -102,8 +134,11 @@ impl<B: Backend> BaseSwapchain<B> {
 
     fn new(
         state: &mut HalStateV2<B>,
+        graph: &FrameGraph,
         render_pass: &B::RenderPass,
+        present_pass: &B::RenderPass,
         config: SwapchainConfig,
+        samples: NumSamples,
         old_chain: Option<B::Swapchain>,
     ) -> Result<Self, &'static str> {
         let (swapchain, extent, backbuffer, config) = {
@@ -131,7 +166,7 @@ impl<B: Backend> BaseSwapchain<B> {
             (swapchain, extent, backbuffer, config)
         };
         dbg!();
-        let (image_views, depth_images, framebuffers) = {
+        let (image_views, depth_images, msaa_images, hdr_images, framebuffers, present_framebuffers) = {
             let image_views: Vec<<B as Backend>::ImageView> = {
                 backbuffer
                     .into_iter()
@@ -155,40 +190,68 @@ impl<B: Backend> BaseSwapchain<B> {
             };
             let depth_images = image_views
                 .iter()
-                .map(|_| DepthImage::new(&state._adapter, &state.device, extent))
+                .map(|_| DepthImage::multisampled(&state._adapter, &state.device, extent, samples))
+                .collect::<Result<Vec<_>, &str>>()?;
+            let msaa_images = match graph.msaa {
+                Some(msaa) => image_views
+                    .iter()
+                    .map(|_| ColorImage::new(&state._adapter, &state.device, extent, HDR_FORMAT, samples, graph.usage(msaa)))
+                    .collect::<Result<Vec<_>, &str>>()?,
+                None => Vec::new(),
+            };
+            let hdr_images = image_views
+                .iter()
+                .map(|_| ColorImage::new(&state._adapter, &state.device, extent, HDR_FORMAT, 1, graph.usage(graph.hdr)))
                 .collect::<Result<Vec<_>, &str>>()?;
+            let framebuffer_extent = Extent {
+                width: extent.width as u32,
+                height: extent.height as u32,
+                depth: 1,
+            };
 
             let framebuffers: Vec<<B as Backend>::Framebuffer> = {
-                image_views
+                hdr_images
                     .iter()
                     .zip(depth_images.iter())
-                    .map(|(image_view, depth_image_view)| unsafe {
-                        let attachments: ArrayVec<[_; 2]> =
-                            [image_view, &depth_image_view.image_view].into();
+                    .enumerate()
+                    .map(|(i, (hdr_image, depth_image_view))| unsafe {
+                        //attachment order must match scene pass of FrameGraph
+                        let attachments: ArrayVec<[_; 3]> = match msaa_images.get(i) {
+                            Some(msaa) => [
+                                &*msaa.image_view,
+                                &*depth_image_view.image_view,
+                                &*hdr_image.image_view,
+                            ].iter().cloned().collect(),
+                            None => [&*hdr_image.image_view, &*depth_image_view.image_view].iter().cloned().collect(),
+                        };
                         state
                             .device
-                            .create_framebuffer(
-                                &render_pass,
-                                attachments,
-                                Extent {
-                                    width: extent.width as u32,
-                                    height: extent.height as u32,
-                                    depth: 1,
-                                },
-                            )
+                            .create_framebuffer(&render_pass, attachments, framebuffer_extent)
                             .map_err(|_| "Failed to create a framebuffer!")
                     })
                     .collect::<Result<Vec<_>, &str>>()?
             };
+            let present_framebuffers: Vec<<B as Backend>::Framebuffer> = image_views
+                .iter()
+                .map(|image_view| unsafe {
+                    state
+                        .device
+                        .create_framebuffer(&present_pass, Some(image_view), framebuffer_extent)
+                        .map_err(|_| "Failed to create a framebuffer!")
+                })
+                .collect::<Result<Vec<_>, &str>>()?;
             info!("Swapchain stuff: {:?}", (&image_views.len(), &framebuffers.len()));
-            (image_views, depth_images, framebuffers)
+            (image_views, depth_images, msaa_images, hdr_images, framebuffers, present_framebuffers)
         };
 
         Ok(Self {
             swapchain: ManuallyDrop::new(swapchain),
             image_views,
             depth_images,
+            msaa_images,
+            hdr_images,
             framebuffers,
+            present_framebuffers,
             img_count: config.image_count as usize,
             extent,
         })
@@ -211,81 +274,53 @@ impl<B: Backend> CommonSwapchain<B> {
             height: size.height as u32
         };
         let old = swapchain.pop_old_swapchain(&state.device);
-        self.base = BaseSwapchain::new(state, &self.render_pass, self.swapchain_config.clone(), Some(old))?;
+        self.base = BaseSwapchain::new(
+            state,
+            &self.graph,
+            &self.render_pass,
+            &self.present_pass,
+            self.swapchain_config.clone(),
+            self.samples,
+            Some(old),
+        )?;
         info!("New extent: {:?}", self.base.extent);
         Ok(())
     }
 
-    fn create_render_pass<'a>(
-        device: &'a <B as Backend>::Device,
-        format: hal::format::Format,
-    ) -> Result<B::RenderPass, &'static str> {
-        //todo move desc's upper
-        use hal::image::Layout;
-        use hal::pass::{
-            Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, SubpassDesc,
-        };
-        let color_attachment = Attachment {
-            format: Some(format),
-            samples: 1,
-            ops: AttachmentOps {
-                load: AttachmentLoadOp::Clear,
-                store: AttachmentStoreOp::Store,
-            },
-            stencil_ops: AttachmentOps::DONT_CARE,
-            layouts: Layout::Undefined..Layout::Present,
-        };
-        let depth_attachment = Attachment {
-            format: Some(hal::format::Format::D32Sfloat),
-            samples: 1,
-            ops: AttachmentOps {
-                load: AttachmentLoadOp::Clear,
-                store: AttachmentStoreOp::DontCare,
-            },
-            stencil_ops: AttachmentOps::DONT_CARE,
-            layouts: Layout::Undefined..Layout::DepthStencilAttachmentOptimal,
-        };
-        //pre frag stage check
-        use hal::image::Access;
-        use hal::memory::Dependencies;
-        let in_dependency = SubpassDependency {
-            passes: None..Some(0),
-            stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT
-                ..PipelineStage::COLOR_ATTACHMENT_OUTPUT | PipelineStage::EARLY_FRAGMENT_TESTS,
-            accesses: Access::empty()
-                ..(Access::COLOR_ATTACHMENT_READ
-                | Access::COLOR_ATTACHMENT_WRITE
-                | Access::DEPTH_STENCIL_ATTACHMENT_READ
-                | Access::DEPTH_STENCIL_ATTACHMENT_WRITE),
-            flags: Dependencies::empty(),
-        };
-        let out_dependency = SubpassDependency {
-            passes: Some(0)..None,
-            stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT | PipelineStage::EARLY_FRAGMENT_TESTS
-                ..PipelineStage::COLOR_ATTACHMENT_OUTPUT,
-            accesses: (Access::COLOR_ATTACHMENT_READ
-                | Access::COLOR_ATTACHMENT_WRITE
-                | Access::DEPTH_STENCIL_ATTACHMENT_READ
-                | Access::DEPTH_STENCIL_ATTACHMENT_WRITE)..Access::empty(),
-            flags: Dependencies::empty(),
-        };
+    ///Recreates render pass and attachments, pipelines made with old render pass must be rebuilt
+    pub fn set_samples(&mut self, state: &mut HalStateV2<B>, samples: NumSamples) -> Result<(), &'static str> {
+        for fence in self.img_fences.iter() {
+            unsafe {
+                state.device
+                    .wait_for_fence(fence, core::u64::MAX)
+                    .map_err(|_| "Failed to wait on the fence!")?;
+            };
+        }
+        let graph = FrameGraph::new(samples, HDR_FORMAT, self.swapchain_config.format)?;
+        let render_pass = graph.create_render_pass::<B>(&state.device, graph.scene)?;
+        let old = self.base.pop_old_swapchain(&state.device);
+        unsafe {
+            use std::ptr::read;
+            state.device.destroy_render_pass(ManuallyDrop::into_inner(read(&mut self.render_pass)));
+        }
+        self.render_pass = ManuallyDrop::new(render_pass);
+        self.samples = samples;
+        self.graph = graph;
+        self.base = BaseSwapchain::new(
+            state,
+            &self.graph,
+            &self.render_pass,
+            &self.present_pass,
+            self.swapchain_config.clone(),
+            samples,
+            Some(old),
+        )?;
+        info!("MSAA samples: {:?}", samples);
+        Ok(())
+    }
 
-        let subpass = SubpassDesc {
-            colors: &[(0, Layout::ColorAttachmentOptimal)],
-            depth_stencil: Some(&(1, Layout::DepthStencilAttachmentOptimal)),
-            inputs: &[],
-            resolves: &[],
-            preserves: &[],
-        };
-        Ok(unsafe {
-            device
-                .create_render_pass(
-                    &[color_attachment, depth_attachment],
-                    &[subpass],
-                    &[in_dependency, out_dependency],
-                )
-                .map_err(|_| "Couldn't create a render pass!")?
-        })
+    pub fn samples(&self) -> NumSamples {
+        self.samples
     }
 
     pub fn current_extent(&self) -> Extent2D {
@@ -296,6 +331,25 @@ impl<B: Backend> CommonSwapchain<B> {
         &self.render_pass
     }
 
+    pub fn present_pass(&self) -> &B::RenderPass {
+        &self.present_pass
+    }
+
+    ///Frame passes for current sample count, other render passes are made from it
+    pub fn graph(&self) -> &FrameGraph {
+        &self.graph
+    }
+
+    ///Resolved scene color per image, recreated with swapchain and on `set_samples`
+    pub fn hdr_views(&self) -> Vec<&B::ImageView> {
+        self.base.hdr_images.iter().map(|image| &*image.image_view).collect()
+    }
+
+    ///pool and queue for one-shot transfer submissions
+    pub(crate) fn transfer_parts(&mut self) -> (&mut B::CommandPool, &mut B::CommandQueue) {
+        (&mut self.command_pool, &mut self.queue_group.queues[0])
+    }
+
     pub fn new<'a>(
         state: &'a mut HalStateV2<B>,
         queue_group: QueueGroup<B>,
@@ -384,9 +438,12 @@ impl<B: Backend> CommonSwapchain<B> {
             }
         };
 
-        let render_pass = Self::create_render_pass(&state.device, swapchain_config.format)?;
+        let samples = 1;
+        let graph = FrameGraph::new(samples, HDR_FORMAT, swapchain_config.format)?;
+        let render_pass = graph.create_render_pass::<B>(&state.device, graph.scene)?;
+        let present_pass = graph.create_render_pass::<B>(&state.device, graph.present)?;
 
-        let base = BaseSwapchain::new(state, &render_pass, swapchain_config.clone(), None)?;
+        let base = BaseSwapchain::new(state, &graph, &render_pass, &present_pass, swapchain_config.clone(), samples, None)?;
         let (image_available_semaphores, render_finished_semaphores, swapchain_img_fences) = {
             let mut image_available_semaphores: Vec<<B as Backend>::Semaphore> = vec![];
             let mut render_finished_semaphores: Vec<<B as Backend>::Semaphore> = vec![];
@@ -446,6 +503,9 @@ impl<B: Backend> CommonSwapchain<B> {
             command_pool: ManuallyDrop::new(command_pool),
             base,
             render_pass: ManuallyDrop::new(render_pass),
+            present_pass: ManuallyDrop::new(present_pass),
+            samples,
+            graph,
             swapchain_config,
         })
     }
@@ -459,6 +519,8 @@ impl<B: Backend> CommonSwapchain<B> {
             &mut B::CommandBuffer,
             &B::Framebuffer,
             &B::RenderPass,
+            &B::Framebuffer,
+            &B::RenderPass,
         ),
         &str,
     > {
@@ -491,6 +553,8 @@ impl<B: Backend> CommonSwapchain<B> {
             &mut self.command_buffers[i_usize],
             &self.base.framebuffers[i_usize],
             &self.render_pass,
+            &self.base.present_framebuffers[i_usize],
+            &self.present_pass,
         ))
     }
 
@@ -0,0 +1,239 @@
+use std::mem::ManuallyDrop;
+
+use arrayvec::ArrayVec;
+use hal::{
+    adapter::Adapter,
+    Backend,
+    command,
+    command::CommandBuffer,
+    device::Device,
+    image::{Extent, Layout, NumSamples, SubresourceRange},
+    pool::CommandPool,
+    pso::PipelineStage,
+    queue::CommandQueue,
+    window::Extent2D,
+};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::assets::TexturePtr;
+use crate::graphics::graph::FrameGraph;
+use crate::graphics::hal_utils::{ColorImage, DepthImage};
+use crate::graphics::swapchain::{DeviceDrop, HDR_FORMAT};
+use crate::graphics::texture::TextureStorage;
+
+///Offscreen color a `Camera` can draw into, `texture` can be used by `Material`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TargetPtr {
+    pub(crate) index: usize,
+    pub texture: TexturePtr,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TargetPtr {
+    pub fn extent(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+///Scene pass attachments of one target, resolved color stays in sampled layout between frames
+pub struct RenderTarget<B: Backend> {
+    pub(crate) extent: Extent2D,
+    msaa: Option<ColorImage<B>>,
+    depth: DepthImage<B>,
+    color: ColorImage<B>,
+    pub(crate) framebuffer: ManuallyDrop<B::Framebuffer>,
+    texture: usize,
+    ///not yet moved out of undefined layout
+    fresh: bool,
+}
+
+impl<B: Backend> RenderTarget<B> {
+    fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        graph: &FrameGraph,
+        render_pass: &B::RenderPass,
+        samples: NumSamples,
+        extent: Extent2D,
+        texture: usize,
+    ) -> Result<Self, &'static str> {
+        let msaa = match graph.msaa {
+            Some(msaa) => Some(ColorImage::new(adapter, device, extent, HDR_FORMAT, samples, graph.usage(msaa))?),
+            None => None,
+        };
+        let depth = DepthImage::multisampled(adapter, device, extent, samples)?;
+        let color = ColorImage::new(adapter, device, extent, HDR_FORMAT, 1, graph.usage(graph.hdr))?;
+        //same attachment order as swapchain framebuffers
+        let attachments: ArrayVec<[_; 3]> = match msaa.as_ref() {
+            Some(msaa) => [&*msaa.image_view, &*depth.image_view, &*color.image_view].iter().cloned().collect(),
+            None => [&*color.image_view, &*depth.image_view].iter().cloned().collect(),
+        };
+        let framebuffer = unsafe {
+            device
+                .create_framebuffer(render_pass, attachments, Extent {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                })
+                .map_err(|_| "Failed to create a framebuffer!")?
+        };
+        Ok(Self {
+            extent,
+            msaa,
+            depth,
+            color,
+            framebuffer: ManuallyDrop::new(framebuffer),
+            texture,
+            fresh: true,
+        })
+    }
+
+    unsafe fn manually_drop(&self, device: &B::Device) {
+        use std::ptr::read;
+        device.destroy_framebuffer(ManuallyDrop::into_inner(read(&self.framebuffer)));
+        self.color.manually_drop(device);
+        self.depth.manually_drop(device);
+        if let Some(msaa) = self.msaa.as_ref() {
+            msaa.manually_drop(device);
+        }
+    }
+}
+
+///Targets drawn with scene render pass, sampled through `TextureStorage` like any texture
+pub struct RenderTargets<B: Backend> {
+    sampler: ManuallyDrop<B::Sampler>,
+    targets: Vec<RenderTarget<B>>,
+}
+
+impl<B: Backend> DeviceDrop<B> for RenderTargets<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        for target in self.targets.drain(..) {
+            target.manually_drop(device);
+        }
+        device.destroy_sampler(ManuallyDrop::into_inner(read(&mut self.sampler)));
+    }
+}
+
+impl<B: Backend> RenderTargets<B> {
+    pub fn new(device: &B::Device) -> Result<Self, &'static str> {
+        let sampler = unsafe {
+            device
+                .create_sampler(&hal::image::SamplerDesc::new(
+                    hal::image::Filter::Linear,
+                    hal::image::WrapMode::Clamp,
+                ))
+                .map_err(|_| "Couldn't create the sampler!")?
+        };
+        Ok(Self {
+            sampler: ManuallyDrop::new(sampler),
+            targets: Vec::new(),
+        })
+    }
+
+    ///`render_pass` is the scene one, so main pipelines draw into target as is
+    pub fn create(
+        &mut self,
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        graph: &FrameGraph,
+        render_pass: &B::RenderPass,
+        samples: NumSamples,
+        extent: Extent2D,
+        textures: &mut TextureStorage<B>,
+    ) -> Result<TargetPtr, &'static str> {
+        if extent.width == 0 || extent.height == 0 {
+            return Err("Render target can't be empty!");
+        }
+        let target = RenderTarget::new(adapter, device, graph, render_pass, samples, extent, 0)?;
+        let texture = match textures.register(device, &target.color.image_view, &self.sampler) {
+            Ok(texture) => texture,
+            Err(e) => {
+                unsafe { target.manually_drop(device) };
+                return Err(e);
+            }
+        };
+        self.targets.push(RenderTarget { texture, ..target });
+        info!("Render target created: {:?}", (texture, extent.width, extent.height));
+        Ok(TargetPtr {
+            index: self.targets.len() - 1,
+            texture: TexturePtr(texture),
+            width: extent.width,
+            height: extent.height,
+        })
+    }
+
+    ///Follows sample count change, device must be idle, textures keep their indices
+    pub fn recreate(
+        &mut self,
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        graph: &FrameGraph,
+        render_pass: &B::RenderPass,
+        samples: NumSamples,
+        textures: &TextureStorage<B>,
+    ) -> Result<(), &'static str> {
+        for target in self.targets.iter_mut() {
+            let fresh = RenderTarget::new(adapter, device, graph, render_pass, samples, target.extent, target.texture)?;
+            textures.rebind(device, fresh.texture, &fresh.color.image_view, &self.sampler);
+            unsafe { target.manually_drop(device) };
+            *target = fresh;
+        }
+        Ok(())
+    }
+
+    ///Moves new targets into sampled layout, so they can be sampled before being drawn
+    pub fn prepare(
+        &mut self,
+        device: &B::Device,
+        command_pool: &mut B::CommandPool,
+        command_queue: &mut B::CommandQueue,
+    ) -> Result<(), &'static str> {
+        if self.targets.iter().all(|t| !t.fresh) {
+            return Ok(());
+        }
+        unsafe {
+            let mut cmd_buffer = command_pool.allocate_one(command::Level::Primary);
+            cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
+            let barriers: Vec<_> = self.targets.iter()
+                .filter(|t| t.fresh)
+                .map(|t| hal::memory::Barrier::Image {
+                    states: (hal::image::Access::empty(), Layout::Undefined)
+                        ..(hal::image::Access::SHADER_READ, Layout::ShaderReadOnlyOptimal),
+                    target: &*t.color.image,
+                    families: None,
+                    range: SubresourceRange {
+                        aspects: hal::format::Aspects::COLOR,
+                        levels: 0..1,
+                        layers: 0..1,
+                    },
+                })
+                .collect();
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::FRAGMENT_SHADER,
+                hal::memory::Dependencies::empty(),
+                barriers,
+            );
+            cmd_buffer.finish();
+            let fence = device
+                .create_fence(false)
+                .map_err(|_| "Couldn't create a fence!")?;
+            command_queue.submit_without_semaphores(Some(&cmd_buffer), Some(&fence));
+            device
+                .wait_for_fence(&fence, core::u64::MAX)
+                .map_err(|_| "Couldn't wait for the fence!")?;
+            device.destroy_fence(fence);
+            command_pool.free(Some(cmd_buffer));
+        }
+        for target in self.targets.iter_mut() {
+            target.fresh = false;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, ptr: &TargetPtr) -> Option<&RenderTarget<B>> {
+        self.targets.get(ptr.index)
+    }
+}
@@ -0,0 +1,196 @@
+use std::mem::ManuallyDrop;
+
+use hal::{
+    adapter::Adapter,
+    Backend,
+    device::Device,
+    image::Layout,
+    pso::{
+        Descriptor, DescriptorPool, DescriptorPoolCreateFlags, DescriptorRangeDesc,
+        DescriptorSetLayoutBinding, DescriptorSetWrite, DescriptorType, ImageDescriptorType,
+        ShaderStageFlags,
+    },
+};
+use image::RgbaImage;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::graphics::hal_utils::LoadedImage;
+use crate::graphics::swapchain::DeviceDrop;
+
+const MAX_TEXTURES: usize = 256;
+
+///Sampled textures, each with own descriptor set (set = 1 in main pipeline)
+///
+///Texture at index 0 is 1x1 white, bound for untextured materials.
+///Images without `LoadedImage` are owned elsewhere, see `register`.
+pub struct TextureStorage<B: Backend> {
+    pub(crate) layout: ManuallyDrop<B::DescriptorSetLayout>,
+    pool: ManuallyDrop<B::DescriptorPool>,
+    textures: Vec<(Option<LoadedImage<B>>, B::DescriptorSet)>,
+}
+
+impl<B: Backend> DeviceDrop<B> for TextureStorage<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        for (image, set) in self.textures.drain(..) {
+            self.pool.free_sets(Some(set));
+            if let Some(image) = image {
+                image.manually_drop(device);
+            }
+        }
+        device.destroy_descriptor_pool(ManuallyDrop::into_inner(read(&mut self.pool)));
+        device.destroy_descriptor_set_layout(ManuallyDrop::into_inner(read(&mut self.layout)));
+    }
+}
+
+impl<B: Backend> TextureStorage<B> {
+    pub fn new(device: &B::Device) -> Result<Self, &'static str> {
+        let layout = unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &[
+                        DescriptorSetLayoutBinding {
+                            binding: 0,
+                            ty: DescriptorType::Image {
+                                ty: ImageDescriptorType::Sampled {
+                                    with_sampler: false
+                                },
+                            },
+                            count: 1,
+                            stage_flags: ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                        DescriptorSetLayoutBinding {
+                            binding: 1,
+                            ty: DescriptorType::Sampler,
+                            count: 1,
+                            stage_flags: ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        },
+                    ],
+                    &[],
+                )
+                .map_err(|_| "Couldn't make a DescriptorSetLayout")?
+        };
+        let pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    MAX_TEXTURES,
+                    &[
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Image {
+                                ty: ImageDescriptorType::Sampled {
+                                    with_sampler: false
+                                },
+                            },
+                            count: MAX_TEXTURES,
+                        },
+                        DescriptorRangeDesc {
+                            ty: DescriptorType::Sampler,
+                            count: MAX_TEXTURES,
+                        },
+                    ],
+                    DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+                )
+                .map_err(|_| "Couldn't create a descriptor pool!")?
+        };
+        Ok(Self {
+            layout: ManuallyDrop::new(layout),
+            pool: ManuallyDrop::new(pool),
+            textures: Vec::new(),
+        })
+    }
+
+    pub fn upload(
+        &mut self,
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        command_pool: &mut B::CommandPool,
+        command_queue: &mut B::CommandQueue,
+        img: RgbaImage,
+    ) -> Result<usize, &'static str> {
+        if self.textures.len() >= MAX_TEXTURES {
+            return Err("Texture limit reached!");
+        }
+        let image = LoadedImage::new(adapter, device, command_pool, command_queue, img)?;
+        self.push(device, image)
+    }
+
+    ///Cube texture, sampled with `samplerCube` through the same set layout
+    pub fn upload_cube(
+        &mut self,
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        command_pool: &mut B::CommandPool,
+        command_queue: &mut B::CommandQueue,
+        faces: &[RgbaImage; 6],
+    ) -> Result<usize, &'static str> {
+        if self.textures.len() >= MAX_TEXTURES {
+            return Err("Texture limit reached!");
+        }
+        let image = LoadedImage::cube(adapter, device, command_pool, command_queue, faces)?;
+        self.push(device, image)
+    }
+
+    fn push(&mut self, device: &B::Device, image: LoadedImage<B>) -> Result<usize, &'static str> {
+        let set = self.allocate_set()?;
+        Self::write_set(device, &set, &image.image_view, &image.sampler);
+        self.textures.push((Some(image), set));
+        info!("Texture uploaded: {:?}", self.textures.len() - 1);
+        Ok(self.textures.len() - 1)
+    }
+
+    ///Makes image owned by caller sampleable as a texture, it must stay alive while drawn
+    ///and be in `ShaderReadOnlyOptimal` layout whenever it's sampled
+    pub fn register(
+        &mut self,
+        device: &B::Device,
+        view: &B::ImageView,
+        sampler: &B::Sampler,
+    ) -> Result<usize, &'static str> {
+        if self.textures.len() >= MAX_TEXTURES {
+            return Err("Texture limit reached!");
+        }
+        let set = self.allocate_set()?;
+        Self::write_set(device, &set, view, sampler);
+        self.textures.push((None, set));
+        Ok(self.textures.len() - 1)
+    }
+
+    ///Points registered texture to another image, set must not be in use
+    pub fn rebind(&self, device: &B::Device, index: usize, view: &B::ImageView, sampler: &B::Sampler) {
+        Self::write_set(device, &self.textures[index].1, view, sampler);
+    }
+
+    fn allocate_set(&mut self) -> Result<B::DescriptorSet, &'static str> {
+        unsafe {
+            self.pool
+                .allocate_set(&self.layout)
+                .map_err(|_| "Couldn't make a Descriptor Set!")
+        }
+    }
+
+    fn write_set(device: &B::Device, set: &B::DescriptorSet, view: &B::ImageView, sampler: &B::Sampler) {
+        unsafe {
+            device.write_descriptor_sets(vec![
+                DescriptorSetWrite {
+                    set,
+                    binding: 0,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Image(view, Layout::ShaderReadOnlyOptimal)),
+                },
+                DescriptorSetWrite {
+                    set,
+                    binding: 1,
+                    array_offset: 0,
+                    descriptors: Some(Descriptor::Sampler(sampler)),
+                },
+            ]);
+        }
+    }
+
+    pub fn descriptor_set(&self, index: usize) -> &B::DescriptorSet {
+        &self.textures[index].1
+    }
+}
@@ -0,0 +1,86 @@
+use std::mem::ManuallyDrop;
+
+use hal::{
+    Backend,
+    command::CommandBuffer,
+    device::Device,
+    pso::PipelineStage,
+    query,
+};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::graphics::swapchain::DeviceDrop;
+
+///Timestamp pair per swapchain image around recorded frame
+pub struct GpuTimer<B: Backend> {
+    pool: ManuallyDrop<B::QueryPool>,
+    ///whether image's queries were ever written, unwritten ones can't be read
+    written: Vec<bool>,
+}
+
+impl<B: Backend> DeviceDrop<B> for GpuTimer<B> {
+    unsafe fn manually_drop(&mut self, device: &<B as Backend>::Device) {
+        use std::ptr::read;
+        device.destroy_query_pool(ManuallyDrop::into_inner(read(&mut self.pool)));
+    }
+}
+
+impl<B: Backend> GpuTimer<B> {
+    ///None if backend has no timestamp queries
+    pub fn new(device: &B::Device, img_count: usize) -> Option<Self> {
+        let pool = unsafe { device.create_query_pool(query::Type::Timestamp, (img_count * 2) as query::Id) };
+        match pool {
+            Ok(pool) => Some(Self {
+                pool: ManuallyDrop::new(pool),
+                written: vec![false; img_count],
+            }),
+            Err(e) => {
+                warn!("Timestamp queries unavailable: {:?}", e);
+                None
+            }
+        }
+    }
+
+    fn queries(frame: usize) -> std::ops::Range<query::Id> {
+        (frame * 2) as query::Id..(frame * 2 + 2) as query::Id
+    }
+
+    pub unsafe fn begin(&mut self, buffer: &mut B::CommandBuffer, frame: usize) {
+        buffer.reset_query_pool(&self.pool, Self::queries(frame));
+        buffer.write_timestamp(PipelineStage::TOP_OF_PIPE, query::Query {
+            pool: &self.pool,
+            id: Self::queries(frame).start,
+        });
+    }
+
+    pub unsafe fn end(&mut self, buffer: &mut B::CommandBuffer, frame: usize) {
+        buffer.write_timestamp(PipelineStage::BOTTOM_OF_PIPE, query::Query {
+            pool: &self.pool,
+            id: Self::queries(frame).start + 1,
+        });
+        self.written[frame] = true;
+    }
+
+    ///Ticks between timestamps of the last submission of `frame`, call after its fence is waited
+    pub fn read(&self, device: &B::Device, frame: usize) -> Option<u64> {
+        if !self.written[frame] {
+            return None;
+        }
+        let mut data = [0_u64; 2];
+        let ready = unsafe {
+            let bytes = std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, std::mem::size_of_val(&data));
+            device.get_query_pool_results(
+                &self.pool,
+                Self::queries(frame),
+                bytes,
+                std::mem::size_of::<u64>() as hal::buffer::Offset,
+                query::ResultFlags::BITS_64,
+            )
+        };
+        match ready {
+            Ok(true) => Some(data[1].saturating_sub(data[0])),
+            _ => None,
+        }
+    }
+}
@@ -1,12 +1,19 @@
-use hal::{Backend, device::Device};
+use hal::{Backend, device::Device, image::NumSamples};
+use image::RgbaImage;
 use winit::window::Window;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
-use crate::graphics::memory::MemoryManager;
-use crate::graphics::pipelines::PipelineV0;
+use crate::graphics::hal_utils::supported_samples;
+use crate::graphics::memory::{MAX_INSTANCES, MemoryManager};
+use crate::graphics::pipelines::{DebugPipeline, PipelineV0, PostPipeline, ShadowPipeline, SkyboxPipeline, UiPipeline};
+use crate::graphics::post::PostProcess;
+use crate::graphics::shadow::{ShadowConfig, ShadowMap};
 use crate::graphics::state::HalStateV2;
 use crate::graphics::swapchain::{CommonSwapchain, DeviceDrop};
+use crate::graphics::target::{RenderTargets, TargetPtr};
+use crate::graphics::texture::TextureStorage;
+use crate::graphics::timing::GpuTimer;
 use crate::hal::adapter::Adapter;
 use winit::dpi::PhysicalSize;
 
@@ -15,13 +22,61 @@ pub struct ApiWrapper<B: Backend> {
     pub(crate) swapchain: CommonSwapchain<B>,
     pub(crate) pipeline: PipelineV0<B>,
     pub(crate) storage: MemoryManager<B>,
+    pub(crate) textures: TextureStorage<B>,
+    pub(crate) targets: RenderTargets<B>,
+    pub(crate) shadow: ShadowMap<B>,
+    pub(crate) shadow_pipeline: ShadowPipeline<B>,
+    pub(crate) debug_pipeline: DebugPipeline<B>,
+    pub(crate) ui_pipeline: UiPipeline<B>,
+    pub(crate) text_pipeline: UiPipeline<B>,
+    pub(crate) skybox_pipeline: SkyboxPipeline<B>,
+    pub(crate) post: PostProcess<B>,
+    pub(crate) post_pipeline: PostPipeline<B>,
+    pub(crate) timer: Option<GpuTimer<B>>,
+}
+
+///Everything needed to record a frame
+pub struct Frame<'a, B: Backend> {
+    pub index: usize,
+    pub buffer: &'a mut B::CommandBuffer,
+    pub framebuffer: &'a B::Framebuffer,
+    pub render_pass: &'a B::RenderPass,
+    pub present_framebuffer: &'a B::Framebuffer,
+    pub present_pass: &'a B::RenderPass,
+    pub storage: &'a MemoryManager<B>,
+    pub pipeline: &'a PipelineV0<B>,
+    pub state: &'a HalStateV2<B>,
+    pub textures: &'a TextureStorage<B>,
+    pub targets: &'a RenderTargets<B>,
+    pub shadow: &'a ShadowMap<B>,
+    pub shadow_pipeline: &'a ShadowPipeline<B>,
+    pub debug_pipeline: &'a DebugPipeline<B>,
+    pub ui_pipeline: &'a UiPipeline<B>,
+    pub text_pipeline: &'a UiPipeline<B>,
+    pub skybox_pipeline: &'a SkyboxPipeline<B>,
+    pub post: &'a PostProcess<B>,
+    pub post_pipeline: &'a PostPipeline<B>,
+    pub timer: Option<&'a mut GpuTimer<B>>,
 }
 
 impl<B: Backend> Drop for ApiWrapper<B> {
     fn drop(&mut self) {
         let _ = self.hal_state.device_ref().wait_idle();
         unsafe {
+            if let Some(timer) = self.timer.as_mut() {
+                timer.manually_drop(&self.hal_state.device);
+            }
+            self.post_pipeline.manually_drop(&self.hal_state.device);
+            self.post.manually_drop(&self.hal_state.device);
+            self.skybox_pipeline.manually_drop(&self.hal_state.device);
+            self.text_pipeline.manually_drop(&self.hal_state.device);
+            self.ui_pipeline.manually_drop(&self.hal_state.device);
+            self.debug_pipeline.manually_drop(&self.hal_state.device);
+            self.shadow_pipeline.manually_drop(&self.hal_state.device);
             self.pipeline.manually_drop(&self.hal_state.device);
+            self.shadow.manually_drop(&self.hal_state.device);
+            self.targets.manually_drop(&self.hal_state.device);
+            self.textures.manually_drop(&self.hal_state.device);
             self.storage.manually_drop(&self.hal_state.device);
             self.swapchain.manually_drop(&self.hal_state.device);
         }
@@ -29,28 +84,161 @@ impl<B: Backend> Drop for ApiWrapper<B> {
 }
 
 impl<B: Backend> ApiWrapper<B> {
-    pub fn next_frame(
-        &mut self,
-    ) -> Result<
-        (
-            usize,
-            &mut B::CommandBuffer,
-            &B::Framebuffer,
-            &B::RenderPass,
-            &MemoryManager<B>,
-            &PipelineV0<B>,
-            &HalStateV2<B>
-        ),
-        &str,
-    > {
-        let (o, r, t, y) = self.swapchain.next_frame(&self.hal_state.device)?;
-        Ok((o, r, t, y, &self.storage, &self.pipeline, &self.hal_state))
+    pub fn next_frame(&mut self) -> Result<Frame<B>, &str> {
+        let (index, buffer, framebuffer, render_pass, present_framebuffer, present_pass) =
+            self.swapchain.next_frame(&self.hal_state.device)?;
+        Ok(Frame {
+            index,
+            buffer,
+            framebuffer,
+            render_pass,
+            present_framebuffer,
+            present_pass,
+            storage: &self.storage,
+            pipeline: &self.pipeline,
+            state: &self.hal_state,
+            textures: &self.textures,
+            targets: &self.targets,
+            shadow: &self.shadow,
+            shadow_pipeline: &self.shadow_pipeline,
+            debug_pipeline: &self.debug_pipeline,
+            ui_pipeline: &self.ui_pipeline,
+            text_pipeline: &self.text_pipeline,
+            skybox_pipeline: &self.skybox_pipeline,
+            post: &self.post,
+            post_pipeline: &self.post_pipeline,
+            timer: self.timer.as_mut(),
+        })
     }
     pub fn present_buffer(&mut self, present: usize) -> Result<(), &str> {
         self.swapchain.present_buffer(present)
     }
+    pub fn upload_texture(&mut self, img: RgbaImage) -> Result<usize, &'static str> {
+        let (command_pool, command_queue) = self.swapchain.transfer_parts();
+        self.textures.upload(
+            &self.hal_state._adapter,
+            &self.hal_state.device,
+            command_pool,
+            command_queue,
+            img,
+        )
+    }
+
+    pub fn upload_cube_texture(&mut self, faces: &[RgbaImage; 6]) -> Result<usize, &'static str> {
+        let (command_pool, command_queue) = self.swapchain.transfer_parts();
+        self.textures.upload_cube(
+            &self.hal_state._adapter,
+            &self.hal_state.device,
+            command_pool,
+            command_queue,
+            faces,
+        )
+    }
+
+    ///Offscreen scene target of `width` x `height`, its texture is valid right away
+    pub fn create_render_target(&mut self, width: u32, height: u32) -> Result<TargetPtr, &'static str> {
+        let target = self.targets.create(
+            &self.hal_state._adapter,
+            &self.hal_state.device,
+            self.swapchain.graph(),
+            self.swapchain.render_pass(),
+            self.swapchain.samples(),
+            hal::window::Extent2D { width, height },
+            &mut self.textures,
+        )?;
+        let (command_pool, command_queue) = self.swapchain.transfer_parts();
+        self.targets.prepare(&self.hal_state.device, command_pool, command_queue)?;
+        Ok(target)
+    }
+
+    ///Grows instance buffer to fit `count` instances per frame, returns resulting capacity
+    ///
+    ///Capacity is capped by `MAX_INSTANCES`, so it may still be lower than `count`
+    pub fn reserve_instances(&mut self, count: usize) -> Result<usize, &'static str> {
+        let capacity = self.storage.instances_per_frame();
+        if count <= capacity || capacity >= MAX_INSTANCES {
+            return Ok(capacity);
+        }
+        let per_frame = count.next_power_of_two().min(MAX_INSTANCES);
+        self.hal_state.device_ref().wait_idle().map_err(|_| "Couldn't wait for idle device!")?;
+        unsafe { self.storage.grow_instances(&self.hal_state.device, per_frame)?; }
+        info!("Instance buffer grown: {} -> {} per frame", capacity, per_frame);
+        Ok(per_frame)
+    }
+
     pub fn reset_swapchain(&mut self, size: PhysicalSize<u32>) -> Result<(), &str> {
-        self.swapchain.reset_inner(&mut self.hal_state, size)
+        self.swapchain.reset_inner(&mut self.hal_state, size)?;
+        self.resize_post()
+    }
+
+    ///Post targets follow swapchain extent and sample its HDR images
+    fn resize_post(&mut self) -> Result<(), &'static str> {
+        self.hal_state.device_ref().wait_idle().map_err(|_| "Couldn't wait for idle device!")?;
+        self.post.resize(
+            &self.hal_state._adapter,
+            &self.hal_state.device,
+            self.swapchain.current_extent(),
+            &self.swapchain.hdr_views(),
+        )
+    }
+
+    pub fn set_shadow_config(&mut self, config: ShadowConfig) -> Result<(), &'static str> {
+        self.hal_state.device_ref().wait_idle().map_err(|_| "Couldn't wait for idle device!")?;
+        self.shadow.reconfigure(&self.hal_state._adapter, &self.hal_state.device, config)
+    }
+
+    ///Requested count is clamped to what adapter supports, actual one is returned
+    pub fn set_msaa(&mut self, samples: NumSamples) -> Result<NumSamples, &'static str> {
+        let samples = supported_samples(&self.hal_state._adapter, samples);
+        if samples == self.swapchain.samples() {
+            return Ok(samples);
+        }
+        self.hal_state.device_ref().wait_idle().map_err(|_| "Couldn't wait for idle device!")?;
+        self.swapchain.set_samples(&mut self.hal_state, samples)?;
+        let pipeline = PipelineV0::new(
+            self.hal_state.device_ref(),
+            self.swapchain.current_extent(),
+            self.swapchain.render_pass(),
+            &self.storage,
+            &self.textures.layout,
+            &self.shadow.layout,
+            self.swapchain.img_count,
+            samples,
+            self.hal_state.features,
+        )?;
+        let debug_pipeline = DebugPipeline::new(
+            self.hal_state.device_ref(),
+            self.swapchain.render_pass(),
+            pipeline.frame_layout(),
+            samples,
+        )?;
+        let skybox_pipeline = SkyboxPipeline::new(
+            self.hal_state.device_ref(),
+            self.swapchain.render_pass(),
+            pipeline.frame_layout(),
+            &self.textures.layout,
+            samples,
+        )?;
+        unsafe {
+            self.skybox_pipeline.manually_drop(&self.hal_state.device);
+            self.debug_pipeline.manually_drop(&self.hal_state.device);
+            self.pipeline.manually_drop(&self.hal_state.device);
+        }
+        self.pipeline = pipeline;
+        self.debug_pipeline = debug_pipeline;
+        self.skybox_pipeline = skybox_pipeline;
+        self.targets.recreate(
+            &self.hal_state._adapter,
+            &self.hal_state.device,
+            self.swapchain.graph(),
+            self.swapchain.render_pass(),
+            samples,
+            &self.textures,
+        )?;
+        let (command_pool, command_queue) = self.swapchain.transfer_parts();
+        self.targets.prepare(&self.hal_state.device, command_pool, command_queue)?;
+        self.resize_post()?;
+        Ok(samples)
     }
 
     pub fn new(window: &Window, instance: Option<B::Instance>, surface: B::Surface, adapters: Vec<Adapter<B>>) -> Result<Self, &'static str> {
@@ -61,19 +249,86 @@ impl<B: Backend> ApiWrapper<B> {
         debug!("Swapchain done.");
         let storage = unsafe { MemoryManager::new(&hal_state, swapchain.img_count as u32) }?;
         debug!("Storage done.");
+        let textures = TextureStorage::new(hal_state.device_ref())?;
+        debug!("Textures done.");
+        let targets = RenderTargets::new(hal_state.device_ref())?;
+        let shadow = ShadowMap::new(&hal_state._adapter, hal_state.device_ref(), swapchain.graph(), ShadowConfig::default())?;
+        debug!("ShadowMap done.");
 
         let pipeline = PipelineV0::new(
             hal_state.device_ref(),
             swapchain.current_extent(),
             swapchain.render_pass(),
+            &storage,
+            &textures.layout,
+            &shadow.layout,
+            swapchain.img_count,
+            swapchain.samples(),
+            hal_state.features,
         )?;
         debug!("Pipeline done.");
+        let shadow_pipeline = ShadowPipeline::new(hal_state.device_ref(), &shadow.render_pass)?;
+        let debug_pipeline = DebugPipeline::new(
+            hal_state.device_ref(),
+            swapchain.render_pass(),
+            pipeline.frame_layout(),
+            swapchain.samples(),
+        )?;
+        //overlays are drawn after post-processing
+        let ui_pipeline = UiPipeline::new(
+            hal_state.device_ref(),
+            swapchain.present_pass(),
+            &textures.layout,
+            1,
+        )?;
+        let text_pipeline = UiPipeline::text(
+            hal_state.device_ref(),
+            swapchain.present_pass(),
+            &textures.layout,
+            1,
+        )?;
+        let skybox_pipeline = SkyboxPipeline::new(
+            hal_state.device_ref(),
+            swapchain.render_pass(),
+            pipeline.frame_layout(),
+            &textures.layout,
+            swapchain.samples(),
+        )?;
+        let post = PostProcess::new(
+            &hal_state._adapter,
+            hal_state.device_ref(),
+            swapchain.graph(),
+            swapchain.current_extent(),
+            &swapchain.hdr_views(),
+        )?;
+        let post_pipeline = PostPipeline::new(
+            hal_state.device_ref(),
+            post.render_pass(0),
+            swapchain.present_pass(),
+            &post.layout,
+        )?;
+        debug!("PostProcess done.");
+        let timer = GpuTimer::new(hal_state.device_ref(), swapchain.img_count);
 
-        Ok(Self {
+        let mut wrapper = Self {
             hal_state,
             swapchain,
             pipeline,
             storage,
-        })
+            textures,
+            targets,
+            shadow,
+            shadow_pipeline,
+            debug_pipeline,
+            ui_pipeline,
+            text_pipeline,
+            skybox_pipeline,
+            post,
+            post_pipeline,
+            timer,
+        };
+        //untextured materials sample it
+        wrapper.upload_texture(RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255])))?;
+        Ok(wrapper)
     }
 }
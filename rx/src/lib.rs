@@ -27,5 +27,12 @@ pub mod run;
 pub mod ecs;
 pub mod render;
 pub mod assets;
+pub mod culling;
+pub mod debug_draw;
 pub mod events;
+pub mod prefab;
+pub mod scene;
+pub mod skybox;
+pub mod text;
+pub mod ui;
 
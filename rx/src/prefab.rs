@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use specs::{Entity, World};
+
+use crate::scene::{Scene, SceneEntity};
+
+const MAX_DEPTH: usize = 8;
+
+///Named entity templates, kept in `World` for `spawn_prefab` and scene entities with `prefab`
+///
+///Written as a RON or JSON map of names to scene entities, a prefab may be based on another one.
+///Game components scenes don't know about are added by `PrefabHooks`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Prefabs(pub HashMap<String, SceneEntity>);
+
+impl Prefabs {
+    pub fn from_ron(text: &str) -> Result<Self, &'static str> {
+        ron::de::from_str(text).map_err(|e| {
+            error!("Prefabs parse error: {:?}", e);
+            "Failed to parse RON prefabs!"
+        })
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, &'static str> {
+        serde_json::from_str(text).map_err(|e| {
+            error!("Prefabs parse error: {:?}", e);
+            "Failed to parse JSON prefabs!"
+        })
+    }
+
+    ///Replaces prefab of the same name
+    pub fn add(&mut self, name: &str, prefab: SceneEntity) {
+        self.0.insert(name.to_owned(), prefab);
+    }
+
+    ///Adds all of `other`, its prefabs win on name clash
+    pub fn extend(&mut self, other: Prefabs) {
+        self.0.extend(other.0);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SceneEntity> {
+        self.0.get(name)
+    }
+
+    ///`entity` with its prefab applied underneath, down to the prefab without one
+    pub fn expand(&self, entity: &SceneEntity) -> Result<SceneEntity, &'static str> {
+        self.expand_chain(entity).map(|(entity, _)| entity)
+    }
+
+    ///Same, with names of applied prefabs from the base-most one
+    pub fn expand_chain(&self, entity: &SceneEntity) -> Result<(SceneEntity, Vec<String>), &'static str> {
+        let mut entity = entity.clone();
+        let mut chain = Vec::new();
+        for _ in 0..MAX_DEPTH {
+            let name = match entity.prefab.take() {
+                Some(name) => name,
+                None => {
+                    chain.reverse();
+                    return Ok((entity, chain));
+                }
+            };
+            let base = self.get(&name).ok_or_else(|| {
+                error!("Unknown prefab: {}", name);
+                "Unknown prefab!"
+            })?;
+            entity = entity.merged(base);
+            chain.push(name);
+        }
+        Err("Prefabs are nested too deep!")
+    }
+}
+
+type PrefabHook = Box<dyn Fn(Entity, &World) + Send + Sync>;
+
+///Code run for every spawned instance of a prefab, e.g. to insert game components, kept in `World`
+///
+///Hooks of base prefabs run first.
+#[derive(Default)]
+pub struct PrefabHooks(HashMap<String, Vec<PrefabHook>>);
+
+impl PrefabHooks {
+    pub fn add<F>(&mut self, name: &str, hook: F) where F: Fn(Entity, &World) + Send + Sync + 'static {
+        self.0.entry(name.to_owned()).or_insert_with(Vec::new).push(Box::new(hook));
+    }
+
+    ///Runs hooks of every prefab in `chain`
+    pub fn run(&self, chain: &[String], entity: Entity, world: &World) {
+        for hook in chain.iter().filter_map(|name| self.0.get(name)).flatten() {
+            hook(entity, world);
+        }
+    }
+}
+
+///Creates an entity from `Prefabs` entry `name`, fields set in `overrides` replace prefab ones
+pub fn spawn_prefab(world: &mut World, name: &str, overrides: SceneEntity) -> Result<Entity, &'static str> {
+    let scene = Scene {
+        entities: vec![SceneEntity {
+            prefab: Some(name.to_owned()),
+            ..overrides
+        }],
+        ..Default::default()
+    };
+    scene.spawn(world).map(|spawned| spawned[0])
+}
@@ -1,5 +1,9 @@
-use std::ops::Deref;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::{Deref, Range};
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
 
 use arrayvec::ArrayVec;
 use back;
@@ -7,33 +11,95 @@ use hal::{
     command,
     command::ClearValue,
     command::CommandBuffer,
-    pso::{Rect, Viewport},
+    pso::{ClearRect, Rect, ShaderStageFlags, Viewport},
 };
+use hal::adapter::PhysicalDevice;
 use hal::Instance;
 use itertools::Itertools;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use winit::dpi::PhysicalSize;
 
-use crate::assets::{AssetsLoader, AssetsStorage, MeshPtr};
+use crate::assets::{AssetsLoader, AssetsStorage, MeshPtr, TexturePtr};
+use crate::culling::{Aabb, Frustum};
+use crate::debug_draw::DebugVertex;
 use crate::glm::Mat4;
-use crate::graphics::wrapper::ApiWrapper;
+use crate::ecs::{Light, Material};
+use crate::graphics::draw::{ClearMode, DebugMode, FrameState, FrameUniform, MAX_LIGHTS, MAX_VIEWS, PassKind, PipelineKind, SortKey, View};
+use crate::graphics::memory::{BufBundle, DEBUG_MEMORY_SIZE, MemoryManager};
+use crate::graphics::pipelines::{INSTANCE_STRIDE, POST_CONSTANTS};
+use crate::graphics::post::PostEffect;
+use crate::graphics::shadow::{ShadowCascades, ShadowConfig};
+use crate::graphics::target::TargetPtr;
+use crate::skybox::SkyboxSource;
+use crate::graphics::wrapper::{ApiWrapper, Frame};
 use crate::hal::buffer::{IndexBufferView, SubRange};
 use crate::hal::IndexType;
+use crate::text::{FontAtlas, Text};
+use crate::ui::{UiFrame, UiVertex};
 use crate::utils::cast_slice;
 use crate::window::WinitState;
 
-pub type DrawCmd = (MeshPtr, glm::Mat4, glm::Mat4);
+///mesh, model matrix, material, render layers; views drawing it come from `PushCamera`
+pub type DrawCmd = (MeshPtr, glm::Mat4, Material, u32);
 
 
 
 pub enum RenderCommand {
-    PushView(glm::Mat4),
-    PushLight(glm::Vec3),
+    ///full-screen camera for the next committed state, same as `PushCamera(View::full(..))`
+    PushView {
+        view: glm::Mat4,
+        proj: glm::Mat4,
+        position: glm::Vec3,
+    },
+    ///appends camera to the next committed state, views are drawn in push order
+    PushCamera(View),
+    ///appends light to the next committed state
+    PushLight {
+        position: glm::Vec3,
+        light: Light,
+    },
+    ///commits pushed cameras and lights, they are used until the next commit
     PushState,
+    ///see `Renderer::set_debug_mode`
+    SetDebugMode(DebugMode),
+    ///line list replacing previous one, see `DebugDraw`
+    DebugLines(Vec<DebugVertex>),
+    ///overlay replacing previous one, see `Ui`
+    Ui(UiFrame),
+    ///strings replacing previous ones, see `Text`
+    Text(Vec<Text>),
     Draw,
 }
 
+///Instances of one mesh with one texture, laid out contiguously in instance buffer
+struct Batch {
+    mesh: MeshPtr,
+    texture: TexturePtr,
+    pass: PassKind,
+    instances: Range<u32>,
+}
+
+///Counters of the last recorded frame
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    ///all passes, including shadow cascades and debug lines
+    pub draw_calls: usize,
+    pub instances: usize,
+    ///all camera views, shadow cascades excluded
+    pub triangles: usize,
+    ///uniform, instance, debug line, ui and text data
+    pub bytes_uploaded: usize,
+    pub cpu_record_time: Duration,
+    ///since the previous frame started recording
+    pub frame_time: Duration,
+    ///last completed GPU frame, none without timestamp queries
+    pub gpu_time: Option<Duration>,
+}
+
+///Shared with ECS as a resource, updated after every frame
+pub type SharedRenderStats = Arc<Mutex<RenderStats>>;
+
 pub trait Pipeline {
     fn process(&mut self);
 }
@@ -49,6 +115,23 @@ pub struct Renderer {
 
     cmd_s: Sender<RenderCommand>,
     cmd_r: Receiver<RenderCommand>,
+    pending_state: FrameState,
+    ///first screen view's camera, used for shadow cascades and world anchored text
+    frame_state: FrameState,
+    pending_views: Vec<View>,
+    views: Vec<View>,
+    dropped_instances: usize,
+    debug_lines: Vec<DebugVertex>,
+    ui_frame: UiFrame,
+    texts: Vec<Text>,
+    font: Option<(FontAtlas, TexturePtr)>,
+    clear_color: [f32; 4],
+    skybox: Option<TexturePtr>,
+    post_effects: Vec<PostEffect>,
+    stats: SharedRenderStats,
+    last_frame: Option<Instant>,
+    ///nanoseconds per timestamp tick
+    timestamp_period: f32,
 
     pipelines: Vec<Box<dyn Pipeline>>
 }
@@ -56,6 +139,7 @@ pub struct Renderer {
 impl Renderer {
     pub fn new(window: &mut WinitState) -> Result<Self, &str> {
         let api = ApiWrapper::typed(window)?;
+        let timestamp_period = api.hal_state.adapter_ref().physical_device.limits().timestamp_period;
         debug!("Wrapper done.");
         #[cfg(not(target_arch = "wasm32"))]
             let loader = Some(AssetsLoader::new("assets")?);
@@ -77,6 +161,21 @@ impl Renderer {
             receiver: recv,
             cmd_s: r_send,
             cmd_r: r_recv,
+            pending_state: FrameState::default(),
+            frame_state: FrameState::default(),
+            pending_views: Vec::new(),
+            views: Vec::new(),
+            dropped_instances: 0,
+            debug_lines: Vec::new(),
+            ui_frame: UiFrame::default(),
+            texts: Vec::new(),
+            font: None,
+            clear_color: [0.5, 0.2, 0.3, 1.0],
+            skybox: None,
+            post_effects: PostEffect::default_chain(),
+            last_frame: None,
+            stats: SharedRenderStats::default(),
+            timestamp_period,
             pipelines: vec![]
         })
     }
@@ -85,6 +184,78 @@ impl Renderer {
         self.resize_flag = Some(size)
     }
 
+    pub fn set_ambient(&mut self, ambient: glm::Vec3) {
+        self.pending_state.ambient = ambient;
+        self.frame_state.ambient = ambient;
+    }
+
+    ///Instances that didn't fit into instance buffer last frame
+    pub fn dropped_instances(&self) -> usize {
+        self.dropped_instances
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        self.stats.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn stats_handle(&self) -> SharedRenderStats {
+        self.stats.clone()
+    }
+
+    ///Timestamp tick length in nanoseconds, adapter's `Limits::timestamp_period` by default
+    pub fn set_timestamp_period(&mut self, period: f32) {
+        self.timestamp_period = period;
+    }
+
+    ///Color of the main pass where nothing is drawn, hidden by skybox if one is set
+    pub fn set_clear_color(&mut self, color: glm::Vec4) {
+        self.clear_color = [color.x, color.y, color.z, color.w];
+    }
+
+    pub fn clear_color(&self) -> glm::Vec4 {
+        glm::vec4(self.clear_color[0], self.clear_color[1], self.clear_color[2], self.clear_color[3])
+    }
+
+    ///Draws sky behind opaque geometry, `None` goes back to clear color
+    pub fn set_skybox(&mut self, source: Option<SkyboxSource>) -> Result<(), &'static str> {
+        self.skybox = match source {
+            Some(source) => {
+                let faces = source.into_faces()?;
+                Some(self.storage.load_cube_texture(&mut self.api, &faces)?)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    ///Full-screen passes applied in order to HDR scene before overlays,
+    ///without tone mapping colors above 1 are clipped
+    pub fn set_post_effects(&mut self, effects: Vec<PostEffect>) {
+        self.post_effects = effects;
+    }
+
+    pub fn post_effects(&self) -> &[PostEffect] {
+        &self.post_effects
+    }
+
+    ///Rasterizes TTF font used by `Text`, replaces previous one
+    ///
+    ///`px` is atlas line size, larger text is upscaled from it.
+    pub fn load_font(&mut self, ttf: Vec<u8>, px: f32) -> Result<(), &'static str> {
+        let (atlas, image) = FontAtlas::new(ttf, px)?;
+        let texture = self.storage.load_texture(&mut self.api, image)?;
+        self.font = Some((atlas, texture));
+        Ok(())
+    }
+
+    ///Offscreen target for `View::target`, its `texture` can be put on any `Material`
+    ///
+    ///Screen views see what was drawn into it this frame. Views into the same target share
+    ///its render pass like screen ones, without them target keeps the last image.
+    pub fn create_render_target(&mut self, width: u32, height: u32) -> Result<TargetPtr, &'static str> {
+        self.api.create_render_target(width, height)
+    }
+
     pub fn queue(&self) -> (Sender<DrawCmd>, Sender<RenderCommand>) {
         (self.sender.clone(), self.cmd_s.clone())
     }
@@ -100,7 +271,50 @@ impl Renderer {
             }
         };
 
+        self.process_commands();
+
         let ex = self.api.swapchain.current_extent();
+        let shadow_config = self.api.shadow.config;
+        let cascades = ShadowCascades::compute(&self.frame_state, &shadow_config);
+        let views = if self.views.is_empty() {
+            vec![View::full(self.frame_state.view, self.frame_state.proj, self.frame_state.camera_position)]
+        } else {
+            self.views.clone()
+        };
+        let main_view = views.iter().position(|v| v.target.is_none()).unwrap_or(0);
+        let draws: Vec<DrawCmd> = self.receiver.try_iter().collect();
+        let bounds: Vec<Aabb> = draws.iter().map(|(mesh, model, ..)| mesh.bounds().transformed(model)).collect();
+        let culled = |frustum: Frustum, layers: u32, casters: bool| -> Vec<DrawCmd> {
+            draws.iter().zip(bounds.iter())
+                .filter(|((_, _, material, l), b)| {
+                    l & layers != 0 && !(casters && material.transparent) && frustum.intersects(b)
+                })
+                .map(|(draw, _)| draw.clone())
+                .collect()
+        };
+        let mut view_draws: Vec<Vec<DrawCmd>> = views.iter()
+            .map(|view| culled(Frustum::from_view_proj(&(view.proj * view.view)), view.layers, false))
+            .collect();
+        //casters come from light volume of each cascade, objects out of view still shadow what's in it
+        let mut caster_draws: Vec<Vec<DrawCmd>> = cascades.iter()
+            .flat_map(|c| c.view_proj.iter())
+            .map(|vp| culled(Frustum::from_view_proj_zo(vp), views[main_view].layers, true))
+            .collect();
+        let total: usize = view_draws.iter().chain(caster_draws.iter()).map(|d| d.len()).sum();
+        let capacity = self.api.reserve_instances(total).unwrap_or_else(|e| {
+            error!("{:?}", e);
+            self.api.storage.instances_per_frame()
+        });
+        self.dropped_instances = total.saturating_sub(capacity);
+        if self.dropped_instances > 0 {
+            warn!("Instance budget exceeded: {} submitted, {} dropped", total, self.dropped_instances);
+            //later views lose their instances first, then shadow casters
+            let mut left = capacity;
+            for draws in view_draws.iter_mut().chain(caster_draws.iter_mut()) {
+                draws.truncate(left);
+                left -= draws.len();
+            }
+        }
         let next_frame = self.api.next_frame();
         match next_frame {
             Ok(fr) => {
@@ -109,21 +323,42 @@ impl Renderer {
                 }
 
 
-                let (
-                    frame,
+                let Frame {
+                    index: frame,
                     buffer,
-                    fb,
+                    framebuffer: fb,
                     render_pass,
+                    present_framebuffer: present_fb,
+                    present_pass,
                     storage,
                     pipeline,
                     state,
-                ) = fr;
+                    textures,
+                    targets,
+                    shadow,
+                    shadow_pipeline,
+                    debug_pipeline,
+                    ui_pipeline,
+                    text_pipeline,
+                    skybox_pipeline,
+                    post,
+                    post_pipeline,
+                    mut timer,
+                } = fr;
+                let mut stats = RenderStats::default();
+                let period = self.timestamp_period as f64;
+                stats.gpu_time = timer.as_ref()
+                    .and_then(|t| t.read(&state.device, frame))
+                    .map(|ticks| Duration::from_nanos((ticks as f64 * period) as u64));
+                let record_start = Instant::now();
+                stats.frame_time = self.last_frame.map_or(Duration::default(), |last| record_start - last);
+                self.last_frame = Some(record_start);
                 //lmao dude move this outta my eyes
                 unsafe {
-                    const CLEAR: [ClearValue; 2] = [
+                    let clear: [ClearValue; 2] = [
                         command::ClearValue {
                             color: command::ClearColor {
-                                float32: [0.5, 0.2, 0.3, 1.0],
+                                float32: self.clear_color,
                             },
                         },
                         command::ClearValue {
@@ -146,119 +381,454 @@ impl Renderer {
                     };
 
                     buffer.begin_primary(command::CommandBufferFlags::empty());
+                    if let Some(timer) = timer.as_mut() {
+                        timer.begin(buffer, frame);
+                    }
                     let instanced_offset = storage.instanced_offset(frame);
-                    buffer.set_viewports(0, &[viewport]);
-                    buffer.set_scissors(0, &[render_area]);
-
 
+                    let opaque_kind = match (self.frame_state.debug_mode, &pipeline.wireframe_pipeline) {
+                        (DebugMode::Wireframe, Some(_)) => PipelineKind::Wireframe,
+                        _ => PipelineKind::Opaque,
+                    };
+                    let mut view_batches = Vec::with_capacity(views.len());
+                    let mut first_instance = 0;
+                    for (i, (view, draws)) in views.iter().zip(view_draws.into_iter()).enumerate() {
+                        let view_state = self.frame_state.for_view(view);
+                        let uniform_offset = storage.view_uniform_offset(frame, i);
+                        let uniform_range = uniform_offset.start as u64..uniform_offset.end as u64;
+                        //cascades cover only the view they were fitted to
+                        let uniform = view_state.as_uniform(cascades.as_ref().filter(|_| i == main_view), &shadow_config);
+                        let uniform_ptr = storage.uniform_bundle
+                            .map_mem_range(&state.device, uniform_range.clone())
+                            .expect("");
+                        std::ptr::copy(
+                            &uniform as *const FrameUniform as *const u8,
+                            uniform_ptr,
+                            size_of::<FrameUniform>(),
+                        );
+                        storage.uniform_bundle.flush_mem_range(&state.device, uniform_range);
+                        storage.uniform_bundle.unmap(&state.device);
 
-                    buffer.bind_graphics_pipeline(&pipeline.graphics_pipeline);
-
+                        let start = instanced_offset.start as u64 + (first_instance as usize * INSTANCE_STRIDE) as u64;
+                        let batches = Self::upload_instances(
+                            draws.into_iter(),
+                            &view_state,
+                            opaque_kind,
+                            storage,
+                            &state.device,
+                            first_instance,
+                            start..instanced_offset.end as u64,
+                        );
+                        first_instance += batches.iter().map(|b| b.instances.len() as u32).sum::<u32>();
+                        view_batches.push(batches);
+                    }
+                    let mut caster_batches = Vec::with_capacity(caster_draws.len());
+                    for draws in caster_draws.into_iter() {
+                        let start = instanced_offset.start as u64 + (first_instance as usize * INSTANCE_STRIDE) as u64;
+                        let batches = Self::upload_instances(
+                            draws.into_iter(),
+                            &self.frame_state,
+                            PipelineKind::Shadow,
+                            storage,
+                            &state.device,
+                            first_instance,
+                            start..instanced_offset.end as u64,
+                        );
+                        first_instance += batches.iter().map(|b| b.instances.len() as u32).sum::<u32>();
+                        caster_batches.push(batches);
+                    }
+                    stats.instances = first_instance as usize;
+                    stats.bytes_uploaded = size_of::<FrameUniform>() * views.len() + stats.instances * INSTANCE_STRIDE;
 
-                    let buffers: ArrayVec<[_; 2]> = [
-                        (storage.mesh_bundle.buffer.deref(), SubRange {
-                            offset: 0,
-                            size: None
-                        }),
-                        (storage.instanced_bundle.buffer.deref(), SubRange {
-                            offset: instanced_offset.start as u64,
-                            size: None
-                        })
-                    ].into();
-                    buffer.bind_vertex_buffers(0, buffers);
+                    let mesh_buffers = || -> ArrayVec<[_; 2]> {
+                        [
+                            (storage.mesh_bundle.buffer.deref(), SubRange {
+                                offset: 0,
+                                size: None
+                            }),
+                            (storage.instanced_bundle.buffer.deref(), SubRange {
+                                offset: instanced_offset.start as u64,
+                                size: None
+                            })
+                        ].into()
+                    };
+                    buffer.bind_vertex_buffers(0, mesh_buffers());
                     buffer.bind_index_buffer(IndexBufferView {
                         buffer: &storage.idx_bundle.buffer,
                         range: SubRange::default(),
                         index_type: IndexType::U32,
                     });
 
-//                buffer.bind_graphics_descriptor_sets(
-//                    &pipeline.pipeline_layout,
-//                    0,
-//                    Some(pipeline.descriptor_set.deref()),
-//                    &[],
-//                );
-
-                    for cmd in self.cmd_r.try_iter() {
-                        match cmd {
-                            RenderCommand::PushView(mtx) => {
-                                use hal::pso::ShaderStageFlags;
-//                                buffer.push_graphics_constants(
-//                                    &pipeline.pipeline_layout,
-//                                    ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
-//                                    0,
-//                                    cast_slice::<f32, u32>(&mtx.as_slice())
-//                                        .expect("this cast never fails for same-aligned same-size data"),
-//                                );
-                            },
-                            _ => ()
+                    //shadow passes always run to keep shadow map in sampled layout,
+                    //casters are opaque draws in cascade's light volume
+                    let shadow_extent = shadow.extent();
+                    let shadow_area = Rect {
+                        x: 0,
+                        y: 0,
+                        w: shadow_extent.width as i16,
+                        h: shadow_extent.height as i16,
+                    };
+                    buffer.set_viewports(0, &[Viewport {
+                        rect: shadow_area,
+                        depth: (0.0..1.0),
+                    }]);
+                    buffer.set_scissors(0, &[shadow_area]);
+                    buffer.bind_graphics_pipeline(&shadow_pipeline.graphics_pipeline);
+                    for (cascade, shadow_fb) in shadow.framebuffers.iter().enumerate() {
+                        buffer.begin_render_pass(
+                            &shadow.render_pass,
+                            shadow_fb,
+                            shadow_area,
+                            clear[1..].iter(),
+                            command::SubpassContents::Inline,
+                        );
+                        if let Some(light_vp) = cascades.as_ref().and_then(|c| c.view_proj.get(cascade)) {
+                            buffer.push_graphics_constants(
+                                &shadow_pipeline.pipeline_layout,
+                                ShaderStageFlags::VERTEX,
+                                0,
+                                cast_slice::<f32, u32>(light_vp.as_slice())
+                                    .expect("this cast never fails for same-aligned same-size data"),
+                            );
+                            for batch in caster_batches.get(cascade).into_iter().flatten() {
+                                buffer.draw_indexed(
+                                    batch.mesh.indices.clone(),
+                                    batch.mesh.base_vertex,
+                                    batch.instances.clone(),
+                                );
+                                stats.draw_calls += 1;
+                            }
                         }
+                        buffer.end_render_pass();
                     }
 
-                    buffer.begin_render_pass(
-                        &render_pass,
-                        &fb,
-                        render_area,
-                        CLEAR.iter(),
-                        command::SubpassContents::Inline,
+                    let debug_offset = storage.debug_offset(frame);
+                    let debug_count = Self::upload_debug_lines(
+                        &self.debug_lines,
+                        storage,
+                        &state.device,
+                        debug_offset.start as u64..debug_offset.end as u64,
                     );
+                    stats.bytes_uploaded += debug_count as usize * size_of::<DebugVertex>();
+
+                    buffer.set_viewports(0, &[viewport]);
+                    buffer.set_scissors(0, &[render_area]);
+                    buffer.set_line_width(1.0);
+                    let opaque_pipeline = match (self.frame_state.debug_mode, &pipeline.wireframe_pipeline) {
+                        (DebugMode::Wireframe, Some(wireframe)) => wireframe,
+                        _ => &pipeline.graphics_pipeline,
+                    };
 
+                    //views into offscreen targets go first, so screen views sample this frame's image,
+                    //a target sampled by a view drawn before it shows previous frame
+                    let mut passes: Vec<(Option<TargetPtr>, Vec<usize>)> = Vec::new();
+                    for (v, view) in views.iter().enumerate().filter(|(_, view)| view.target.is_some()) {
+                        match passes.iter_mut().find(|(target, _)| *target == view.target) {
+                            Some((_, pass_views)) => pass_views.push(v),
+                            None => passes.push((view.target, vec![v])),
+                        }
+                    }
+                    passes.push((None, (0..views.len()).filter(|&v| views[v].target.is_none()).collect()));
 
-                    let instanced_ptr = storage.instanced_bundle.map_mem_range(
-                        &state.device,
-                        instanced_offset.start as u64..instanced_offset.end as u64,
-                    ).expect("");
-
-                    let mut instances_offset: u32 = 0;
-                    let mut data_offset = 0;
-                    let grouped_queue = self.receiver
-                        .try_iter()
-                        .into_iter()
-                        .sorted_by(|(l_ptr, ..), (r_ptr, ..)| {
-                            l_ptr.base_vertex.partial_cmp(&r_ptr.base_vertex).unwrap()
-                        })
-                        .group_by(|ptr| ptr.0.clone());
-
-                    for (ptr, list) in &grouped_queue {
-
-                        let mut current_count = 0;
-
-                        let data: Vec<_> = list.flat_map(|(_, mvp, model)| {
-                            current_count += 1;
-                            let mut base = mvp.as_slice().to_owned();
-                            base.append(&mut model.as_slice().to_owned());
-                            base
-                        }).collect::<Vec<f32>>();
-
-                        let data_len = data.len() * 4;
-
-                        use std::ptr;
-                        ptr::copy(
-                            data.as_slice().as_ptr() as *const u8,
-                            instanced_ptr.offset(data_offset),
-                            data_len,
+                    for (target, pass_views) in passes.iter() {
+                        let (pass_fb, pass_extent) = match target {
+                            Some(ptr) => match targets.get(ptr) {
+                                Some(t) => (t.framebuffer.deref(), (t.extent.width, t.extent.height)),
+                                None => continue,
+                            },
+                            None => (fb, (ex.width, ex.height)),
+                        };
+                        let pass_area = Rect {
+                            x: 0,
+                            y: 0,
+                            w: pass_extent.0 as i16,
+                            h: pass_extent.1 as i16,
+                        };
+                        //target can't be sampled while it's drawn into
+                        let own_texture = target.map(|t| t.texture);
+                        buffer.begin_render_pass(
+                            &render_pass,
+                            pass_fb,
+                            pass_area,
+                            clear.iter(),
+                            command::SubpassContents::Inline,
                         );
 
-                        data_offset += data_len as isize;
+                        for (n, &v) in pass_views.iter().enumerate() {
+                            let (view, batches) = (&views[v], &view_batches[v]);
+                            let view_area = view.viewport.to_rect(pass_extent);
+                            if view_area.w <= 0 || view_area.h <= 0 {
+                                continue;
+                            }
+                            buffer.set_viewports(0, &[Viewport {
+                                rect: view_area,
+                                depth: (-1.0..1.0),
+                            }]);
+                            buffer.set_scissors(0, &[view_area]);
+                            //whole target is cleared by render pass, later views clear their own area
+                            let color = match view.clear {
+                                ClearMode::Background if n > 0 => Some(self.clear_color),
+                                ClearMode::Color(c) => Some([c.x, c.y, c.z, c.w]),
+                                _ => None,
+                            };
+                            let depth = match view.clear {
+                                ClearMode::None => false,
+                                _ => n > 0 || color.is_some(),
+                            };
+                            let mut clears: ArrayVec<[_; 2]> = ArrayVec::new();
+                            if let Some(color) = color {
+                                clears.push(command::AttachmentClear::Color {
+                                    index: 0,
+                                    value: command::ClearColor { float32: color },
+                                });
+                            }
+                            if depth {
+                                clears.push(command::AttachmentClear::DepthStencil {
+                                    depth: Some(1.0),
+                                    stencil: None,
+                                });
+                            }
+                            if !clears.is_empty() {
+                                buffer.clear_attachments(clears, &[ClearRect {
+                                    rect: view_area,
+                                    layers: 0..1,
+                                }]);
+                            }
+
+                            buffer.bind_vertex_buffers(0, mesh_buffers());
+                            buffer.bind_graphics_pipeline(opaque_pipeline);
+                            buffer.bind_graphics_descriptor_sets(
+                                &pipeline.pipeline_layout,
+                                0,
+                                Some(pipeline.frame_set(frame, v)),
+                                &[],
+                            );
+                            buffer.bind_graphics_descriptor_sets(
+                                &pipeline.pipeline_layout,
+                                2,
+                                Some(shadow.descriptor_set.deref()),
+                                &[],
+                            );
 
+                            //batches are sorted by pass, opaque ones go first
+                            let mut bound_texture = None;
+                            let mut bound_pass = PassKind::Opaque;
+                            //sky goes after opaque geometry to skip covered pixels, but before blended one
+                            let sky = match (self.frame_state.debug_mode, view.clear) {
+                                (DebugMode::Filled, ClearMode::Background) | (DebugMode::Wireframe, ClearMode::Background) => self.skybox,
+                                _ => None,
+                            };
+                            let sky_at = batches.iter()
+                                .position(|b| b.pass == PassKind::Transparent)
+                                .unwrap_or(batches.len());
+                            for i in 0..=batches.len() {
+                                if let Some(sky) = sky.filter(|_| i == sky_at) {
+                                    buffer.bind_graphics_pipeline(&skybox_pipeline.graphics_pipeline);
+                                    buffer.bind_graphics_descriptor_sets(
+                                        &skybox_pipeline.pipeline_layout,
+                                        0,
+                                        vec![pipeline.frame_set(frame, v), textures.descriptor_set(sky.0)],
+                                        &[],
+                                    );
+                                    buffer.draw(0..3, 0..1);
+                                    stats.draw_calls += 1;
+                                    //sets bound with another layout, restore them for instanced pipelines
+                                    buffer.bind_graphics_pipeline(opaque_pipeline);
+                                    buffer.bind_graphics_descriptor_sets(
+                                        &pipeline.pipeline_layout,
+                                        0,
+                                        Some(pipeline.frame_set(frame, v)),
+                                        &[],
+                                    );
+                                    buffer.bind_graphics_descriptor_sets(
+                                        &pipeline.pipeline_layout,
+                                        2,
+                                        Some(shadow.descriptor_set.deref()),
+                                        &[],
+                                    );
+                                    bound_texture = None;
+                                }
+                                let batch = match batches.get(i) {
+                                    Some(batch) => batch,
+                                    None => break,
+                                };
+                                if own_texture == Some(batch.texture) {
+                                    continue;
+                                }
+                                if bound_pass != batch.pass {
+                                    buffer.bind_graphics_pipeline(&pipeline.transparent_pipeline);
+                                    bound_pass = batch.pass;
+                                }
+                                if bound_texture != Some(batch.texture) {
+                                    buffer.bind_graphics_descriptor_sets(
+                                        &pipeline.pipeline_layout,
+                                        1,
+                                        Some(textures.descriptor_set(batch.texture.0)),
+                                        &[],
+                                    );
+                                    bound_texture = Some(batch.texture);
+                                }
+                                buffer.draw_indexed(
+                                    batch.mesh.indices.clone(),
+                                    batch.mesh.base_vertex,
+                                    batch.instances.clone(),
+                                );
+                                stats.draw_calls += 1;
+                                stats.triangles += batch.mesh.indices.len() / 3 * batch.instances.len();
+                            }
 
-                        buffer.draw_indexed(
-                            ptr.indices.clone(),
-                            ptr.base_vertex,
-                            instances_offset..instances_offset + current_count,
+                            if debug_count > 0 {
+                                buffer.bind_graphics_pipeline(&debug_pipeline.graphics_pipeline);
+                                buffer.bind_vertex_buffers(0, Some((storage.debug_bundle.buffer.deref(), SubRange {
+                                    offset: debug_offset.start as u64,
+                                    size: None
+                                })));
+                                buffer.draw(0..debug_count, 0..1);
+                                stats.draw_calls += 1;
+                            }
+                        }
+                        buffer.end_render_pass();
+                    }
+                    buffer.set_viewports(0, &[viewport]);
+                    buffer.set_scissors(0, &[render_area]);
+
+                    //each effect reads previous result, targets alternate
+                    let texel = [1. / ex.width as f32, 1. / ex.height as f32];
+                    let mut source = 0;
+                    for (i, effect) in self.post_effects.iter().enumerate() {
+                        let target = i % 2;
+                        buffer.begin_render_pass(
+                            post.render_pass(i),
+                            post.framebuffer(frame, target),
+                            render_area,
+                            std::iter::empty::<ClearValue>(),
+                            command::SubpassContents::Inline,
                         );
-                        instances_offset += current_count
-                    };
+                        buffer.bind_graphics_pipeline(post_pipeline.pipeline(effect.shader));
+                        buffer.bind_graphics_descriptor_sets(
+                            &post_pipeline.pipeline_layout,
+                            0,
+                            Some(post.input_set(frame, source)),
+                            &[],
+                        );
+                        let constants: [f32; POST_CONSTANTS] = [
+                            effect.params[0], effect.params[1], effect.params[2], effect.params[3],
+                            texel[0], texel[1], 0., 0.,
+                        ];
+                        buffer.push_graphics_constants(
+                            &post_pipeline.pipeline_layout,
+                            ShaderStageFlags::FRAGMENT,
+                            0,
+                            cast_slice::<f32, u32>(&constants)
+                                .expect("this cast never fails for same-aligned same-size data"),
+                        );
+                        buffer.draw(0..3, 0..1);
+                        buffer.end_render_pass();
+                        stats.draw_calls += 1;
+                        source = target + 1;
+                    }
+
+                    buffer.begin_render_pass(
+                        &present_pass,
+                        &present_fb,
+                        render_area,
+                        std::iter::empty::<ClearValue>(),
+                        command::SubpassContents::Inline,
+                    );
+                    buffer.bind_graphics_pipeline(&post_pipeline.present);
+                    buffer.bind_graphics_descriptor_sets(
+                        &post_pipeline.pipeline_layout,
+                        0,
+                        Some(post.input_set(frame, source)),
+                        &[],
+                    );
+                    buffer.draw(0..3, 0..1);
+                    stats.draw_calls += 1;
 
-                    storage.instanced_bundle.flush_mem_range(
+                    let ui_offset = storage.ui_offset(frame);
+                    let ui_count = Self::upload_overlay(
+                        &self.ui_frame.vertices,
+                        &storage.ui_bundle,
                         &state.device,
-                        instanced_offset.start as u64..instanced_offset.end as u64,
+                        ui_offset.start as u64..ui_offset.end as u64,
                     );
-                    storage.instanced_bundle.unmap(&state.device);
-                    //
+                    if ui_count > 0 {
+                        buffer.bind_graphics_pipeline(&ui_pipeline.graphics_pipeline);
+                        buffer.bind_vertex_buffers(0, Some((storage.ui_bundle.buffer.deref(), SubRange {
+                            offset: ui_offset.start as u64,
+                            size: None
+                        })));
+                        buffer.push_graphics_constants(
+                            &ui_pipeline.pipeline_layout,
+                            ShaderStageFlags::VERTEX,
+                            0,
+                            cast_slice::<f32, u32>(&[ex.width as f32, ex.height as f32])
+                                .expect("this cast never fails for same-aligned same-size data"),
+                        );
+                        for batch in self.ui_frame.batches.iter().filter(|b| b.vertices.start < ui_count) {
+                            buffer.bind_graphics_descriptor_sets(
+                                &ui_pipeline.pipeline_layout,
+                                0,
+                                Some(textures.descriptor_set(batch.texture.0)),
+                                &[],
+                            );
+                            buffer.draw(batch.vertices.start..batch.vertices.end.min(ui_count), 0..1);
+                            stats.draw_calls += 1;
+                        }
+                        stats.bytes_uploaded += ui_count as usize * size_of::<UiVertex>();
+                    }
+
+                    if let Some((atlas, atlas_texture)) = self.font.as_ref() {
+                        let screen = (ex.width as f32, ex.height as f32);
+                        let view_proj = self.frame_state.proj * self.frame_state.view;
+                        let mut text_vertices = Vec::new();
+                        for text in self.texts.iter() {
+                            if let Some(origin) = FontAtlas::origin(text, &view_proj, screen) {
+                                atlas.layout(text, origin, &mut text_vertices);
+                            }
+                        }
+                        let text_offset = storage.text_offset(frame);
+                        let text_count = Self::upload_overlay(
+                            &text_vertices,
+                            &storage.text_bundle,
+                            &state.device,
+                            text_offset.start as u64..text_offset.end as u64,
+                        );
+                        if text_count > 0 {
+                            buffer.bind_graphics_pipeline(&text_pipeline.graphics_pipeline);
+                            buffer.bind_vertex_buffers(0, Some((storage.text_bundle.buffer.deref(), SubRange {
+                                offset: text_offset.start as u64,
+                                size: None
+                            })));
+                            buffer.push_graphics_constants(
+                                &text_pipeline.pipeline_layout,
+                                ShaderStageFlags::VERTEX,
+                                0,
+                                cast_slice::<f32, u32>(&[screen.0, screen.1])
+                                    .expect("this cast never fails for same-aligned same-size data"),
+                            );
+                            buffer.bind_graphics_descriptor_sets(
+                                &text_pipeline.pipeline_layout,
+                                0,
+                                Some(textures.descriptor_set(atlas_texture.0)),
+                                &[],
+                            );
+                            buffer.draw(0..text_count, 0..1);
+                            stats.draw_calls += 1;
+                            stats.bytes_uploaded += text_count as usize * size_of::<UiVertex>();
+                        }
+                    }
+
                     buffer.end_render_pass();
+                    if let Some(timer) = timer.as_mut() {
+                        timer.end(buffer, frame);
+                    }
                     buffer.finish();
                 }
+                stats.cpu_record_time = record_start.elapsed();
+                if let Ok(mut shared) = self.stats.lock() {
+                    *shared = stats;
+                }
                 self.api.present_buffer(frame).expect("");
             }
             Err(e) => {
@@ -267,6 +837,222 @@ impl Renderer {
             }
         };
     }
+
+    ///Writes instance data ordered by [`SortKey`] into `range` of this frame's instances,
+    ///starting at `first_instance`, consecutive draws with the same mesh and texture become one batch
+    ///
+    ///[`SortKey`]: ../graphics/draw/struct.SortKey.html
+    unsafe fn upload_instances<I>(
+        queue: I,
+        frame_state: &FrameState,
+        opaque: PipelineKind,
+        storage: &MemoryManager<back::Backend>,
+        device: &<back::Backend as hal::Backend>::Device,
+        first_instance: u32,
+        range: Range<u64>,
+    ) -> Vec<Batch> where I: Iterator<Item=DrawCmd> {
+        let instanced_ptr = storage.instanced_bundle.map_mem_range(device, range.clone())
+            .expect("");
+
+        let mut batches = Vec::new();
+        let mut instances_offset = first_instance;
+        let mut data_offset = 0;
+        let capacity = (range.end - range.start) as isize;
+        let queue: Vec<DrawCmd> = queue.collect();
+        //compact mesh ids, keys are only compared within one upload
+        let mesh_ids: HashMap<(i32, u32), u32> = queue.iter()
+            .map(|(ptr, ..)| (ptr.base_vertex, ptr.indices.start))
+            .sorted()
+            .dedup()
+            .enumerate()
+            .map(|(id, mesh)| (mesh, id as u32))
+            .collect();
+        let grouped_queue = queue.into_iter()
+            .map(|cmd| {
+                let (ptr, model, material, _) = &cmd;
+                let origin = glm::vec3(model[(0, 3)], model[(1, 3)], model[(2, 3)]);
+                let (pass, pipeline) = if material.transparent {
+                    (PassKind::Transparent, PipelineKind::Transparent)
+                } else {
+                    (PassKind::Opaque, opaque)
+                };
+                let key = SortKey::new(
+                    pass,
+                    pipeline,
+                    material.texture.unwrap_or_default().0 as u32,
+                    mesh_ids[&(ptr.base_vertex, ptr.indices.start)],
+                    frame_state.sort_depth(&origin),
+                );
+                (key, cmd)
+            })
+            .sorted_by_key(|(key, _)| *key)
+            .group_by(|(key, (ptr, _, material, _))| {
+                (key.pass(), ptr.clone(), material.texture.unwrap_or_default())
+            });
+
+        for ((pass, mesh, texture), list) in &grouped_queue {
+            let mut current_count = 0;
+
+            let data: Vec<_> = list.flat_map(|(_, (_, model, material, _))| {
+                current_count += 1;
+                let mut base = model.as_slice().to_owned();
+                base.extend_from_slice(material.base_color.as_slice());
+                base.extend_from_slice(&[material.shininess, 0., 0., 0.]);
+                base
+            }).collect::<Vec<f32>>();
+
+            let data_len = data.len() * 4;
+            if data_offset + data_len as isize > capacity {
+                error!("Instance data doesn't fit into frame range, batch skipped");
+                continue;
+            }
+
+            use std::ptr;
+            ptr::copy(
+                data.as_slice().as_ptr() as *const u8,
+                instanced_ptr.offset(data_offset),
+                data_len,
+            );
+
+            data_offset += data_len as isize;
+
+            batches.push(Batch {
+                mesh,
+                texture,
+                pass,
+                instances: instances_offset..instances_offset + current_count,
+            });
+            instances_offset += current_count
+        };
+
+        storage.instanced_bundle.flush_mem_range(device, range);
+        storage.instanced_bundle.unmap(device);
+        batches
+    }
+
+    ///Copies debug vertices into this frame's range, returns how many fit
+    unsafe fn upload_debug_lines(
+        lines: &[DebugVertex],
+        storage: &MemoryManager<back::Backend>,
+        device: &<back::Backend as hal::Backend>::Device,
+        range: Range<u64>,
+    ) -> u32 {
+        //whole lines only
+        let max = DEBUG_MEMORY_SIZE / size_of::<DebugVertex>() / 2 * 2;
+        if lines.len() > max {
+            warn!("{} debug vertices submitted, only first {} are drawn", lines.len(), max);
+        }
+        let count = lines.len().min(max);
+        if count == 0 {
+            return 0;
+        }
+        let debug_ptr = storage.debug_bundle.map_mem_range(device, range.clone())
+            .expect("");
+        std::ptr::copy(
+            lines.as_ptr() as *const u8,
+            debug_ptr,
+            count * size_of::<DebugVertex>(),
+        );
+        storage.debug_bundle.flush_mem_range(device, range);
+        storage.debug_bundle.unmap(device);
+        count as u32
+    }
+
+    ///Copies ui or text vertices into this frame's range of `bundle`, returns how many fit
+    unsafe fn upload_overlay(
+        vertices: &[UiVertex],
+        bundle: &BufBundle<back::Backend>,
+        device: &<back::Backend as hal::Backend>::Device,
+        range: Range<u64>,
+    ) -> u32 {
+        //whole triangles only
+        let max = (range.end - range.start) as usize / size_of::<UiVertex>() / 3 * 3;
+        if vertices.len() > max {
+            warn!("{} overlay vertices submitted, only first {} are drawn", vertices.len(), max);
+        }
+        let count = vertices.len().min(max);
+        if count == 0 {
+            return 0;
+        }
+        let overlay_ptr = bundle.map_mem_range(device, range.clone())
+            .expect("");
+        std::ptr::copy(
+            vertices.as_ptr() as *const u8,
+            overlay_ptr,
+            count * size_of::<UiVertex>(),
+        );
+        bundle.flush_mem_range(device, range);
+        bundle.unmap(device);
+        count as u32
+    }
+
+    pub fn set_shadow_config(&mut self, config: ShadowConfig) -> Result<(), &'static str> {
+        self.api.set_shadow_config(config)
+    }
+
+    ///Sets MSAA sample count (1 - off, 2, 4, 8), returns the one adapter allows
+    pub fn set_msaa(&mut self, samples: u8) -> Result<u8, &'static str> {
+        self.api.set_msaa(samples)
+    }
+
+    ///Switches main pass output, wireframe fails if device can't draw lines as polygons
+    pub fn set_debug_mode(&mut self, mode: DebugMode) -> Result<(), &'static str> {
+        if mode == DebugMode::Wireframe && self.api.pipeline.wireframe_pipeline.is_none() {
+            return Err("Wireframe mode isn't supported by the device!");
+        }
+        info!("Debug mode: {:?}", mode);
+        self.pending_state.debug_mode = mode;
+        self.frame_state.debug_mode = mode;
+        Ok(())
+    }
+
+    pub fn debug_mode(&self) -> DebugMode {
+        self.frame_state.debug_mode
+    }
+
+    fn process_commands(&mut self) {
+        while let Ok(cmd) = self.cmd_r.try_recv() {
+            match cmd {
+                RenderCommand::PushView { view, proj, position } => {
+                    self.pending_views.push(View::full(view, proj, position))
+                }
+                RenderCommand::PushCamera(view) => self.pending_views.push(view),
+                RenderCommand::PushLight { position, light } => {
+                    self.pending_state.lights.push((position, light))
+                }
+                RenderCommand::PushState => {
+                    if self.pending_state.lights.len() > MAX_LIGHTS {
+                        warn!("{} lights submitted, only first {} are used",
+                              self.pending_state.lights.len(), MAX_LIGHTS);
+                    }
+                    if self.pending_views.len() > MAX_VIEWS {
+                        warn!("{} cameras submitted, only first {} are drawn",
+                              self.pending_views.len(), MAX_VIEWS);
+                        self.pending_views.truncate(MAX_VIEWS);
+                    }
+                    //without new cameras previous ones stay
+                    let first = self.pending_views.iter()
+                        .find(|v| v.target.is_none())
+                        .or(self.pending_views.first());
+                    if let Some(first) = first {
+                        self.pending_state = self.pending_state.for_view(first);
+                        self.views = self.pending_views.drain(..).collect();
+                    }
+                    self.frame_state = self.pending_state.clone();
+                    self.pending_state.lights.clear();
+                }
+                RenderCommand::DebugLines(lines) => self.debug_lines = lines,
+                RenderCommand::Ui(frame) => self.ui_frame = frame,
+                RenderCommand::Text(texts) => self.texts = texts,
+                RenderCommand::SetDebugMode(mode) => {
+                    if let Err(e) = self.set_debug_mode(mode) {
+                        warn!("{}", e);
+                    }
+                }
+                RenderCommand::Draw => (),
+            }
+        }
+    }
 }
 
 #[cfg(all(not(feature = "gl"), not(feature = "wgl")))]
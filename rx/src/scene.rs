@@ -0,0 +1,1000 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use glm::{Vec3, Vec4};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use specs::{Builder, Entity, Join, World, WorldExt};
+
+use crate::assets::{MeshPtr, TexturePtr};
+use crate::ecs::{
+    ActiveCamera,
+    Camera,
+    CameraObstacle,
+    CameraTarget,
+    FlyCamera,
+    Light,
+    LightKind,
+    Material,
+    Name,
+    OrthoCamera,
+    Parent,
+    Position,
+    Render,
+    RenderLayers,
+    Rotation,
+    TargetCamera,
+    Transform,
+    Transformation,
+    Velocity,
+};
+use crate::graphics::draw::{ClearMode, ViewportRect};
+use crate::graphics::target::TargetPtr;
+use crate::prefab::{PrefabHooks, Prefabs};
+
+///Names scene files use for uploaded meshes, textures and render targets, kept in `World`
+#[derive(Debug, Clone, Default)]
+pub struct SceneAssets {
+    meshes: Vec<(String, MeshPtr)>,
+    textures: Vec<(String, TexturePtr)>,
+    targets: Vec<(String, TargetPtr)>,
+}
+
+impl SceneAssets {
+    pub fn add_mesh(&mut self, name: &str, mesh: MeshPtr) {
+        self.meshes.retain(|(n, _)| n != name);
+        self.meshes.push((name.to_owned(), mesh));
+    }
+
+    pub fn add_texture(&mut self, name: &str, texture: TexturePtr) {
+        self.textures.retain(|(n, _)| n != name);
+        self.textures.push((name.to_owned(), texture));
+    }
+
+    ///Target texture goes by the same name
+    pub fn add_target(&mut self, name: &str, target: TargetPtr) {
+        self.targets.retain(|(n, _)| n != name);
+        self.targets.push((name.to_owned(), target));
+        self.add_texture(name, target.texture);
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<&MeshPtr> {
+        self.meshes.iter().find(|(n, _)| n == name).map(|(_, m)| m)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<TexturePtr> {
+        self.textures.iter().find(|(n, _)| n == name).map(|(_, t)| *t)
+    }
+
+    pub fn target(&self, name: &str) -> Option<TargetPtr> {
+        self.targets.iter().find(|(n, _)| n == name).map(|(_, t)| *t)
+    }
+
+    fn mesh_name(&self, mesh: &MeshPtr) -> Option<&str> {
+        self.meshes.iter()
+            .find(|(_, m)| m.base_vertex == mesh.base_vertex && m.indices == mesh.indices)
+            .map(|(n, _)| n.as_str())
+    }
+
+    fn texture_name(&self, texture: TexturePtr) -> Option<&str> {
+        self.textures.iter().find(|(_, t)| *t == texture).map(|(n, _)| n.as_str())
+    }
+
+    fn target_name(&self, target: TargetPtr) -> Option<&str> {
+        self.targets.iter().find(|(_, t)| *t == target).map(|(n, _)| n.as_str())
+    }
+}
+
+///Level as data, written to and read from RON or JSON
+///
+///Assets are referred to by `SceneAssets` names, entities by `Name`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Scene {
+    ///`ActiveCamera` entity name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<String>,
+    ///`CameraTarget` entity name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_target: Option<String>,
+    pub entities: Vec<SceneEntity>,
+}
+
+///Components of one entity, everything left out is not added
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SceneEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    ///`Prefabs` entry the other fields override
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefab: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+    ///degrees, see `Rotation`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformDesc>,
+    ///name of `Parent` entity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    ///`Render` mesh name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialDesc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layers: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light: Option<LightDesc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraDesc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_camera: Option<TargetCameraDesc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ortho_camera: Option<OrthoCameraDesc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fly_camera: Option<FlyCameraDesc>,
+    ///`CameraObstacle`, `Some(false)` turns off prefab's one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obstacle: Option<bool>,
+}
+
+///`Transform` with rotation quaternion as x, y, z, w
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TransformDesc {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+///`Material` with texture name
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MaterialDesc {
+    pub color: [f32; 4],
+    pub shininess: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+    pub transparent: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum LightKindDesc {
+    Point,
+    Directional {
+        direction: [f32; 3],
+    },
+    Spot {
+        direction: [f32; 3],
+        inner: f32,
+        outer: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct LightDesc {
+    pub kind: LightKindDesc,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ClearDesc {
+    Background,
+    Color([f32; 4]),
+    DepthOnly,
+    None,
+}
+
+///`Camera` with render target name, none - screen
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CameraDesc {
+    ///x, y, width, height
+    pub viewport: [f32; 4],
+    pub layers: u32,
+    pub clear: ClearDesc,
+    pub order: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TargetCameraDesc {
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub pitch_range: (f32, f32),
+    pub distance_range: (f32, f32),
+    pub smoothing: f32,
+    pub collision_margin: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct OrthoCameraDesc {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub height: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct FlyCameraDesc {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub speed: f32,
+}
+
+impl Scene {
+    pub fn from_ron(text: &str) -> Result<Self, &'static str> {
+        ron::de::from_str(text).map_err(|e| {
+            error!("Scene parse error: {:?}", e);
+            "Failed to parse RON scene!"
+        })
+    }
+
+    pub fn to_ron(&self) -> Result<String, &'static str> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| {
+            error!("Scene write error: {:?}", e);
+            "Failed to write RON scene!"
+        })
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, &'static str> {
+        serde_json::from_str(text).map_err(|e| {
+            error!("Scene parse error: {:?}", e);
+            "Failed to parse JSON scene!"
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, &'static str> {
+        serde_json::to_string_pretty(self).map_err(|e| {
+            error!("Scene write error: {:?}", e);
+            "Failed to write JSON scene!"
+        })
+    }
+
+    ///JSON for `.json` files, RON otherwise
+    pub fn load(path: &Path) -> Result<Self, &'static str> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            error!("Scene not found: {:?}, err: {:?}", path, e);
+            "Error opening scene file"
+        })?;
+        if is_json(path) { Self::from_json(&text) } else { Self::from_ron(&text) }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), &'static str> {
+        let text = if is_json(path) { self.to_json()? } else { self.to_ron()? };
+        fs::write(path, text).map_err(|e| {
+            error!("Scene not saved: {:?}, err: {:?}", path, e);
+            "Error writing scene file"
+        })?;
+        info!("Scene saved: {:?}, {} entities", path, self.entities.len());
+        Ok(())
+    }
+
+    ///Creates scene entities, components must be registered and assets added to `SceneAssets`
+    ///
+    ///Rendered entities get `Transformation`, and without `transform` default `Position` and `Rotation`.
+    ///Nothing is created if an asset, prefab or entity name is unknown,
+    ///if `camera` has no `TargetCamera`, `OrthoCamera` or `FlyCamera` to look through,
+    ///or if `camera_target` gets no `Transformation` to orbit.
+    ///`PrefabHooks` run last, once all entities, parents and cameras are in place.
+    pub fn spawn(&self, world: &mut World) -> Result<Vec<Entity>, &'static str> {
+        let assets = world.try_fetch::<SceneAssets>().map(|a| (*a).clone()).unwrap_or_default();
+        let prefabs = world.try_fetch::<Prefabs>().map(|p| (*p).clone()).unwrap_or_default();
+        let (entities, chains): (Vec<_>, Vec<_>) = self.entities.iter()
+            .map(|e| prefabs.expand_chain(e))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        let mut named: HashMap<String, Entity> = {
+            let (entities, names) = (world.entities(), world.read_storage::<Name>());
+            (&entities, &names).join().map(|(e, n)| (n.0.clone(), e)).collect()
+        };
+
+        let mut resolved = Vec::with_capacity(entities.len());
+        for desc in entities.iter() {
+            let mesh = lookup(desc.mesh.as_ref(), |n| assets.mesh(n).cloned(), "Scene refers to unknown mesh!")?;
+            let texture = lookup(
+                desc.material.as_ref().and_then(|m| m.texture.as_ref()),
+                |n| assets.texture(n),
+                "Scene refers to unknown texture!",
+            )?;
+            let target = lookup(
+                desc.camera.as_ref().and_then(|c| c.target.as_ref()),
+                |n| assets.target(n),
+                "Scene refers to unknown render target!",
+            )?;
+            resolved.push((mesh, texture, target));
+        }
+        let references = entities.iter()
+            .filter_map(|d| d.parent.as_ref())
+            .chain(self.camera.iter())
+            .chain(self.camera_target.iter());
+        for name in references {
+            if !named.contains_key(name) && entities.iter().all(|d| d.name.as_ref() != Some(name)) {
+                error!("Unknown scene entity: {}", name);
+                return Err("Scene refers to unknown entity!");
+            }
+        }
+        if let Some(name) = self.camera.as_ref() {
+            let controlled = match entities.iter().rev().find(|d| d.name.as_ref() == Some(name)) {
+                Some(d) => d.target_camera.is_some() || d.ortho_camera.is_some() || d.fly_camera.is_some(),
+                None => {
+                    let e = named[name];
+                    world.read_storage::<TargetCamera>().contains(e)
+                        || world.read_storage::<OrthoCamera>().contains(e)
+                        || world.read_storage::<FlyCamera>().contains(e)
+                }
+            };
+            if !controlled {
+                error!("Scene camera entity has no camera: {}", name);
+                return Err("Scene camera entity has no camera!");
+            }
+        }
+        //cameras orbit its `Transformation`
+        if let Some(name) = self.camera_target.as_ref() {
+            let placed = match entities.iter().rev().find(|d| d.name.as_ref() == Some(name)) {
+                Some(d) => d.transformed(),
+                None => world.read_storage::<Transformation>().contains(named[name]),
+            };
+            if !placed {
+                error!("Scene camera target has no transformation: {}", name);
+                return Err("Scene camera target has no transformation!");
+            }
+        }
+
+        let mut spawned = Vec::with_capacity(entities.len());
+        for (desc, (mesh, texture, target)) in entities.iter().zip(resolved) {
+            let rendered = mesh.is_some();
+            let placed = rendered && desc.transform.is_none();
+            let position = desc.position.or(if placed { Some([0.; 3]) } else { None });
+            let rotation = desc.rotation.or(if placed { Some([0.; 3]) } else { None });
+
+            let mut builder = world.create_entity();
+            if let Some(name) = desc.name.as_ref() {
+                builder = builder.with(Name(name.clone()));
+            }
+            if let Some([x, y, z]) = position {
+                builder = builder.with(Position { x, y, z });
+            }
+            if let Some([x, y, z]) = rotation {
+                builder = builder.with(Rotation { x, y, z });
+            }
+            if let Some(v) = desc.velocity {
+                builder = builder.with(Velocity { v: vec3(v) });
+            }
+            if let Some(t) = desc.transform.as_ref() {
+                builder = builder.with(Transform::from(t));
+            }
+            if desc.transformed() {
+                builder = builder.with(Transformation::default());
+            }
+            if let Some(mesh) = mesh {
+                builder = builder.with(Render { mesh });
+            }
+            if let Some(m) = desc.material.as_ref() {
+                builder = builder.with(Material {
+                    base_color: vec4(m.color),
+                    shininess: m.shininess,
+                    texture,
+                    transparent: m.transparent,
+                });
+            }
+            if let Some(layers) = desc.layers {
+                builder = builder.with(RenderLayers(layers));
+            }
+            if let Some(l) = desc.light.as_ref() {
+                builder = builder.with(Light::from(l));
+            }
+            if let Some(c) = desc.camera.as_ref() {
+                let [x, y, width, height] = c.viewport;
+                builder = builder.with(Camera {
+                    viewport: ViewportRect::new(x, y, width, height),
+                    layers: c.layers,
+                    clear: c.clear.into(),
+                    order: c.order,
+                    target,
+                });
+            }
+            if let Some(c) = desc.target_camera.as_ref() {
+                builder = builder.with(TargetCamera::from(c));
+            }
+            if let Some(c) = desc.ortho_camera.as_ref() {
+                builder = builder.with(OrthoCamera::from(c));
+            }
+            if let Some(c) = desc.fly_camera.as_ref() {
+                builder = builder.with(FlyCamera::from(c));
+            }
+            if desc.obstacle == Some(true) {
+                builder = builder.with(CameraObstacle);
+            }
+            let entity = builder.build();
+            if let Some(name) = desc.name.as_ref() {
+                named.insert(name.clone(), entity);
+            }
+            spawned.push(entity);
+        }
+
+        //parents may come later in the file
+        {
+            let mut parents = world.write_storage::<Parent>();
+            for (desc, entity) in entities.iter().zip(spawned.iter()) {
+                if let Some(parent) = desc.parent.as_ref() {
+                    parents.insert(*entity, Parent(named[parent])).expect("not able to insert parent");
+                }
+            }
+        }
+        if let Some(camera) = self.camera.as_ref() {
+            world.insert(ActiveCamera(Some(named[camera])));
+        }
+        if let Some(target) = self.camera_target.as_ref() {
+            world.insert(CameraTarget(Some(named[target])));
+        }
+        if let Some(hooks) = world.try_fetch::<PrefabHooks>() {
+            for (chain, entity) in chains.iter().zip(spawned.iter()) {
+                hooks.run(chain, *entity, world);
+            }
+        }
+        debug!("Scene spawned: {} entities", spawned.len());
+        Ok(spawned)
+    }
+
+    ///Scene of `world` as it is now, e.g. for saving mid-session
+    ///
+    ///Entities without scene components are left out, as are meshes and textures missing from `SceneAssets`.
+    ///Prefab instances are written out with all their components.
+    ///Referred entities without `Name` are named after their id.
+    pub fn capture(world: &World) -> Self {
+        let assets = world.try_fetch::<SceneAssets>().map(|a| (*a).clone()).unwrap_or_default();
+        let entities = world.entities();
+        let names = world.read_storage::<Name>();
+        let positions = world.read_storage::<Position>();
+        let rotations = world.read_storage::<Rotation>();
+        let velocities = world.read_storage::<Velocity>();
+        let transforms = world.read_storage::<Transform>();
+        let parents = world.read_storage::<Parent>();
+        let renders = world.read_storage::<Render>();
+        let materials = world.read_storage::<Material>();
+        let layers = world.read_storage::<RenderLayers>();
+        let lights = world.read_storage::<Light>();
+        let cameras = world.read_storage::<Camera>();
+        let target_cameras = world.read_storage::<TargetCamera>();
+        let ortho_cameras = world.read_storage::<OrthoCamera>();
+        let fly_cameras = world.read_storage::<FlyCamera>();
+        let obstacles = world.read_storage::<CameraObstacle>();
+
+        let camera = world.try_fetch::<ActiveCamera>().and_then(|c| c.0);
+        let camera_target = world.try_fetch::<CameraTarget>().and_then(|c| c.0);
+        let referred: Vec<Entity> = (&parents).join().map(|p| p.0).chain(camera).chain(camera_target).collect();
+        let name_of = |e: Entity| names.get(e).map_or_else(|| format!("entity{}", e.id()), |n| n.0.clone());
+
+        let mut scene = Scene {
+            camera: camera.map(&name_of),
+            camera_target: camera_target.map(&name_of),
+            entities: Vec::new(),
+        };
+        for e in (&entities).join() {
+            let desc = SceneEntity {
+                name: if names.get(e).is_some() || referred.contains(&e) { Some(name_of(e)) } else { None },
+                position: positions.get(e).map(|p| [p.x, p.y, p.z]),
+                rotation: rotations.get(e).map(|r| [r.x, r.y, r.z]),
+                velocity: velocities.get(e).map(|v| arr3(&v.v)),
+                transform: transforms.get(e).map(TransformDesc::from),
+                parent: parents.get(e).map(|p| name_of(p.0)),
+                mesh: renders.get(e).and_then(|r| {
+                    let name = assets.mesh_name(&r.mesh);
+                    if name.is_none() {
+                        warn!("Mesh without scene name is not saved: {:?}", e);
+                    }
+                    name.map(str::to_owned)
+                }),
+                material: materials.get(e).map(|m| MaterialDesc {
+                    color: arr4(&m.base_color),
+                    shininess: m.shininess,
+                    texture: m.texture.and_then(|t| {
+                        let name = assets.texture_name(t);
+                        if name.is_none() {
+                            warn!("Texture without scene name is not saved: {:?}", e);
+                        }
+                        name.map(str::to_owned)
+                    }),
+                    transparent: m.transparent,
+                }),
+                layers: layers.get(e).map(|l| l.0),
+                light: lights.get(e).map(LightDesc::from),
+                camera: cameras.get(e).map(|c| CameraDesc {
+                    viewport: [c.viewport.x, c.viewport.y, c.viewport.width, c.viewport.height],
+                    layers: c.layers,
+                    clear: c.clear.into(),
+                    order: c.order,
+                    target: c.target.and_then(|t| {
+                        let name = assets.target_name(t);
+                        if name.is_none() {
+                            warn!("Render target without scene name is not saved: {:?}", e);
+                        }
+                        name.map(str::to_owned)
+                    }),
+                }),
+                target_camera: target_cameras.get(e).map(TargetCameraDesc::from),
+                ortho_camera: ortho_cameras.get(e).map(OrthoCameraDesc::from),
+                fly_camera: fly_cameras.get(e).map(FlyCameraDesc::from),
+                obstacle: obstacles.get(e).map(|_| true),
+            };
+            if desc != SceneEntity::default() {
+                scene.entities.push(desc);
+            }
+        }
+        scene
+    }
+}
+
+impl SceneEntity {
+    ///Gets `Transformation` when spawned
+    fn transformed(&self) -> bool {
+        self.mesh.is_some() || self.transform.is_some() || (self.position.is_some() && self.rotation.is_some())
+    }
+
+    ///Fields set here replace those of `base` as a whole, `name` is never inherited
+    pub fn merged(&self, base: &SceneEntity) -> SceneEntity {
+        SceneEntity {
+            name: self.name.clone(),
+            prefab: self.prefab.clone().or_else(|| base.prefab.clone()),
+            position: self.position.or(base.position),
+            rotation: self.rotation.or(base.rotation),
+            velocity: self.velocity.or(base.velocity),
+            transform: self.transform.or(base.transform),
+            parent: self.parent.clone().or_else(|| base.parent.clone()),
+            mesh: self.mesh.clone().or_else(|| base.mesh.clone()),
+            material: self.material.clone().or_else(|| base.material.clone()),
+            layers: self.layers.or(base.layers),
+            light: self.light.or(base.light),
+            camera: self.camera.clone().or_else(|| base.camera.clone()),
+            target_camera: self.target_camera.or(base.target_camera),
+            ortho_camera: self.ortho_camera.or(base.ortho_camera),
+            fly_camera: self.fly_camera.or(base.fly_camera),
+            obstacle: self.obstacle.or(base.obstacle),
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "json")
+}
+
+fn lookup<T, F>(name: Option<&String>, find: F, err: &'static str) -> Result<Option<T>, &'static str>
+    where F: Fn(&str) -> Option<T> {
+    match name {
+        Some(name) => find(name).map(Some).ok_or_else(|| {
+            error!("{}: {}", err, name);
+            err
+        }),
+        None => Ok(None),
+    }
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    glm::vec3(v[0], v[1], v[2])
+}
+
+fn vec4(v: [f32; 4]) -> Vec4 {
+    glm::vec4(v[0], v[1], v[2], v[3])
+}
+
+fn arr3(v: &Vec3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn arr4(v: &Vec4) -> [f32; 4] {
+    [v.x, v.y, v.z, v.w]
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        (&Transform::default()).into()
+    }
+}
+
+impl From<&Transform> for TransformDesc {
+    fn from(t: &Transform) -> Self {
+        Self {
+            translation: arr3(&t.translation),
+            rotation: arr4(&t.rotation.coords),
+            scale: arr3(&t.scale),
+        }
+    }
+}
+
+impl From<&TransformDesc> for Transform {
+    fn from(t: &TransformDesc) -> Self {
+        let [x, y, z, w] = t.rotation;
+        Transform {
+            translation: vec3(t.translation),
+            rotation: glm::quat(x, y, z, w),
+            scale: vec3(t.scale),
+        }
+    }
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        let m = Material::default();
+        Self {
+            color: arr4(&m.base_color),
+            shininess: m.shininess,
+            texture: None,
+            transparent: m.transparent,
+        }
+    }
+}
+
+impl Default for LightDesc {
+    fn default() -> Self {
+        (&Light::default()).into()
+    }
+}
+
+impl From<&Light> for LightDesc {
+    fn from(l: &Light) -> Self {
+        let kind = match l.kind {
+            LightKind::Point => LightKindDesc::Point,
+            LightKind::Directional { direction } => LightKindDesc::Directional { direction: arr3(&direction) },
+            LightKind::Spot { direction, inner, outer } => LightKindDesc::Spot { direction: arr3(&direction), inner, outer },
+        };
+        Self {
+            kind,
+            color: arr3(&l.color),
+            intensity: l.intensity,
+            range: l.range,
+        }
+    }
+}
+
+impl From<&LightDesc> for Light {
+    fn from(l: &LightDesc) -> Self {
+        let kind = match l.kind {
+            LightKindDesc::Point => LightKind::Point,
+            LightKindDesc::Directional { direction } => LightKind::Directional { direction: vec3(direction) },
+            LightKindDesc::Spot { direction, inner, outer } => LightKind::Spot { direction: vec3(direction), inner, outer },
+        };
+        Light {
+            kind,
+            color: vec3(l.color),
+            intensity: l.intensity,
+            range: l.range,
+        }
+    }
+}
+
+impl From<ClearMode> for ClearDesc {
+    fn from(clear: ClearMode) -> Self {
+        match clear {
+            ClearMode::Background => ClearDesc::Background,
+            ClearMode::Color(color) => ClearDesc::Color(arr4(&color)),
+            ClearMode::DepthOnly => ClearDesc::DepthOnly,
+            ClearMode::None => ClearDesc::None,
+        }
+    }
+}
+
+impl From<ClearDesc> for ClearMode {
+    fn from(clear: ClearDesc) -> Self {
+        match clear {
+            ClearDesc::Background => ClearMode::Background,
+            ClearDesc::Color(color) => ClearMode::Color(vec4(color)),
+            ClearDesc::DepthOnly => ClearMode::DepthOnly,
+            ClearDesc::None => ClearMode::None,
+        }
+    }
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        let c = Camera::default();
+        Self {
+            viewport: [c.viewport.x, c.viewport.y, c.viewport.width, c.viewport.height],
+            layers: c.layers,
+            clear: c.clear.into(),
+            order: c.order,
+            target: None,
+        }
+    }
+}
+
+impl Default for TargetCameraDesc {
+    fn default() -> Self {
+        (&TargetCamera::default()).into()
+    }
+}
+
+impl From<&TargetCamera> for TargetCameraDesc {
+    fn from(c: &TargetCamera) -> Self {
+        Self {
+            fov: c.fov,
+            near: c.near,
+            far: c.far,
+            distance: c.distance,
+            yaw: c.yaw,
+            pitch: c.pitch,
+            pitch_range: c.pitch_range,
+            distance_range: c.distance_range,
+            smoothing: c.smoothing,
+            collision_margin: c.collision_margin,
+        }
+    }
+}
+
+impl From<&TargetCameraDesc> for TargetCamera {
+    fn from(c: &TargetCameraDesc) -> Self {
+        let mut camera = TargetCamera::default();
+        camera.fov = c.fov;
+        camera.near = c.near;
+        camera.far = c.far;
+        camera.distance = c.distance;
+        camera.yaw = c.yaw;
+        camera.pitch = c.pitch;
+        camera.pitch_range = c.pitch_range;
+        camera.distance_range = c.distance_range;
+        camera.smoothing = c.smoothing;
+        camera.collision_margin = c.collision_margin;
+        camera.update_projection();
+        camera.snap();
+        camera
+    }
+}
+
+impl Default for OrthoCameraDesc {
+    fn default() -> Self {
+        (&OrthoCamera::default()).into()
+    }
+}
+
+impl From<&OrthoCamera> for OrthoCameraDesc {
+    fn from(c: &OrthoCamera) -> Self {
+        Self {
+            position: arr3(&c.position),
+            yaw: c.yaw,
+            pitch: c.pitch,
+            height: c.height,
+            near: c.near,
+            far: c.far,
+        }
+    }
+}
+
+impl From<&OrthoCameraDesc> for OrthoCamera {
+    fn from(c: &OrthoCameraDesc) -> Self {
+        let mut camera = OrthoCamera::default();
+        camera.position = vec3(c.position);
+        camera.yaw = c.yaw;
+        camera.pitch = c.pitch;
+        camera.height = c.height;
+        camera.near = c.near;
+        camera.far = c.far;
+        camera.update_projection();
+        camera.update_view();
+        camera
+    }
+}
+
+impl Default for FlyCameraDesc {
+    fn default() -> Self {
+        (&FlyCamera::default()).into()
+    }
+}
+
+impl From<&FlyCamera> for FlyCameraDesc {
+    fn from(c: &FlyCamera) -> Self {
+        Self {
+            position: arr3(&c.position),
+            yaw: c.yaw,
+            pitch: c.pitch,
+            fov: c.fov,
+            near: c.near,
+            far: c.far,
+            speed: c.speed,
+        }
+    }
+}
+
+impl From<&FlyCameraDesc> for FlyCamera {
+    fn from(c: &FlyCameraDesc) -> Self {
+        let mut camera = FlyCamera::default();
+        camera.position = vec3(c.position);
+        camera.yaw = c.yaw;
+        camera.pitch = c.pitch;
+        camera.fov = c.fov;
+        camera.near = c.near;
+        camera.far = c.far;
+        camera.speed = c.speed;
+        camera.update_projection();
+        camera.update_view();
+        camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::culling::Aabb;
+
+    fn mesh(base_vertex: i32) -> MeshPtr {
+        MeshPtr { indices: 0..6, base_vertex, bounds: Aabb::default(), triangles: Default::default() }
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Name>();
+        world.register::<Position>();
+        world.register::<Rotation>();
+        world.register::<Velocity>();
+        world.register::<Transform>();
+        world.register::<Transformation>();
+        world.register::<Parent>();
+        world.register::<Render>();
+        world.register::<Material>();
+        world.register::<RenderLayers>();
+        world.register::<Light>();
+        world.register::<Camera>();
+        world.register::<TargetCamera>();
+        world.register::<OrthoCamera>();
+        world.register::<FlyCamera>();
+        world.register::<CameraObstacle>();
+
+        let mut assets = SceneAssets::default();
+        assets.add_mesh("map", mesh(0));
+        assets.add_mesh("screen", mesh(100));
+        assets.add_target("screen", TargetPtr { index: 0, texture: TexturePtr(1), width: 256, height: 256 });
+        world.insert(assets);
+        world.insert(Prefabs::from_ron(include_str!("../../assets/prefabs/demo.ron")).unwrap());
+        world
+    }
+
+    fn level() -> Scene {
+        Scene::from_ron(include_str!("../../assets/scenes/level.ron")).unwrap()
+    }
+
+    fn entity_count(world: &World) -> usize {
+        (&world.entities()).join().count()
+    }
+
+    #[test]
+    fn capture_of_spawned_level_equals_level() {
+        let mut world = world();
+        let scene = level();
+        scene.spawn(&mut world).unwrap();
+
+        //prefab instances are captured with all their components
+        let prefabs = (*world.read_resource::<Prefabs>()).clone();
+        let expected = Scene {
+            entities: scene.entities.iter().map(|e| prefabs.expand(e).unwrap()).collect(),
+            ..scene
+        };
+        assert_eq!(Scene::capture(&world), expected);
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let scene = level();
+        let json = scene.to_json().unwrap();
+        assert_eq!(Scene::from_json(&json).unwrap(), scene);
+        assert_eq!(Scene::from_ron(&scene.to_ron().unwrap()).unwrap(), scene);
+    }
+
+    #[test]
+    fn unknown_names_spawn_nothing() {
+        let broken = [
+            SceneEntity { mesh: Some("missing".to_owned()), ..Default::default() },
+            SceneEntity {
+                material: Some(MaterialDesc { texture: Some("missing".to_owned()), ..Default::default() }),
+                ..Default::default()
+            },
+            SceneEntity { parent: Some("missing".to_owned()), ..Default::default() },
+            SceneEntity { prefab: Some("missing".to_owned()), ..Default::default() },
+        ];
+        for entity in broken.iter() {
+            let mut world = world();
+            let mut scene = level();
+            scene.entities.push(entity.clone());
+            assert!(scene.spawn(&mut world).is_err());
+            assert_eq!(entity_count(&world), 0);
+        }
+
+        let mut world = world();
+        let scene = Scene { camera_target: Some("missing".to_owned()), ..level() };
+        assert!(scene.spawn(&mut world).is_err());
+        assert_eq!(entity_count(&world), 0);
+
+        //cameras can't orbit a light or an entity with only position
+        let mut scene = level();
+        scene.entities.push(SceneEntity {
+            name: Some("marker".to_owned()),
+            position: Some([1., 2., 3.]),
+            ..Default::default()
+        });
+        for target in &["minimap", "marker"] {
+            let mut world = world();
+            let scene = Scene { camera_target: Some(target.to_string()), ..scene.clone() };
+            assert!(scene.spawn(&mut world).is_err());
+            assert_eq!(entity_count(&world), 0);
+        }
+        let mut world = world();
+        let scene = Scene { camera_target: Some("screen".to_owned()), ..scene };
+        assert!(scene.spawn(&mut world).is_ok());
+    }
+
+    #[test]
+    fn merged_keeps_own_name_and_obstacle() {
+        let base = SceneEntity {
+            name: Some("base".to_owned()),
+            position: Some([1., 2., 3.]),
+            obstacle: Some(true),
+            ..Default::default()
+        };
+        let merged = SceneEntity { obstacle: Some(false), ..Default::default() }.merged(&base);
+        assert_eq!(merged.name, None);
+        assert_eq!(merged.position, Some([1., 2., 3.]));
+        assert_eq!(merged.obstacle, Some(false));
+        assert_eq!(SceneEntity::default().merged(&base).obstacle, Some(true));
+    }
+
+    #[test]
+    fn prefab_hooks_run_for_derived_prefabs() {
+        let mut world = world();
+        world.write_resource::<Prefabs>().add("red_light", SceneEntity {
+            prefab: Some("spot_light".to_owned()),
+            ..Default::default()
+        });
+        let mut hooks = PrefabHooks::default();
+        hooks.add("spot_light", |e, world| {
+            world.write_storage::<Velocity>().insert(e, Velocity::default()).unwrap();
+        });
+        world.insert(hooks);
+
+        let scene = Scene {
+            entities: vec![
+                SceneEntity { prefab: Some("red_light".to_owned()), ..Default::default() },
+                SceneEntity { position: Some([0.; 3]), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let spawned = scene.spawn(&mut world).unwrap();
+        let velocities = world.read_storage::<Velocity>();
+        assert!(velocities.contains(spawned[0]));
+        assert!(!velocities.contains(spawned[1]));
+    }
+
+    #[test]
+    fn active_camera_needs_camera() {
+        let mut world = world();
+        let scene = Scene { camera: Some("map".to_owned()), ..level() };
+        assert!(scene.spawn(&mut world).is_err());
+        assert_eq!(entity_count(&world), 0);
+
+        let scene = Scene { camera: Some("security".to_owned()), ..level() };
+        let spawned = scene.spawn(&mut world).unwrap();
+        assert_eq!(world.read_resource::<ActiveCamera>().0, Some(spawned[5]));
+    }
+}
@@ -0,0 +1,96 @@
+use image::RgbaImage;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+///Sky images for `Renderer::set_skybox`
+pub enum SkyboxSource {
+    ///cube faces in +X, -X, +Y, -Y, +Z, -Z order, square and of the same size
+    Faces(Box<[RgbaImage; 6]>),
+    ///longitude along width, +Y at the top row
+    Equirectangular(RgbaImage),
+}
+
+impl SkyboxSource {
+    ///Sky is sampled as a cubemap, equirectangular image is resampled into faces
+    pub fn into_faces(self) -> Result<Box<[RgbaImage; 6]>, &'static str> {
+        match self {
+            SkyboxSource::Faces(faces) => Ok(faces),
+            SkyboxSource::Equirectangular(image) => {
+                let (width, height) = image.dimensions();
+                if width == 0 || height == 0 {
+                    return Err("Skybox image is empty!");
+                }
+                let size = (width / 4).max(1);
+                let face = |face: usize| RgbaImage::from_fn(size, size, |x, y| {
+                    let s = (x as f32 + 0.5) / size as f32;
+                    let t = (y as f32 + 0.5) / size as f32;
+                    let d = glm::normalize(&face_direction(face, s, t));
+                    let u = d.z.atan2(d.x) / (std::f32::consts::PI * 2.) + 0.5;
+                    let v = d.y.max(-1.).min(1.).acos() / std::f32::consts::PI;
+                    let px = ((u * width as f32) as u32).min(width - 1);
+                    let py = ((v * height as f32) as u32).min(height - 1);
+                    *image.get_pixel(px, py)
+                });
+                debug!("Skybox resampled to {}x{} faces", size, size);
+                Ok(Box::new([face(0), face(1), face(2), face(3), face(4), face(5)]))
+            }
+        }
+    }
+}
+
+///Direction through 0..1 texture coordinates of face, as cubemap sampling picks them
+fn face_direction(face: usize, s: f32, t: f32) -> glm::Vec3 {
+    let (sc, tc) = (s * 2. - 1., t * 2. - 1.);
+    match face {
+        0 => glm::vec3(1., -tc, -sc),
+        1 => glm::vec3(-1., -tc, sc),
+        2 => glm::vec3(sc, 1., tc),
+        3 => glm::vec3(sc, -1., -tc),
+        4 => glm::vec3(sc, -tc, 1.),
+        _ => glm::vec3(-sc, -tc, -1.),
+    }
+}
+
+///Face index and its 0..1 texture coordinates, inverse of `face_direction`
+#[cfg(test)]
+fn cube_face(d: &glm::Vec3) -> (usize, f32, f32) {
+    let (ax, ay, az) = (d.x.abs(), d.y.abs(), d.z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if d.x > 0. { (0, -d.z, -d.y, ax) } else { (1, d.z, -d.y, ax) }
+    } else if ay >= az {
+        if d.y > 0. { (2, d.x, d.z, ay) } else { (3, d.x, -d.z, ay) }
+    } else if d.z > 0. {
+        (4, d.x, -d.y, az)
+    } else {
+        (5, -d.x, -d.y, az)
+    };
+    (face, (sc / ma + 1.) / 2., (tc / ma + 1.) / 2.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_direction_picks_its_face() {
+        for face in 0..6 {
+            for &(s, t) in &[(0.5, 0.5), (0.1, 0.8), (0.9, 0.2)] {
+                let (picked, ps, pt) = cube_face(&face_direction(face, s, t));
+                assert_eq!(picked, face);
+                assert!((ps - s).abs() < 1e-5 && (pt - t).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn equirectangular_is_split_into_faces() {
+        //top half white, bottom half black
+        let image = RgbaImage::from_fn(64, 32, |_, y| {
+            if y < 16 { image::Rgba([255; 4]) } else { image::Rgba([0, 0, 0, 255]) }
+        });
+        let faces = SkyboxSource::Equirectangular(image).into_faces().unwrap();
+        assert!(faces.iter().all(|f| f.dimensions() == (16, 16)));
+        assert_eq!(faces[2].get_pixel(8, 8).0, [255; 4]);
+        assert_eq!(faces[3].get_pixel(8, 8).0, [0, 0, 0, 255]);
+    }
+}
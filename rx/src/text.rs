@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use glm::{Mat4, Vec3, Vec4};
+use image::RgbaImage;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use rusttype::{Font, point, Scale};
+use specs::{Component, VecStorage};
+
+use crate::ui::UiVertex;
+
+const ATLAS_WIDTH: u32 = 512;
+///printable ASCII, other chars are drawn as `FALLBACK`
+const FIRST_CHAR: u8 = 32;
+const LAST_CHAR: u8 = 126;
+const FALLBACK: char = '?';
+///between glyphs, keeps linear filtering from bleeding
+const PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    ///top left corner in pixels
+    Screen(f32, f32),
+    ///projected position, relative to entity's `Transformation` for `Text` components
+    World(Vec3),
+}
+
+///String drawn over the scene, either as a component or pushed to `TextQueue`
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Text {
+    pub text: String,
+    pub anchor: TextAnchor,
+    ///line height in pixels
+    pub size: f32,
+    pub color: Vec4,
+}
+
+impl Text {
+    pub fn screen(x: f32, y: f32, text: &str, size: f32, color: &Vec4) -> Self {
+        Self {
+            text: text.to_owned(),
+            anchor: TextAnchor::Screen(x, y),
+            size,
+            color: color.clone(),
+        }
+    }
+
+    pub fn world(position: &Vec3, text: &str, size: f32, color: &Vec4) -> Self {
+        Self {
+            text: text.to_owned(),
+            anchor: TextAnchor::World(position.clone()),
+            size,
+            color: color.clone(),
+        }
+    }
+}
+
+///Immediate mode strings, usable from any system as a specs resource
+///
+///Cleared by `EcsLayer` before every rated update, like `DebugDraw`.
+#[derive(Debug, Default)]
+pub struct TextQueue {
+    texts: Vec<Text>,
+}
+
+impl TextQueue {
+    pub fn screen(&mut self, x: f32, y: f32, text: &str, size: f32, color: &Vec4) {
+        self.texts.push(Text::screen(x, y, text, size, color));
+    }
+
+    pub fn world(&mut self, position: &Vec3, text: &str, size: f32, color: &Vec4) {
+        self.texts.push(Text::world(position, text, size, color));
+    }
+
+    pub fn push(&mut self, text: Text) {
+        self.texts.push(text);
+    }
+
+    pub fn texts(&self) -> &[Text] {
+        &self.texts
+    }
+
+    pub fn clear(&mut self) {
+        self.texts.clear();
+    }
+}
+
+///Placement of rasterized glyph, in atlas pixels
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    ///`[u0, v0, u1, v1]`
+    uv: [f32; 4],
+    ///from pen position on baseline to top left corner
+    offset: (f32, f32),
+    size: (f32, f32),
+    advance: f32,
+}
+
+///Printable ASCII of one TTF font rasterized at fixed pixel height
+///
+///Coverage is stored in alpha of white texels, `text.frag` tints it with vertex color.
+///Other sizes are scaled from this one, so load the font at the largest size in use.
+pub struct FontAtlas {
+    font: Font<'static>,
+    scale: Scale,
+    ascent: f32,
+    line_height: f32,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl FontAtlas {
+    ///Rasterizes `ttf` at `px` pixels, returns atlas with image to upload as texture
+    pub fn new(ttf: Vec<u8>, px: f32) -> Result<(Self, RgbaImage), &'static str> {
+        let font = Font::from_bytes(ttf).map_err(|e| {
+            error!("{:?}", e);
+            "Couldn't parse font!"
+        })?;
+        let scale = Scale::uniform(px);
+        let v_metrics = font.v_metrics(scale);
+
+        //shelf packing, rows are as tall as their tallest glyph
+        let mut placed = Vec::new();
+        let (mut x, mut y, mut row_height) = (PADDING, PADDING, 0);
+        for c in (FIRST_CHAR..=LAST_CHAR).map(|c| c as char) {
+            let glyph = font.glyph(c).scaled(scale);
+            let advance = glyph.h_metrics().advance_width;
+            let glyph = glyph.positioned(point(0., 0.));
+            let (w, h) = glyph.pixel_bounding_box()
+                .map_or((0, 0), |bb| (bb.width() as u32, bb.height() as u32));
+            if w + PADDING * 2 > ATLAS_WIDTH {
+                return Err("Font size is too big for atlas!");
+            }
+            if x + w + PADDING > ATLAS_WIDTH {
+                x = PADDING;
+                y += row_height + PADDING;
+                row_height = 0;
+            }
+            placed.push((c, glyph, advance, x, y));
+            x += w + PADDING;
+            row_height = row_height.max(h);
+        }
+        let height = (y + row_height + PADDING).next_power_of_two();
+
+        let mut image = RgbaImage::from_pixel(ATLAS_WIDTH, height, image::Rgba([255, 255, 255, 0]));
+        let mut glyphs = HashMap::new();
+        let (atlas_w, atlas_h) = (ATLAS_WIDTH as f32, height as f32);
+        for (c, glyph, advance, x, y) in placed {
+            let entry = match glyph.pixel_bounding_box() {
+                Some(bb) => {
+                    glyph.draw(|gx, gy, coverage| {
+                        image.put_pixel(x + gx, y + gy, image::Rgba([255, 255, 255, (coverage * 255.) as u8]));
+                    });
+                    let (w, h) = (bb.width() as f32, bb.height() as f32);
+                    Glyph {
+                        uv: [
+                            x as f32 / atlas_w,
+                            y as f32 / atlas_h,
+                            (x as f32 + w) / atlas_w,
+                            (y as f32 + h) / atlas_h,
+                        ],
+                        offset: (bb.min.x as f32, bb.min.y as f32),
+                        size: (w, h),
+                        advance,
+                    }
+                }
+                //whitespace
+                None => Glyph {
+                    uv: [0.; 4],
+                    offset: (0., 0.),
+                    size: (0., 0.),
+                    advance,
+                },
+            };
+            glyphs.insert(c, entry);
+        }
+        info!("Font atlas: {}x{}, {} glyphs at {}px", ATLAS_WIDTH, height, glyphs.len(), px);
+
+        Ok((Self {
+            font,
+            scale,
+            ascent: v_metrics.ascent,
+            line_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+            glyphs,
+        }, image))
+    }
+
+    ///Screen position of `text` top left corner, none when world anchor is behind the camera
+    pub fn origin(text: &Text, view_proj: &Mat4, screen: (f32, f32)) -> Option<(f32, f32)> {
+        match text.anchor {
+            TextAnchor::Screen(x, y) => Some((x, y)),
+            TextAnchor::World(p) => {
+                let clip = view_proj * glm::vec4(p.x, p.y, p.z, 1.);
+                if clip.w <= 0. {
+                    return None;
+                }
+                Some(((clip.x / clip.w + 1.) / 2. * screen.0, (clip.y / clip.w + 1.) / 2. * screen.1))
+            }
+        }
+    }
+
+    ///Appends quads of `text` starting at `origin` in pixels, `\n` starts a new line
+    pub fn layout(&self, text: &Text, origin: (f32, f32), out: &mut Vec<UiVertex>) {
+        let k = text.size / self.line_height;
+        let color = [text.color.x, text.color.y, text.color.z, text.color.w];
+        let vertex = |px: f32, py: f32, u: f32, v: f32| UiVertex { position: [px, py], uv: [u, v], color };
+        let (mut pen_x, mut baseline) = (origin.0, origin.1 + self.ascent * k);
+        let mut previous = None;
+        for c in text.text.chars() {
+            if c == '\n' {
+                pen_x = origin.0;
+                baseline += self.line_height * k;
+                previous = None;
+                continue;
+            }
+            let c = if self.glyphs.contains_key(&c) { c } else { FALLBACK };
+            let glyph = &self.glyphs[&c];
+            if let Some(previous) = previous {
+                pen_x += self.font.pair_kerning(self.scale, previous, c) * k;
+            }
+            previous = Some(c);
+            if glyph.size.0 > 0. {
+                let (x, y) = (pen_x + glyph.offset.0 * k, baseline + glyph.offset.1 * k);
+                let (x1, y1) = (x + glyph.size.0 * k, y + glyph.size.1 * k);
+                let uv = glyph.uv;
+                out.extend_from_slice(&[
+                    vertex(x, y, uv[0], uv[1]),
+                    vertex(x1, y, uv[2], uv[1]),
+                    vertex(x1, y1, uv[2], uv[3]),
+                    vertex(x, y, uv[0], uv[1]),
+                    vertex(x1, y1, uv[2], uv[3]),
+                    vertex(x, y1, uv[0], uv[3]),
+                ]);
+            }
+            pen_x += glyph.advance * k;
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::ops::Range;
+
+use glm::Vec4;
+use winit::event::{ElementState, MouseButton};
+
+use crate::assets::TexturePtr;
+use crate::events::MyEvent;
+
+///Vertex of UI triangle list in pixels, mirrors inputs of `ui.vert`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UiVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+///Consecutive vertices sharing a texture
+#[derive(Debug, Clone)]
+pub struct UiBatch {
+    pub texture: TexturePtr,
+    pub vertices: Range<u32>,
+}
+
+///Triangles of one UI frame, see `RenderCommand::Ui`
+#[derive(Debug, Clone, Default)]
+pub struct UiFrame {
+    pub vertices: Vec<UiVertex>,
+    pub batches: Vec<UiBatch>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl Area {
+    fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+///Immediate mode UI drawn over the scene, usable from any system as a specs resource
+///
+///`EcsLayer` feeds it events first: mouse input over last frame's panels is consumed
+///and never reaches `WinitEvents`. Widgets are rebuilt every rated update.
+#[derive(Debug, Default)]
+pub struct Ui {
+    frame: UiFrame,
+    cursor: (f32, f32),
+    pressed: bool,
+    clicked: bool,
+    areas: Vec<Area>,
+    last_areas: Vec<Area>,
+}
+
+impl Ui {
+    ///Returns true if event is consumed by UI
+    pub fn handle_event(&mut self, event: &MyEvent) -> bool {
+        match event {
+            MyEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x as f32, position.y as f32);
+                false
+            }
+            MyEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                let hovered = self.is_hovered();
+                match state {
+                    ElementState::Pressed if hovered => {
+                        self.pressed = true;
+                        self.clicked = true;
+                    }
+                    ElementState::Released => self.pressed = false,
+                    _ => {}
+                }
+                hovered
+            }
+            MyEvent::MouseInput { .. } | MyEvent::MouseMotion { .. } => self.is_hovered(),
+            _ => false,
+        }
+    }
+
+    ///Cursor is over any panel of the last frame
+    pub fn is_hovered(&self) -> bool {
+        self.last_areas.iter().any(|a| a.contains(self.cursor))
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame.vertices.clear();
+        self.frame.batches.clear();
+        self.areas.clear();
+    }
+
+    pub fn end_frame(&mut self) {
+        self.clicked = false;
+        std::mem::swap(&mut self.areas, &mut self.last_areas);
+    }
+
+    pub fn frame(&self) -> &UiFrame {
+        &self.frame
+    }
+
+    ///Textured quad, `uv` is `[u0, v0, u1, v1]`
+    pub fn image(&mut self, x: f32, y: f32, w: f32, h: f32, texture: TexturePtr, uv: [f32; 4], color: &Vec4) {
+        let color = [color.x, color.y, color.z, color.w];
+        let vertex = |px: f32, py: f32, u: f32, v: f32| UiVertex { position: [px, py], uv: [u, v], color };
+        let (x1, y1) = (x + w, y + h);
+        let quad = [
+            vertex(x, y, uv[0], uv[1]),
+            vertex(x1, y, uv[2], uv[1]),
+            vertex(x1, y1, uv[2], uv[3]),
+            vertex(x, y, uv[0], uv[1]),
+            vertex(x1, y1, uv[2], uv[3]),
+            vertex(x, y1, uv[0], uv[3]),
+        ];
+        let start = self.frame.vertices.len() as u32;
+        self.frame.vertices.extend_from_slice(&quad);
+        let end = self.frame.vertices.len() as u32;
+        match self.frame.batches.last_mut() {
+            Some(batch) if batch.texture == texture => batch.vertices.end = end,
+            _ => self.frame.batches.push(UiBatch { texture, vertices: start..end }),
+        }
+    }
+
+    ///Flat colored quad
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: &Vec4) {
+        self.image(x, y, w, h, TexturePtr::default(), [0., 0., 1., 1.], color);
+    }
+
+    ///Background that captures mouse input
+    pub fn panel(&mut self, x: f32, y: f32, w: f32, h: f32, color: &Vec4) {
+        self.areas.push(Area { x, y, w, h });
+        self.rect(x, y, w, h, color);
+    }
+
+    ///Returns true when clicked this update
+    pub fn button(&mut self, x: f32, y: f32, w: f32, h: f32, color: &Vec4) -> bool {
+        let area = Area { x, y, w, h };
+        let hovered = area.contains(self.cursor);
+        let shade = if hovered && self.pressed { 0.6 } else if hovered { 1.2 } else { 1. };
+        self.panel(x, y, w, h, &glm::vec4(color.x * shade, color.y * shade, color.z * shade, color.w));
+        hovered && self.clicked
+    }
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+
+layout (set = 0, binding = 0) uniform texture2D u_input;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+layout (location = 0) out vec4 color;
+
+void main() {
+  color = texture(sampler2D(u_input, u_sampler), frag_uv);
+}
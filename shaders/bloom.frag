@@ -0,0 +1,34 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+
+layout (set = 0, binding = 0) uniform texture2D u_input;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+//see PostEffect
+layout (push_constant) uniform Post {
+  vec4 params;
+  vec2 texel;
+} post;
+
+layout (location = 0) out vec4 color;
+
+#define TAPS 4
+
+void main() {
+  vec4 base = texture(sampler2D(u_input, u_sampler), frag_uv);
+  float threshold = post.params.x;
+  vec2 spacing = post.texel * post.params.z / float(TAPS);
+  vec3 sum = vec3(0.0);
+  float weight = 0.0;
+  //gaussian over bright part only
+  for (int x = -TAPS; x <= TAPS; x++) {
+    for (int y = -TAPS; y <= TAPS; y++) {
+      float w = exp(-float(x * x + y * y) / float(TAPS * TAPS));
+      vec3 c = texture(sampler2D(u_input, u_sampler), frag_uv + vec2(x, y) * spacing).rgb;
+      sum += max(c - vec3(threshold), vec3(0.0)) * w;
+      weight += w;
+    }
+  }
+  color = vec4(base.rgb + sum / weight * post.params.y, base.a);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec4 color;
+
+//leading members of FrameState block, see one.frag
+layout (set = 0, binding = 0) uniform FrameState {
+  mat4 view;
+  mat4 proj;
+} frame;
+
+layout (location = 0) out gl_PerVertex {
+  vec4 gl_Position;
+};
+layout (location = 0) out vec4 frag_color;
+
+void main() {
+  gl_Position = frame.proj * frame.view * vec4(position, 1.0);
+  frag_color = color;
+}
@@ -0,0 +1,48 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+
+layout (set = 0, binding = 0) uniform texture2D u_input;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+//see PostEffect
+layout (push_constant) uniform Post {
+  vec4 params;
+  vec2 texel;
+} post;
+
+layout (location = 0) out vec4 color;
+
+#define EDGE_MIN (1.0 / 128.0)
+#define EDGE_MUL (1.0 / 8.0)
+#define SPAN_MAX 8.0
+
+float luma(vec3 rgb) {
+  return dot(rgb, vec3(0.299, 0.587, 0.114));
+}
+
+vec3 fetch(vec2 uv) {
+  return texture(sampler2D(u_input, u_sampler), uv).rgb;
+}
+
+void main() {
+  vec3 center = fetch(frag_uv);
+  float l_nw = luma(fetch(frag_uv + vec2(-1.0, -1.0) * post.texel));
+  float l_ne = luma(fetch(frag_uv + vec2(1.0, -1.0) * post.texel));
+  float l_sw = luma(fetch(frag_uv + vec2(-1.0, 1.0) * post.texel));
+  float l_se = luma(fetch(frag_uv + vec2(1.0, 1.0) * post.texel));
+  float l_m = luma(center);
+  float l_min = min(l_m, min(min(l_nw, l_ne), min(l_sw, l_se)));
+  float l_max = max(l_m, max(max(l_nw, l_ne), max(l_sw, l_se)));
+
+  //blur along the edge
+  vec2 dir = vec2(-((l_nw + l_ne) - (l_sw + l_se)), (l_nw + l_sw) - (l_ne + l_se));
+  float reduce = max((l_nw + l_ne + l_sw + l_se) * 0.25 * EDGE_MUL, EDGE_MIN);
+  float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+  dir = clamp(dir * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * post.texel;
+
+  vec3 a = 0.5 * (fetch(frag_uv + dir * (1.0 / 3.0 - 0.5)) + fetch(frag_uv + dir * (2.0 / 3.0 - 0.5)));
+  vec3 b = a * 0.5 + 0.25 * (fetch(frag_uv - dir * 0.5) + fetch(frag_uv + dir * 0.5));
+  float l_b = luma(b);
+  color = vec4((l_b < l_min || l_b > l_max) ? a : b, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+
+layout (set = 0, binding = 0) uniform texture2D u_input;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+//see PostEffect
+layout (push_constant) uniform Post {
+  vec4 params;
+  vec2 texel;
+} post;
+
+layout (location = 0) out vec4 color;
+
+void main() {
+  vec4 linear = texture(sampler2D(u_input, u_sampler), frag_uv);
+  color = vec4(pow(linear.rgb, vec3(1.0 / post.params.x)), linear.a);
+}
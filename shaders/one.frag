@@ -1,10 +1,144 @@
 #version 450
+#define MAX_LIGHTS 16
+#define MAX_CASCADES 4
+#define LIGHT_POINT 0
+#define LIGHT_DIRECTIONAL 1
+#define LIGHT_SPOT 2
+#define DEBUG_NORMALS 2
+#define DEBUG_UV 3
+#define DEBUG_DEPTH 4
+
+struct Light {
+  //xyz - position, w - kind
+  vec4 position;
+  //xyz - direction, w - range
+  vec4 direction;
+  //rgb - color, a - intensity
+  vec4 color;
+  //x - cos inner, y - cos outer
+  vec4 cone;
+};
+
+layout (set = 0, binding = 0) uniform FrameState {
+  mat4 view;
+  mat4 proj;
+  vec4 camera_position;
+  vec4 ambient;
+  uvec4 light_count;
+  Light lights[MAX_LIGHTS];
+  mat4 shadow_view_proj[MAX_CASCADES];
+  vec4 cascade_splits;
+  //x - cascade count, y - bias
+  vec4 shadow_params;
+  //x - debug mode, y - near, z - far
+  vec4 debug;
+} frame;
+
 layout (location = 0) in vec2 frag_uv;
-layout (location = 1) in vec3 light_intensity;
+layout (location = 1) in vec3 frag_world;
+layout (location = 2) in vec3 frag_normal;
+layout (location = 3) flat in vec4 frag_color;
+layout (location = 4) flat in vec4 frag_params;
+
+layout (set = 1, binding = 0) uniform texture2D u_texture;
+layout (set = 1, binding = 1) uniform sampler u_sampler;
+
+layout (set = 2, binding = 0) uniform texture2DArray u_shadow;
+layout (set = 2, binding = 1) uniform samplerShadow u_shadow_sampler;
 
 layout (location = 0) out vec4 color;
 
+const float specular_strength = 0.5;
+
+//1 - lit, 0 - fully in shadow
+float shadow_factor(vec3 normal, vec3 to_light) {
+  int cascades = int(frame.shadow_params.x);
+  if (cascades == 0) {
+    return 1.0;
+  }
+  float depth = -(frame.view * vec4(frag_world, 1.0)).z;
+  int cascade = cascades - 1;
+  for (int i = 0; i < cascades; i++) {
+    if (depth < frame.cascade_splits[i]) {
+      cascade = i;
+      break;
+    }
+  }
+  vec4 light_space = frame.shadow_view_proj[cascade] * vec4(frag_world, 1.0);
+  vec3 ndc = light_space.xyz / light_space.w;
+  vec2 uv = ndc.xy * 0.5 + 0.5;
+  if (ndc.z > 1.0 || any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+    return 1.0;
+  }
+  float bias = max(frame.shadow_params.y * (1.0 - dot(normal, to_light)), frame.shadow_params.y * 0.1);
+  vec2 texel = 1.0 / vec2(textureSize(sampler2DArrayShadow(u_shadow, u_shadow_sampler), 0).xy);
+  //3x3 pcf
+  float lit = 0.0;
+  for (int x = -1; x <= 1; x++) {
+    for (int y = -1; y <= 1; y++) {
+      lit += texture(
+        sampler2DArrayShadow(u_shadow, u_shadow_sampler),
+        vec4(uv + vec2(x, y) * texel, float(cascade), ndc.z - bias)
+      );
+    }
+  }
+  return lit / 9.0;
+}
+
+vec3 blinn_phong(Light light, vec3 normal, vec3 to_view, float shininess, bool shadow_caster) {
+  int kind = int(light.position.w);
+  vec3 to_light;
+  float attenuation = 1.0;
+  if (kind == LIGHT_DIRECTIONAL) {
+    to_light = normalize(-light.direction.xyz);
+    if (shadow_caster) {
+      attenuation = shadow_factor(normal, to_light);
+    }
+  } else {
+    vec3 diff = light.position.xyz - frag_world;
+    float dist = length(diff);
+    to_light = diff / dist;
+    float falloff = clamp(1.0 - pow(dist / light.direction.w, 4.0), 0.0, 1.0);
+    attenuation = falloff * falloff / (dist * dist * 0.001 + 1.0);
+    if (kind == LIGHT_SPOT) {
+      float theta = dot(to_light, normalize(-light.direction.xyz));
+      attenuation *= clamp((theta - light.cone.y) / max(light.cone.x - light.cone.y, 0.0001), 0.0, 1.0);
+    }
+  }
+  float diffuse = max(dot(normal, to_light), 0.0);
+  vec3 halfway = normalize(to_light + to_view);
+  float specular = diffuse > 0.0 ? pow(max(dot(normal, halfway), 0.0), shininess) : 0.0;
+  return (diffuse + specular_strength * specular) * light.color.rgb * light.color.a * attenuation;
+}
+
 void main() {
-  vec4 tex_color = vec4(light_intensity, 1.0) * vec4(1, 1, 1, 0);
-  color = tex_color;
-}
\ No newline at end of file
+  vec3 normal = normalize(frag_normal);
+  int debug_mode = int(frame.debug.x);
+  if (debug_mode == DEBUG_NORMALS) {
+    color = vec4(normal * 0.5 + 0.5, 1.0);
+    return;
+  }
+  if (debug_mode == DEBUG_UV) {
+    color = vec4(fract(frag_uv), 0.0, 1.0);
+    return;
+  }
+  if (debug_mode == DEBUG_DEPTH) {
+    float view_depth = -(frame.view * vec4(frag_world, 1.0)).z;
+    float depth = clamp((view_depth - frame.debug.y) / (frame.debug.z - frame.debug.y), 0.0, 1.0);
+    color = vec4(vec3(1.0 - sqrt(depth)), 1.0);
+    return;
+  }
+  vec3 to_view = normalize(frame.camera_position.xyz - frag_world);
+  vec3 lit = frame.ambient.rgb;
+  //only first directional light has shadow map
+  bool shadow_caster = true;
+  for (uint i = 0; i < min(frame.light_count.x, uint(MAX_LIGHTS)); i++) {
+    Light light = frame.lights[i];
+    lit += blinn_phong(light, normal, to_view, frag_params.x, shadow_caster);
+    if (int(light.position.w) == LIGHT_DIRECTIONAL) {
+      shadow_caster = false;
+    }
+  }
+  vec4 albedo = frag_color * texture(sampler2D(u_texture, u_sampler), frag_uv);
+  color = vec4(lit * albedo.rgb, albedo.a);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout (location = 0) out gl_PerVertex {
+  vec4 gl_Position;
+};
+layout (location = 0) out vec2 frag_uv;
+
+void main() {
+  //full-screen triangle
+  frag_uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+  gl_Position = vec4(frag_uv * 2.0 - 1.0, 0.0, 1.0);
+}
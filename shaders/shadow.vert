@@ -0,0 +1,16 @@
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in mat4 model;
+
+layout (push_constant) uniform Cascade {
+  mat4 light_view_proj;
+} cascade;
+
+layout (location = 0) out gl_PerVertex {
+  vec4 gl_Position;
+};
+
+void main() {
+  gl_Position = cascade.light_view_proj * model * vec4(position, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout (location = 0) in vec3 frag_direction;
+
+layout (set = 1, binding = 0) uniform textureCube u_sky;
+layout (set = 1, binding = 1) uniform sampler u_sampler;
+
+layout (location = 0) out vec4 color;
+
+void main() {
+  color = vec4(texture(samplerCube(u_sky, u_sampler), normalize(frag_direction)).rgb, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+//leading members of FrameState block, see one.frag
+layout (set = 0, binding = 0) uniform FrameState {
+  mat4 view;
+  mat4 proj;
+} frame;
+
+layout (location = 0) out gl_PerVertex {
+  vec4 gl_Position;
+};
+layout (location = 0) out vec3 frag_direction;
+
+void main() {
+  //fullscreen triangle at far plane
+  vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+  gl_Position = vec4(position, 1.0, 1.0);
+  vec4 eye = inverse(frame.proj) * gl_Position;
+  frag_direction = transpose(mat3(frame.view)) * (eye.xyz / eye.w);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+layout (location = 1) in vec4 frag_color;
+
+//white texels, glyph coverage in alpha
+layout (set = 0, binding = 0) uniform texture2D u_atlas;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+layout (location = 0) out vec4 color;
+
+void main() {
+  float coverage = texture(sampler2D(u_atlas, u_sampler), frag_uv).a;
+  color = vec4(frag_color.rgb, frag_color.a * coverage);
+}
@@ -0,0 +1,28 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+
+layout (set = 0, binding = 0) uniform texture2D u_input;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+//see PostEffect
+layout (push_constant) uniform Post {
+  vec4 params;
+  vec2 texel;
+} post;
+
+layout (location = 0) out vec4 color;
+
+vec3 aces(vec3 x) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main() {
+  vec4 hdr = texture(sampler2D(u_input, u_sampler), frag_uv);
+  color = vec4(aces(hdr.rgb * post.params.x), hdr.a);
+}
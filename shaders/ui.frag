@@ -0,0 +1,13 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+layout (location = 1) in vec4 frag_color;
+
+layout (set = 0, binding = 0) uniform texture2D u_texture;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+layout (location = 0) out vec4 color;
+
+void main() {
+  color = frag_color * texture(sampler2D(u_texture, u_sampler), frag_uv);
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in vec4 color;
+
+layout (push_constant) uniform Screen {
+  vec2 size;
+} screen;
+
+layout (location = 0) out gl_PerVertex {
+  vec4 gl_Position;
+};
+layout (location = 0) out vec2 frag_uv;
+layout (location = 1) out vec4 frag_color;
+
+void main() {
+  //pixels, origin at top left
+  gl_Position = vec4(position / screen.size * 2.0 - 1.0, 0.0, 1.0);
+  frag_uv = uv;
+  frag_color = color;
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout (location = 0) in vec2 frag_uv;
+
+layout (set = 0, binding = 0) uniform texture2D u_input;
+layout (set = 0, binding = 1) uniform sampler u_sampler;
+
+//see PostEffect
+layout (push_constant) uniform Post {
+  vec4 params;
+  vec2 texel;
+} post;
+
+layout (location = 0) out vec4 color;
+
+void main() {
+  vec4 input_color = texture(sampler2D(u_input, u_sampler), frag_uv);
+  //0 in the center, 1 in corners
+  float dist = length(frag_uv - 0.5) / 0.70710678;
+  float darken = post.params.x * smoothstep(post.params.y, 1.0, dist);
+  color = vec4(input_color.rgb * (1.0 - darken), input_color.a);
+}